@@ -0,0 +1,114 @@
+//! Removes terminal escape sequences from the input, for `--strip-ansi`.
+//!
+//! Colored CI logs and sessions captured with `script` are full of escape
+//! sequences that break up the text, so a query can fail to match what is
+//! plainly visible on screen, and printed lines come out garbled. The
+//! `StripAnsi` reader drops the sequences from the input before it is split
+//! into lines, so matching and printing only ever see the visible text.
+
+use std::io::{self, Read};
+
+/// Where the scanner is within an escape sequence.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    /// Outside of any escape sequence.
+    Text,
+    /// After an `ESC` byte.
+    Escape,
+    /// Inside an `ESC` sequence with intermediate bytes, such as `ESC ( B`.
+    Intermediate,
+    /// Inside a control sequence (`ESC [`), such as a color change.
+    Csi,
+    /// Inside an operating system command (`ESC ]`), such as a window title.
+    Osc,
+    /// After an `ESC` inside an operating system command, which ends it.
+    OscEscape,
+}
+
+/// A reader that drops terminal escape sequences from the bytes of another
+/// reader. Sequences split across reads are handled, since the scanner keeps
+/// its state between them.
+pub struct StripAnsi<R> {
+    /// The underlying reader.
+    inner: R,
+    /// The scanner state at the end of the last read.
+    state: State,
+}
+
+impl<R: Read> StripAnsi<R> {
+    /// Creates a reader stripping the escape sequences from `inner`.
+    pub fn new(inner: R) -> Self {
+        StripAnsi {
+            inner,
+            state: State::Text,
+        }
+    }
+}
+
+impl<R: Read> Read for StripAnsi<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let read = self.inner.read(buf)?;
+            if read == 0 {
+                return Ok(0);
+            }
+
+            // Kept bytes are moved to the front of `buf` in place.
+            let mut kept = 0;
+            for i in 0..read {
+                let byte = buf[i];
+                self.state = match (self.state, byte) {
+                    (State::Text, 0x1B) => State::Escape,
+                    (State::Text, _) => {
+                        buf[kept] = byte;
+                        kept += 1;
+                        State::Text
+                    }
+                    (State::Escape, b'[') => State::Csi,
+                    (State::Escape, b']') => State::Osc,
+                    (State::Escape | State::Intermediate, 0x20..=0x2F) => State::Intermediate,
+                    (State::Escape | State::Intermediate, _) => State::Text,
+                    (State::Csi, 0x40..=0x7E) => State::Text,
+                    (State::Csi, _) => State::Csi,
+                    (State::Osc, 0x07) => State::Text,
+                    (State::Osc, 0x1B) => State::OscEscape,
+                    (State::Osc, _) => State::Osc,
+                    (State::OscEscape, _) => State::Text,
+                };
+            }
+            // A read that held nothing but escape sequences must not be
+            // mistaken for the end of the input.
+            if kept > 0 {
+                return Ok(kept);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strips_escape_sequences() {
+        let input = "\x1b[1;31merror\x1b[0m: \x1b]0;title\x07bad \x1b(Bthing\x1b]8;;u\x1b\\\n";
+        let mut output = String::new();
+        StripAnsi::new(input.as_bytes())
+            .read_to_string(&mut output)
+            .unwrap();
+        assert_eq!(output, "error: bad thing\n");
+    }
+
+    #[test]
+    fn test_keeps_state_between_reads() {
+        // Reading one byte at a time splits every sequence.
+        let input = "a\x1b[32mb\x1b[0mc";
+        let mut reader = StripAnsi::new(input.as_bytes());
+        let mut output = Vec::new();
+        let mut byte = [0; 1];
+        while reader.read(&mut byte).unwrap() == 1 {
+            output.push(byte[0]);
+        }
+        assert_eq!(output, b"abc");
+    }
+}
@@ -0,0 +1,132 @@
+//! Suppresses known matches recorded in a baseline file.
+//!
+//! `--write-baseline` records every matching line of a run in the file given
+//! with `--baseline`, as the path of its file and a hash of its content.
+//! Later runs with `--baseline` then skip those lines and report only new
+//! occurrences, which lets a pattern be banned in a large legacy codebase
+//! without fixing every existing use first.
+//!
+//! Lines are identified by their content rather than their line number, so
+//! that edits elsewhere in a file do not bring known matches back. A line
+//! that occurs several times is suppressed as many times as it was recorded.
+
+use crate::messages::{MessageId, message};
+use crate::report::fnv1a;
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Write},
+};
+
+/// The first line of a baseline file.
+const HEADER: &str = "# grep-rust baseline";
+
+/// The known matches of a baseline file, by path.
+pub struct Baseline {
+    files: HashMap<String, KnownMatches>,
+}
+
+/// The known matches of a single file.
+#[derive(Default)]
+pub struct KnownMatches {
+    /// How many times each line content hash was recorded.
+    counts: HashMap<u64, usize>,
+}
+
+impl Baseline {
+    /// Reads a baseline file written by `--write-baseline`.
+    ///
+    /// # Returns
+    /// The baseline, or an error if the file cannot be read or contains a
+    /// malformed entry.
+    pub fn load(path: &str) -> io::Result<Baseline> {
+        let mut files: HashMap<String, KnownMatches> = HashMap::new();
+        for (i, line) in fs::read_to_string(path)?.lines().enumerate() {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let entry = line
+                .split_once(' ')
+                .and_then(|(hash, file)| Some((u64::from_str_radix(hash, 16).ok()?, file)));
+            let Some((hash, file)) = entry else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    message(MessageId::InvalidBaseline, &[&path, &(i + 1)]),
+                ));
+            };
+            *files
+                .entry(file.to_string())
+                .or_default()
+                .counts
+                .entry(hash)
+                .or_default() += 1;
+        }
+        Ok(Baseline { files })
+    }
+
+    /// Takes the known matches of the file at `path` out of the baseline.
+    ///
+    /// Files without any recorded match get an empty set.
+    pub fn take_file(&mut self, path: &str) -> KnownMatches {
+        self.files.remove(path).unwrap_or_default()
+    }
+}
+
+impl KnownMatches {
+    /// Checks whether `line` is a known match, using up one of its recorded
+    /// occurrences if so.
+    pub fn suppress(&mut self, line: &str) -> bool {
+        match self.counts.get_mut(&line_hash(line)) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Returns the hash identifying a matching line in a baseline.
+///
+/// Leading and trailing whitespace is ignored, so re-indenting a line keeps
+/// it suppressed.
+pub fn line_hash(line: &str) -> u64 {
+    fnv1a(line.trim().as_bytes())
+}
+
+/// Writes a baseline file recording the given matches.
+///
+/// # Arguments
+/// * `out` - The writer to write the baseline to.
+/// * `matches` - The path and line hash of every matching line.
+pub fn write_baseline(out: &mut impl Write, matches: &[(&str, u64)]) -> io::Result<()> {
+    writeln!(out, "{}", HEADER)?;
+    for (path, hash) in matches {
+        writeln!(out, "{:016x} {}", hash, path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+
+        let hash = line_hash("x.unwrap();");
+        let mut out = Vec::new();
+        write_baseline(&mut out, &[("src/a.rs", hash), ("src/a.rs", hash)]).unwrap();
+        fs::write(path, out).unwrap();
+
+        let mut baseline = Baseline::load(path).unwrap();
+        let mut known = baseline.take_file("src/a.rs");
+        assert!(known.suppress("    x.unwrap();"));
+        assert!(known.suppress("x.unwrap();"));
+        assert!(!known.suppress("x.unwrap();"));
+        assert!(!baseline.take_file("src/b.rs").suppress("x.unwrap();"));
+    }
+}
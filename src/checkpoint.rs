@@ -0,0 +1,108 @@
+//! Records the progress of a long search, for `--checkpoint` and `--resume`.
+//!
+//! With `--checkpoint FILE`, the path of every file whose search completed
+//! is appended to `FILE` as soon as its results are output: right after it
+//! is searched, or at the end of the run for results held back until then,
+//! such as those of `--sort-lines`, `--count` or `--format json`. A later run with `--resume`
+//! reads the file back and skips those paths, so a search that was
+//! interrupted, or killed along with its machine, continues where it stopped
+//! instead of starting from zero. Matches in the skipped files are not
+//! reported again; they were output by the earlier run.
+
+use std::{
+    collections::HashSet,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+};
+
+/// The first line of a checkpoint file.
+const HEADER: &str = "# grep-rust checkpoint";
+
+/// The completed paths of a search, and the file they are recorded in.
+pub struct Checkpoint {
+    /// The paths completed by earlier runs.
+    completed: HashSet<String>,
+    /// The checkpoint file, opened for appending.
+    file: File,
+}
+
+impl Checkpoint {
+    /// Opens the checkpoint file at `path`.
+    ///
+    /// # Arguments
+    /// * `path` - The path of the checkpoint file.
+    /// * `resume` - Whether to continue from the paths already recorded in
+    ///   the file, rather than starting a new one.
+    ///
+    /// # Returns
+    /// The checkpoint, or an error if the file cannot be read or written.
+    pub fn open(path: &str, resume: bool) -> io::Result<Checkpoint> {
+        let mut completed = HashSet::new();
+        if resume {
+            match fs::read_to_string(path) {
+                Ok(text) => completed.extend(
+                    text.lines()
+                        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                        .map(str::to_string),
+                ),
+                // Resuming a search that never got to write its checkpoint
+                // is starting it.
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        let mut file = if resume {
+            OpenOptions::new().create(true).append(true).open(path)?
+        } else {
+            File::create(path)?
+        };
+        if file.metadata()?.len() == 0 {
+            writeln!(file, "{}", HEADER)?;
+        }
+        Ok(Checkpoint { completed, file })
+    }
+
+    /// Returns whether the search of `path` completed in an earlier run.
+    pub fn is_completed(&self, path: &str) -> bool {
+        self.completed.contains(path)
+    }
+
+    /// Records that the search of `path` completed.
+    ///
+    /// Each path is written through to the file right away, so that the
+    /// checkpoint survives the process being killed.
+    pub fn complete(&mut self, path: &str) -> io::Result<()> {
+        writeln!(self.file, "{}", path)?;
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resume() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("checkpoint");
+        let path = path.to_str().unwrap();
+
+        let mut checkpoint = Checkpoint::open(path, false).unwrap();
+        assert!(!checkpoint.is_completed("a.log"));
+        checkpoint.complete("a.log").unwrap();
+        drop(checkpoint);
+
+        let mut checkpoint = Checkpoint::open(path, true).unwrap();
+        assert!(checkpoint.is_completed("a.log"));
+        assert!(!checkpoint.is_completed("b.log"));
+        checkpoint.complete("b.log").unwrap();
+        drop(checkpoint);
+
+        assert_eq!(
+            fs::read_to_string(path).unwrap(),
+            format!("{}\na.log\nb.log\n", HEADER)
+        );
+        assert!(!Checkpoint::open(path, false).unwrap().is_completed("a.log"));
+    }
+}
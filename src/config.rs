@@ -5,7 +5,12 @@
 //! command-line inputs into a strongly-typed struct, making argument
 //! handling robust and easy to manage.
 
-use clap::{Parser, arg, command};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum, parser::ValueSource};
+
+use crate::log_format;
+use crate::messages::{MessageId, message};
+use crate::preset;
+use std::{ffi::OsString, fmt, time::Duration};
 
 /// Represents the configuration for the `grep-rust` application, derived
 /// directly from command-line arguments.
@@ -18,15 +23,57 @@ use clap::{Parser, arg, command};
     author,
     version,
     about,
-    long_about = "This program is a basic implementation of the 'grep' utility. It allows users to search for a specific text pattern within a given file. Features include case-insensitive search, line numbering, and printing lines before/after a match (context)."
+    long_about = "This program is a basic implementation of the 'grep' utility. It allows users to search for a specific text pattern within a given file. Features include case-insensitive search, line numbering, and printing lines before/after a match (context).",
+    subcommand_negates_reqs = true
 )]
-
 pub struct Config {
-    /// The string pattern to search for within the specified file.
+    /// The string pattern to search for within the specified file. When
+    /// patterns are given with `-e`, or with `--undo`, this is the first file
+    /// path instead.
+    #[arg(
+        required_unless_present_any = ["patterns", "presets", "undo"],
+        default_value = "",
+        hide_default_value = true
+    )]
     pub query: String,
 
-    /// The path to the file where the search operation will be performed.
-    pub file_path: String,
+    /// Searches for `PATTERN`; can be given several times to search for any
+    /// of them. A pattern may start with modifiers that override `-i` and
+    /// `-w` for it alone: `(?i)` or `(?-i)` to ignore case or not, `(?w)` or
+    /// `(?-w)` to match whole words or not, combined as in `(?i-w)`.
+    #[arg(short = 'e', long = "regexp", value_name = "PATTERN")]
+    pub patterns: Vec<String>,
+
+    /// Searches the raw bytes of the files for `HEX`, e.g. `'DE AD BE EF'`,
+    /// with `??` matching any byte, and prints every match in an `xxd`-style
+    /// hex dump instead of as lines.
+    #[arg(
+        long,
+        value_name = "HEX",
+        value_parser = parse_hex_pattern,
+        conflicts_with_all = ["patterns", "presets", "replace", "undo", "diff", "staged"]
+    )]
+    pub hex_pattern: Option<HexPattern>,
+
+    /// Searches for one of the built-in patterns, such as email addresses
+    /// or UUIDs; can be given several times, and combined with `-e`. Like
+    /// `-e`, this makes every positional argument a file path.
+    #[arg(long = "preset", value_enum, value_name = "NAME")]
+    pub presets: Vec<Preset>,
+
+    /// The paths to the files where the search operation will be performed.
+    /// When more than one file is given, every output line is prefixed
+    /// with the name of the file it came from. With `--diff` or `--staged`,
+    /// they limit the files taken from git.
+    #[arg(required_unless_present_any = [
+        "diff",
+        "staged",
+        "patterns",
+        "presets",
+        "undo",
+        "hex_pattern"
+    ])]
+    pub file_paths: Vec<String>,
 
     /// Flag to enable case-insensitive searching. If set, the search
     /// will ignore differences in letter casing.
@@ -35,7 +82,7 @@ pub struct Config {
 
     /// Flag to enable line numbering in the output. If set, each matching
     /// line (and its context) will be prefixed with its line number in the file.
-    #[arg(short, long)]
+    #[arg(short = 'n', long)]
     pub line_number: bool,
 
     /// Flag to enable word-only matching. The pattern will only match
@@ -44,6 +91,25 @@ pub struct Config {
     #[arg(short, long)]
     pub word_regexp: bool,
 
+    /// Flag to only match whole lines. The pattern must match from the start
+    /// to the end of a line.
+    #[arg(short = 'x', long)]
+    pub line_regexp: bool,
+
+    /// Flag to only match the pattern at the start of a line, as if it
+    /// began with `^`.
+    #[arg(long)]
+    pub starts_with: bool,
+
+    /// Flag to only match the pattern at the end of a line, as if it ended
+    /// with `$`.
+    #[arg(long)]
+    pub ends_with: bool,
+
+    /// Flag to select the lines that do not match instead of those that do.
+    #[arg(short = 'v', long)]
+    pub invert_match: bool,
+
     /// Specifies the number of lines to print before a matching line.
     /// This provides "leading context" for matches. If not specified, defaults to 0.
     #[arg(short = 'B', long, value_name = "NUM")]
@@ -53,6 +119,1297 @@ pub struct Config {
     /// This provides "trailing context" for matches. If not specified, defaults to 0.
     #[arg(short = 'A', long, value_name = "NUM")]
     pub after_context: Option<usize>,
+
+    /// Flag to not count blank lines towards `-A` and `-B`. They are still
+    /// printed when they lie between counted context lines.
+    #[arg(long)]
+    pub context_skip_blank: bool,
+
+    /// Flag to not count lines holding only a comment towards `-A` and `-B`,
+    /// so that context shows that many lines of code. The comment syntax is
+    /// chosen by file extension, as for `--only-comments`.
+    #[arg(long)]
+    pub context_skip_comments: bool,
+
+    /// Flag to print only the matched parts of matching lines, each on its
+    /// own output line. Context lines are not printed in this mode.
+    #[arg(short = 'o', long)]
+    pub only_matching: bool,
+
+    /// Flag to print the byte offset in the file before each output line.
+    /// With `--only-matching`, the offset of each match is printed instead.
+    #[arg(short = 'b', long)]
+    pub byte_offset: bool,
+
+    /// Flag to print the matching lines of all files sorted lexically, once
+    /// the search is done, instead of in the order they were found.
+    #[arg(long)]
+    pub sort_lines: bool,
+
+    /// Flag to print each distinct line only once with `--sort-lines`.
+    #[arg(long, requires = "sort_lines")]
+    pub unique: bool,
+
+    /// Flag to print only the number of matching lines instead of the lines
+    /// themselves. When several files are searched, each count is printed as
+    /// `path:count`.
+    #[arg(short, long)]
+    pub count: bool,
+
+    /// Flag to also list files with zero matches when `--count` is combined
+    /// with multiple files. By default such files are left out of the output.
+    #[arg(long, requires = "count")]
+    pub include_zero: bool,
+
+    /// Stops the whole search after this many matching lines have been
+    /// found in total, across all files. Trailing context of the last
+    /// match is still printed.
+    #[arg(long, value_name = "NUM")]
+    pub max_total: Option<usize>,
+
+    /// Draws a bar chart of the match counts per file, or per directory with
+    /// `--histogram=dir`, instead of printing the matching lines.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "BY",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "file",
+        conflicts_with = "count"
+    )]
+    pub histogram: Option<HistogramBy>,
+
+    /// Prints only the `NUM` files with the most matching lines, with their
+    /// counts, instead of the matching lines.
+    #[arg(long, value_name = "NUM", conflicts_with_all = ["count", "histogram"])]
+    pub top: Option<usize>,
+
+    /// Flag to print how often each distinct match occurs across all files,
+    /// most frequent first, instead of the matching lines. This does in one
+    /// pass what `grep -o | sort | uniq -c | sort -rn` does.
+    #[arg(
+        long,
+        conflicts_with_all = ["count", "histogram", "top", "sort_lines", "invert_match", "format"]
+    )]
+    pub count_unique: bool,
+
+    /// Also writes the matches to `FILE` in Vim's quickfix format,
+    /// `file:line:column: text`, for loading with `:cfile`.
+    #[arg(long, value_name = "FILE")]
+    pub quickfix: Option<String>,
+
+    /// Flag to print file paths as absolute paths, e.g. in recursive
+    /// results, instead of as they were given or found.
+    #[arg(long, conflicts_with = "relative_to")]
+    pub absolute_path: bool,
+
+    /// Prints file paths relative to the directory `DIR`, e.g. the root of
+    /// a repository, wherever the search is run from.
+    #[arg(long, value_name = "DIR")]
+    pub relative_to: Option<String>,
+
+    /// How printed file paths are abbreviated: `short` leaves out the
+    /// directories between the first and the last component, and `tilde`
+    /// writes the home directory as `~`.
+    #[arg(long, value_enum, value_name = "STYLE", default_value_t = PathStyle::Full)]
+    pub path_style: PathStyle,
+
+    /// Runs `CMD` through the shell for every matching file, with `{}`
+    /// replaced by the file's path, like `find -exec`. `{path}`, `{line}`,
+    /// `{column}`, `{text}` and `{match}` pass the first match, each quoted
+    /// for the shell. Without any of them, the path is appended to `CMD`. A
+    /// command that fails is reported, and the exit status is then 2.
+    #[arg(long, value_name = "CMD")]
+    pub exec: Option<String>,
+
+    /// Flag to run the `--exec` command for every matching line instead of
+    /// once per matching file.
+    #[arg(long, requires = "exec", conflicts_with = "files_with_matches")]
+    pub exec_per_match: bool,
+
+    /// Flag to keep watching the searched files after the search, and to
+    /// search again whenever one of them changes, appears or disappears.
+    #[arg(long, conflicts_with_all = ["replace", "undo", "diff", "staged"])]
+    pub watch: bool,
+
+    /// Flag to clear the screen before every new search of `--watch`.
+    #[arg(long, requires = "watch")]
+    pub clear: bool,
+
+    /// Flag to print only the names of files that contain at least one
+    /// match. Each file is only read up to its first match.
+    #[arg(short = 'l', long, conflicts_with = "count")]
+    pub files_with_matches: bool,
+
+    /// Flag to terminate each file name printed by `--files-with-matches`
+    /// with a NUL byte instead of a newline, so the output can be safely
+    /// consumed by `xargs -0` even when file names contain newlines. File
+    /// names in front of matching lines are followed by a NUL byte as well.
+    #[arg(short = '0', long)]
+    pub null: bool,
+
+    /// Specifies the output format. `text` prints the matching lines, `json`
+    /// prints them as JSON lines with the byte and column spans of each
+    /// match, while `codequality` and `junit` write a GitLab Code Quality or
+    /// JUnit XML report for CI.
+    #[arg(long, value_enum, value_name = "FORMAT", default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    /// Flag to print results in the layout Emacs' `grep-mode` parses: the
+    /// file name, a NUL byte, the line number and the line, without colors
+    /// or headings, so that `M-x grep` can run `grep-rust`.
+    #[arg(long)]
+    pub emacs: bool,
+
+    /// Replaces every match with `TEXT` in the files themselves, instead of
+    /// printing the matching lines. Files that are not valid UTF-8 are left
+    /// unchanged. Every match in the file is replaced, so the options that
+    /// limit the search to some lines or parts of lines, the options that
+    /// only report matches, and the options that change how the input is
+    /// read cannot be combined with it.
+    #[arg(
+        long,
+        value_name = "TEXT",
+        group = "edit",
+        conflicts_with_all = [
+            "invert_match", "from", "until", "since", "until_time", "log_format", "level",
+            "json_path", "csv", "strip_tags", "section", "markdown_scope", "diff", "staged",
+            "baseline", "only_comments", "only_strings", "max_total", "count",
+            "files_with_matches", "quiet", "top", "histogram", "strip_ansi", "normalize",
+            "record_separator", "max_line_length", "chunked", "pre_glob",
+        ]
+    )]
+    pub replace: Option<String>,
+
+    /// Flag to print the changes `--replace` would make as a colored unified
+    /// diff, without writing anything.
+    #[arg(long, requires = "replace")]
+    pub preview: bool,
+
+    /// Flag to show each change `--replace` would make and ask whether to
+    /// make it: `y` makes it, `n` skips it, `a` makes it and all the
+    /// remaining ones, and `q` skips it and all the remaining ones.
+    #[arg(long, requires = "replace", conflicts_with = "preview")]
+    pub confirm: bool,
+
+    /// Flag to give every `--replace` replacement the case of the match it
+    /// replaces, so that replacing `color` with `colour` in a case-insensitive
+    /// search turns `Color` into `Colour` and `COLOR` into `COLOUR`.
+    #[arg(long, requires = "replace")]
+    pub preserve_case: bool,
+
+    /// Keeps the original of every file `--replace` changes next to it, with
+    /// `SUFFIX` appended to its name, e.g. `.bak`. It needs `--replace` or
+    /// `--undo`.
+    #[arg(long, value_name = "SUFFIX", requires = "edit")]
+    pub backup: Option<String>,
+
+    /// Flag to restore the files from the backups `--backup` kept, instead
+    /// of searching them. Every path given is a file to restore; there is
+    /// no query.
+    #[arg(
+        long,
+        group = "edit",
+        requires = "backup",
+        conflicts_with_all = ["replace", "patterns"]
+    )]
+    pub undo: bool,
+
+    /// Flag to leave out the warnings about files and directories that do
+    /// not exist or cannot be read. Such files are skipped either way, and
+    /// the exit status is still 2.
+    #[arg(short = 's', long)]
+    pub no_messages: bool,
+
+    /// Flag to suppress all normal output. The exit status tells whether a
+    /// match was found, and the search stops at the very first match.
+    #[arg(short, long)]
+    pub quiet: bool,
+
+    /// Flag to exit with status 1 when a match is found and with status 0
+    /// when nothing matched, for CI checks that ban a pattern.
+    #[arg(long, conflicts_with = "fail_if_no_match")]
+    pub fail_on_match: bool,
+
+    /// Flag to exit with status 1 when nothing matched and with status 0
+    /// when a match is found. This is the default, spelled out for CI checks
+    /// that require a pattern.
+    #[arg(long)]
+    pub fail_if_no_match: bool,
+
+    /// Exits with status 1 only if more than `NUM` matching lines are found,
+    /// to ratchet down the uses of a legacy pattern in CI.
+    #[arg(long, value_name = "NUM", conflicts_with_all = ["fail_on_match", "fail_if_no_match"])]
+    pub max_allowed: Option<usize>,
+
+    /// Searches only within ranges of lines starting at a line containing
+    /// `PATTERN`, like a sed address range. Combine with an empty query to
+    /// print the ranges whole.
+    #[arg(long, value_name = "PATTERN")]
+    pub from: Option<String>,
+
+    /// Ends each `--from` range at the next line containing `PATTERN`, which
+    /// is still part of the range. Without `--from`, the search stops there.
+    #[arg(long, value_name = "PATTERN")]
+    pub until: Option<String>,
+
+    /// Searches only the log lines from `TIME` on, going by the timestamp
+    /// each line starts with. `TIME` is a duration counted back from now,
+    /// such as `1h`, or a timestamp such as `2024-01-31T12:00:00`.
+    #[arg(long, value_name = "TIME")]
+    pub since: Option<String>,
+
+    /// Searches only the log lines from before `TIME`, going by the
+    /// timestamp each line starts with, as for `--since`.
+    #[arg(long, value_name = "TIME")]
+    pub until_time: Option<String>,
+
+    /// Reads the timestamps of `--since` and `--until-time` in `FORMAT`,
+    /// e.g. `%d.%m.%Y %H:%M`, instead of trying ISO 8601, syslog and access
+    /// log timestamps. Knows `%Y %m %d %e %H %M %S %b %z`.
+    #[arg(long, value_name = "FORMAT")]
+    pub time_format: Option<String>,
+
+    /// Reads the level of each log entry from its line, in `FORMAT`, for
+    /// `--level`. In JSON logs, only the `message` field is searched.
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    pub log_format: Option<LogFormat>,
+
+    /// Searches only the log entries of `LEVEL`, such as `error`, or with
+    /// `LEVEL+`, e.g. `warn+`, also those of the more severe levels.
+    #[arg(long, value_name = "LEVEL", value_parser = parse_level, requires = "log_format")]
+    pub level: Option<LevelFilter>,
+
+    /// Matches only within the value at `PATH` in every JSON line, e.g.
+    /// `.request.url`, `.tags[0]` or the JSON pointer `/request/url`. Lines
+    /// without that value are not searched.
+    #[arg(long, value_name = "PATH", value_parser = parse_json_path)]
+    pub json_path: Option<JsonPath>,
+
+    /// Flag to read every line as a row of comma-separated values and to
+    /// match only within the column given with `--column-name` or
+    /// `--column-index`.
+    #[arg(long, requires = "csv_column")]
+    pub csv: bool,
+
+    /// Matches only in the CSV column named `NAME` in the first row, which
+    /// is taken as the header and not searched.
+    #[arg(long, value_name = "NAME", group = "csv_column", requires = "csv")]
+    pub column_name: Option<String>,
+
+    /// Matches only in the `N`th CSV column, counting from 1.
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u64).range(1..),
+        group = "csv_column",
+        requires = "csv"
+    )]
+    pub column_index: Option<u64>,
+
+    /// Flag to print only the cell of the `--csv` column instead of the
+    /// whole row.
+    #[arg(long, requires = "csv", conflicts_with = "only_matching")]
+    pub print_cell: bool,
+
+    /// Flag to remove terminal escape sequences, such as colors, from the
+    /// input before matching and printing it.
+    #[arg(long)]
+    pub strip_ansi: bool,
+
+    /// Flag to match the query against the visible text of HTML and XML
+    /// lines, leaving out tags and comments and decoding references such as
+    /// `&amp;`. Lines are still printed as they are.
+    #[arg(long, conflicts_with = "print_cell")]
+    pub strip_tags: bool,
+
+    /// Brings the query and every input line into the Unicode normalization
+    /// form `FORM` before matching, so that e.g. precomposed and decomposed
+    /// accents match each other.
+    #[arg(long, value_enum, value_name = "FORM")]
+    pub normalize: Option<NormalizationForm>,
+
+    /// Searches only inside the section `NAME` of INI and TOML files (and
+    /// its nested sections such as `[NAME.sub]`), or under the top-level key
+    /// `NAME` of YAML files.
+    #[arg(long, value_name = "NAME")]
+    pub section: Option<String>,
+
+    /// Searches only the fenced code blocks, the headings or the prose of
+    /// Markdown files. Other files are searched as usual.
+    #[arg(long, value_enum, value_name = "SCOPE")]
+    pub markdown_scope: Option<MarkdownScope>,
+
+    /// Flag to search only the lines added by a unified diff, read from
+    /// standard input or taken from `git diff`, reporting their line numbers
+    /// in the new version of each file.
+    #[arg(long)]
+    pub diff: bool,
+
+    /// Flag to search the files with staged changes, as staged in the git
+    /// index rather than as in the working tree.
+    #[arg(long, conflicts_with = "diff")]
+    pub staged: bool,
+
+    /// Skips the matches recorded in the baseline `FILE`, so that only new
+    /// occurrences are reported. Paths must be given the same way as when
+    /// the baseline was written.
+    #[arg(long, value_name = "FILE")]
+    pub baseline: Option<String>,
+
+    /// Flag to record every match in the `--baseline` file instead of
+    /// skipping the ones it contains.
+    #[arg(long, requires = "baseline")]
+    pub write_baseline: bool,
+
+    /// Caps how many bytes of a single line are held in memory at once.
+    /// Longer lines are searched in overlapping chunks of this size, so
+    /// matches spanning a chunk edge are still found. Each matching chunk is
+    /// printed on its own. If not specified, lines are read whole.
+    #[arg(long, value_name = "NUM")]
+    pub max_line_length: Option<usize>,
+
+    /// Splits the input into records at `STR` instead of at newlines, e.g.
+    /// at `;` or at a banner starting every log entry. Matching, context and
+    /// line numbers then work on records. `\n`, `\r`, `\t`, `\f`, `\0` and
+    /// `\\` are read as escapes, so `'\f'` splits at form feeds.
+    #[arg(long, value_name = "STR", value_parser = parse_separator, conflicts_with = "chunked")]
+    pub record_separator: Option<String>,
+
+    /// Searches the input in overlapping windows of `SIZE` bytes, e.g. `1M`,
+    /// and reports each match alone at its byte offset instead of its line
+    /// number. Meant for inputs without newlines, such as minified bundles,
+    /// which are then never held in memory as a whole. Accepts `K`, `M` and
+    /// `G` suffixes.
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub chunked: Option<usize>,
+
+    /// Bounds the memory used for line buffers, e.g. `64M`. Lines and context
+    /// buffers that would not fit are read in smaller chunks instead, trading
+    /// speed for a predictable footprint. Accepts `K`, `M` and `G` suffixes.
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub max_memory: Option<usize>,
+
+    /// Selects how files are read. `std` reads them with plain reads, while
+    /// `fadvise` also tells the kernel on Linux to read each file ahead in
+    /// full, which helps when millions of small files are searched.
+    #[arg(long, value_enum, value_name = "BACKEND", default_value_t = IoBackend::Std)]
+    pub io_backend: IoBackend,
+
+    /// Limits how many bytes per second are read from files, e.g. `20M`, so
+    /// that a search over a network share does not saturate its storage.
+    /// Accepts `K`, `M` and `G` suffixes. If not specified, files are read
+    /// as fast as possible.
+    #[arg(long, value_name = "BYTES_PER_SEC", value_parser = parse_size)]
+    pub throttle: Option<usize>,
+
+    /// Searches files of 64 MiB and more on this many threads, each taking
+    /// a piece of the file at a time. Only plain searches are split; with
+    /// context lines or line-based filters such as `--from`, files are
+    /// searched on a single thread. If not specified, every file is searched
+    /// on a single thread.
+    #[arg(long, value_name = "NUM", value_parser = clap::value_parser!(u64).range(1..))]
+    pub threads: Option<u64>,
+
+    /// Flag to print the pieces of a file split by `--threads` as soon as
+    /// each one is searched, instead of in file order, for the most
+    /// throughput. Lines are shown with their byte offsets instead of line
+    /// numbers, which are only known once all earlier pieces are searched.
+    #[arg(long, requires = "threads")]
+    pub unordered: bool,
+
+    /// Sets the size limit of the compiled pattern, e.g. `100M`. Raise it
+    /// when a very large pattern fails to compile. Accepts `K`, `M` and `G`
+    /// suffixes. If not specified, the `regex` crate's default is used.
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub regex_size_limit: Option<usize>,
+
+    /// Sets the size limit of the lazy DFA cache used while matching, e.g.
+    /// `10M`. A larger cache can speed up very large patterns. Accepts `K`,
+    /// `M` and `G` suffixes. If not specified, the `regex` crate's default
+    /// is used.
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub dfa_size_limit: Option<usize>,
+
+    /// Sorts the files in ascending order of the given key before searching
+    /// them, instead of searching them in the order they were given.
+    #[arg(long, value_enum, value_name = "KEY", conflicts_with = "sortr")]
+    pub sort: Option<SortBy>,
+
+    /// Sorts the files in descending order of the given key before searching
+    /// them, e.g. `--sortr modified` searches the newest files first.
+    #[arg(long, value_enum, value_name = "KEY")]
+    pub sortr: Option<SortBy>,
+
+    /// Specifies what to do with FIFOs, sockets and device files. They are
+    /// skipped by default, since reading them can block forever.
+    #[arg(
+        short = 'D',
+        long,
+        value_enum,
+        value_name = "ACTION",
+        default_value_t = DeviceAction::Skip
+    )]
+    pub devices: DeviceAction,
+
+    /// Specifies what to do with directories given as file paths: report
+    /// an error and go on with the other files (`read`), silently skip them,
+    /// or search every file below them (`recurse`).
+    #[arg(
+        short = 'd',
+        long,
+        value_enum,
+        value_name = "ACTION",
+        default_value_t = DirectoryAction::Read
+    )]
+    pub directories: DirectoryAction,
+
+    /// Stops the whole search once this much time has passed, e.g. `30s`.
+    /// Accepts `ms`, `s`, `m` and `h` suffixes; a plain number is seconds.
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub timeout: Option<Duration>,
+
+    /// Abandons the search of a single file once this much time has been
+    /// spent on it, e.g. `5s`, and moves on to the next file. Timed-out
+    /// files are reported on stderr.
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub file_timeout: Option<Duration>,
+
+    /// Maps files matching a glob to a preprocessor command, given as
+    /// `GLOB=COMMAND`, e.g. `'*.pdf=pdftotext - -'`. The command gets the
+    /// file on its standard input, and its output is searched instead of the
+    /// file. Can be given several times; the first matching glob wins. A
+    /// command that fails is reported with its error output, like a file
+    /// that cannot be read.
+    #[arg(long, value_name = "GLOB=COMMAND", value_parser = parse_pre_glob)]
+    pub pre_glob: Vec<PreGlob>,
+
+    /// Flag to only report matches inside comments. The comment syntax is
+    /// chosen by file extension; files in unknown languages never match.
+    #[arg(long)]
+    pub only_comments: bool,
+
+    /// Flag to only report matches inside string literals. Can be combined
+    /// with `--only-comments` to accept matches in either.
+    #[arg(long)]
+    pub only_strings: bool,
+
+    /// Records the path of every completely searched file in `FILE`, so
+    /// that an interrupted search can be continued with `--resume`.
+    #[arg(long, value_name = "FILE")]
+    pub checkpoint: Option<String>,
+
+    /// Flag to skip the files already recorded in the `--checkpoint` file,
+    /// continuing an earlier search instead of starting a new one.
+    #[arg(long, requires = "checkpoint")]
+    pub resume: bool,
+
+    /// Flag to search only one copy of files with identical content, such as
+    /// vendored license files and lockfiles. Files with the same length and
+    /// the same first 64 KiB are compared in full, and the number of skipped
+    /// copies is reported in the summary.
+    #[arg(long)]
+    pub dedupe_content: bool,
+
+    /// Only searches files whose content has the type `TYPE`, detected from
+    /// their first bytes rather than their extension, e.g. `text/xml`.
+    /// Accepts glob wildcards such as `text/*` or `*xml`, and can be given
+    /// several times.
+    #[arg(long, value_name = "TYPE")]
+    pub mime: Vec<String>,
+
+    /// Flag to also search files with a known binary extension, such as
+    /// `.png`, `.so` or `.class`, when walking directories. By default they
+    /// are skipped without being opened.
+    #[arg(long)]
+    pub no_ext_filter: bool,
+
+    /// Flag to stay on the file system of each directory walked with `-d
+    /// recurse`, so that network mounts or pseudo file systems such as
+    /// `/proc` below it are not searched. Only supported on Unix.
+    #[arg(long)]
+    pub one_file_system: bool,
+
+    /// Flag to print each file name once, above its matching lines, instead
+    /// of in front of every line.
+    #[arg(long)]
+    pub heading: bool,
+
+    /// Flag to turn off the defaults that depend on whether the output is a
+    /// terminal, so that the output looks the same wherever it goes.
+    #[arg(long)]
+    pub no_smart_defaults: bool,
+
+    /// Flag to suppress the summary of matches, files and elapsed time that
+    /// is printed to stderr at the end of the search.
+    #[arg(long)]
+    pub no_summary: bool,
+
+    /// A subcommand to run instead of a single search. To search for a query
+    /// that is the name of a subcommand, put `--` in front of it.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// The subcommands of `grep-rust`.
+#[derive(Subcommand, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Loads the files once and then searches them for each pattern entered
+    /// at a prompt. Options such as `-i` go before `repl`; only those that
+    /// decide which lines match, how they are shown and which files are
+    /// loaded can be given.
+    Repl {
+        /// The paths to the files to load.
+        #[arg(required = true)]
+        file_paths: Vec<String>,
+    },
+}
+
+/// A `--pre-glob` mapping from a file glob to a preprocessor command.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PreGlob {
+    /// The glob selecting the files the command applies to.
+    pub glob: String,
+    /// The shell command that turns a file into searchable text.
+    pub command: String,
+}
+
+/// The actions `--directories` can take for directories given as file paths.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DirectoryAction {
+    /// Report the directory as an error.
+    Read,
+    /// Silently skip the directory.
+    Skip,
+    /// Search all files below the directory recursively.
+    Recurse,
+}
+
+/// The ways of reading files selectable with `--io-backend`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IoBackend {
+    /// Plain buffered reads.
+    Std,
+    /// Plain reads, with readahead hints given through `posix_fadvise`.
+    Fadvise,
+}
+
+/// The actions `--devices` can take for FIFOs, sockets and device files.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeviceAction {
+    /// Read the file like a regular file.
+    Read,
+    /// Silently skip the file.
+    Skip,
+}
+
+/// The keys files can be sorted by with `--sort` and `--sortr`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortBy {
+    /// Sort by file path.
+    Path,
+    /// Sort by last modification time.
+    Modified,
+    /// Sort by file size.
+    Size,
+}
+
+/// How match counts are grouped by `--histogram`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HistogramBy {
+    /// One bar per file.
+    File,
+    /// One bar per directory, summing the counts of the files directly in it.
+    Dir,
+}
+
+/// The abbreviations of printed paths selectable with `--path-style`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PathStyle {
+    /// The whole path.
+    Full,
+    /// The first and last components only, e.g. `src/.../module.rs`.
+    Short,
+    /// The home directory written as `~`.
+    Tilde,
+}
+
+/// The kinds of Markdown content selectable with `--markdown-scope`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MarkdownScope {
+    /// The lines inside fenced code blocks.
+    Code,
+    /// Everything that is neither code nor a heading.
+    Prose,
+    /// `#` headings.
+    Headings,
+}
+
+/// The log line formats selectable with `--log-format`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Syslog lines, with a `<PRI>` prefix or a level word.
+    Syslog,
+    /// One JSON object per line, with `level` and `message` fields.
+    Json,
+    /// `key=value` pairs, with a `level` field.
+    Logfmt,
+}
+
+/// The levels of log entries, from the least to the most severe.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    /// The finest-grained diagnostics.
+    Trace,
+    /// Diagnostics for developers.
+    Debug,
+    /// Normal operation, including syslog's `notice`.
+    Info,
+    /// Something unexpected that was recovered from.
+    Warn,
+    /// A failed operation.
+    Error,
+    /// A failure of the whole program, including syslog's `crit`, `alert`
+    /// and `emerg`.
+    Fatal,
+}
+
+/// A `--level` filter: a level, and whether more severe levels pass too.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LevelFilter {
+    /// The level searched.
+    pub level: Level,
+    /// Whether the levels above `level` are searched too.
+    pub or_above: bool,
+}
+
+impl LevelFilter {
+    /// Returns whether entries of `level` are searched.
+    pub fn allows(&self, level: Level) -> bool {
+        level == self.level || (self.or_above && level > self.level)
+    }
+}
+
+/// A `--json-path`: the keys and array indices leading to a value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JsonPath {
+    /// The object keys and array indices, from the outermost value in.
+    pub segments: Vec<String>,
+}
+
+/// A `--hex-pattern`: the bytes searched for, with `None` for a `??`
+/// wildcard.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HexPattern {
+    /// The bytes, in order.
+    pub bytes: Vec<Option<u8>>,
+}
+
+impl fmt::Display for HexPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, byte) in self.bytes.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            match byte {
+                Some(byte) => write!(f, "{:02X}", byte)?,
+                None => f.write_str("??")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The patterns selectable with `--preset`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Preset {
+    /// Email addresses, such as `jane@example.com`.
+    Email,
+    /// IPv4 addresses, such as `192.168.0.1`.
+    Ipv4,
+    /// IPv6 addresses, such as `2001:db8::1`.
+    Ipv6,
+    /// UUIDs, such as `123e4567-e89b-12d3-a456-426614174000`.
+    Uuid,
+    /// HTTP, HTTPS and FTP URLs.
+    Url,
+    /// ISO 8601 dates, such as `2024-01-31`, with an optional time.
+    IsoDate,
+    /// MAC addresses, such as `00:1a:2b:3c:4d:5e`.
+    MacAddress,
+}
+
+/// The Unicode normalization forms selectable with `--normalize`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NormalizationForm {
+    /// Canonical composition, which only unifies equivalent encodings.
+    Nfc,
+    /// Compatibility composition, which also folds ligatures, fullwidth
+    /// letters and similar variants into their plain forms.
+    Nfkc,
+}
+
+/// The output formats selectable with `--format`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// The matching lines as text.
+    Text,
+    /// One JSON object per matching line, with the spans of its matches.
+    Json,
+    /// A GitLab Code Quality JSON report.
+    Codequality,
+    /// A JUnit XML report, with each matching line as a failing test case.
+    Junit,
+}
+
+/// A pattern to search for, with the options that apply to it.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Pattern {
+    /// The literal text to search for.
+    pub text: String,
+    /// Whether case is ignored.
+    pub ignore_case: bool,
+    /// Whether only whole words match.
+    pub word: bool,
+    /// Whether the text is a regex rather than literal text.
+    pub regex: bool,
+}
+
+impl Pattern {
+    /// Parses a `-e` pattern, applying its leading modifiers over the global
+    /// `ignore_case` and `word` options. A leading group that is not made of
+    /// known modifiers is part of the text.
+    fn parse(spec: &str, ignore_case: bool, word: bool) -> Pattern {
+        let mut pattern = Pattern {
+            text: spec.to_string(),
+            ignore_case,
+            word,
+            regex: false,
+        };
+        let Some((modifiers, text)) = spec
+            .strip_prefix("(?")
+            .and_then(|rest| rest.split_once(')'))
+        else {
+            return pattern;
+        };
+        if modifiers.is_empty() || !modifiers.chars().all(|c| matches!(c, 'i' | 'w' | '-')) {
+            return pattern;
+        }
+
+        let mut enable = true;
+        for c in modifiers.chars() {
+            match c {
+                '-' => enable = false,
+                'i' => pattern.ignore_case = enable,
+                _ => pattern.word = enable,
+            }
+        }
+        pattern.text = text.to_string();
+        pattern
+    }
+}
+
+impl Config {
+    /// Moves the positional query over to the file paths when the patterns
+    /// are given with `-e` or `--preset`, since every positional argument is
+    /// a file path then. The query is set to the patterns, for display.
+    ///
+    /// # Returns
+    /// The config, or an error if patterns are given with `-e` or `--preset`
+    /// but no file path is.
+    pub fn resolve_patterns(mut self) -> Result<Config, clap::Error> {
+        if self.patterns.is_empty()
+            && self.presets.is_empty()
+            && !self.undo
+            && self.hex_pattern.is_none()
+        {
+            return Ok(self);
+        }
+        if !self.query.is_empty() {
+            self.file_paths.insert(0, std::mem::take(&mut self.query));
+        }
+        if self.file_paths.is_empty() && !self.diff && !self.staged {
+            return Err(Config::command().error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                "the following required arguments were not provided:\n  <file_paths>...",
+            ));
+        }
+        if let Some(hex_pattern) = &self.hex_pattern {
+            self.query = hex_pattern.to_string();
+        } else if !self.undo {
+            let presets = self.presets.iter().map(|preset| {
+                preset
+                    .to_possible_value()
+                    .map_or_else(String::new, |value| value.get_name().to_string())
+            });
+            self.query = self
+                .patterns
+                .iter()
+                .cloned()
+                .chain(presets)
+                .collect::<Vec<_>>()
+                .join("', '");
+        }
+        Ok(self)
+    }
+
+    /// Rejects the options given on the command line that the `repl`
+    /// subcommand does not apply, if it is run.
+    ///
+    /// # Arguments
+    /// * `args` - The arguments the config was parsed from.
+    ///
+    /// # Returns
+    /// The config, or an error naming the first option `repl` does not
+    /// apply.
+    pub fn check_repl_options(self, args: &[OsString]) -> Result<Config, clap::Error> {
+        if self.command.is_none() {
+            return Ok(self);
+        }
+        let mut command = Config::command();
+        let matches = command.clone().try_get_matches_from(args)?;
+        let unsupported = command.get_arguments().find(|arg| {
+            let id = arg.get_id().as_str();
+            !REPL_OPTIONS.contains(&id)
+                && matches.value_source(id) == Some(ValueSource::CommandLine)
+        });
+        match unsupported {
+            Some(arg) => {
+                let arg = match (arg.get_long(), arg.get_short()) {
+                    (Some(long), _) => format!("--{}", long),
+                    (None, Some(short)) => format!("-{}", short),
+                    (None, None) => arg.get_id().to_string(),
+                };
+                Err(command.error(
+                    clap::error::ErrorKind::ArgumentConflict,
+                    message(MessageId::ReplUnsupported, &[&arg]),
+                ))
+            }
+            None => Ok(self),
+        }
+    }
+
+    /// Returns the patterns to search for: those given with `-e` and
+    /// `--preset`, or else the query, with the options that apply to each of
+    /// them.
+    pub fn search_patterns(&self) -> Vec<Pattern> {
+        if self.patterns.is_empty() && self.presets.is_empty() {
+            return vec![Pattern {
+                text: self.query.clone(),
+                ignore_case: self.ignore_case,
+                word: self.word_regexp,
+                regex: false,
+            }];
+        }
+        // Presets are complete regexes, which know about case and word
+        // boundaries themselves.
+        let presets = self.presets.iter().map(|&preset| Pattern {
+            text: preset::regex(preset).to_string(),
+            ignore_case: false,
+            word: false,
+            regex: true,
+        });
+        self.patterns
+            .iter()
+            .map(|spec| Pattern::parse(spec, self.ignore_case, self.word_regexp))
+            .chain(presets)
+            .collect()
+    }
+
+    /// Returns whether only per-file match counts are reported, so that
+    /// matching lines are counted but not printed.
+    pub fn counts_only(&self) -> bool {
+        self.count || self.histogram.is_some() || self.top.is_some()
+    }
+
+    /// Returns whether a search that found `match_count` matches succeeded,
+    /// i.e. whether it exits with status 0.
+    pub fn succeeded(&self, match_count: usize) -> bool {
+        if let Some(max_allowed) = self.max_allowed {
+            match_count <= max_allowed
+        } else if self.fail_on_match {
+            match_count == 0
+        } else {
+            match_count > 0
+        }
+    }
+
+    /// Returns whether the search of a file stops at its first match, which
+    /// is enough for `--quiet` and `--files-with-matches` unless every match
+    /// has to be counted for `--max-allowed` or recorded for
+    /// `--write-baseline`.
+    pub fn stops_at_first_match(&self) -> bool {
+        (self.quiet || self.files_with_matches)
+            && self.max_allowed.is_none()
+            && !self.write_baseline
+    }
+
+    /// Returns whether matching lines are held back until every file has
+    /// been searched, to be sorted or written as a report.
+    pub fn holds_lines(&self) -> bool {
+        self.sort_lines || self.count_unique || self.format != OutputFormat::Text
+    }
+
+    /// Returns whether any of the results of a file are only output once
+    /// every file has been searched: held lines, counts, or the matches of
+    /// `--write-baseline`.
+    pub fn holds_output(&self) -> bool {
+        self.holds_lines() || self.counts_only() || self.write_baseline
+    }
+}
+
+/// Options of other greps that are spelled differently here, with the
+/// arguments they stand for. An empty replacement drops the option, which
+/// is the case for options asking for what `grep-rust` always does.
+const ARG_ALIASES: &[(&str, &[&str])] = &[
+    ("-r", &["--directories=recurse"]),
+    ("-R", &["--directories=recurse"]),
+    ("--recursive", &["--directories=recurse"]),
+    ("--dereference-recursive", &["--directories=recurse"]),
+    ("-y", &["--ignore-case"]),
+    ("--files-with-match", &["--files-with-matches"]),
+    ("--silent", &["--quiet"]),
+    ("-F", &[]),
+    ("--fixed-strings", &[]),
+    ("--line-buffered", &[]),
+    ("-U", &[]),
+    ("--binary", &[]),
+];
+
+/// The options the `repl` subcommand applies: those that decide which lines
+/// match and how they are shown, and those that choose the files to load.
+const REPL_OPTIONS: &[&str] = &[
+    "ignore_case",
+    "word_regexp",
+    "line_regexp",
+    "starts_with",
+    "ends_with",
+    "invert_match",
+    "line_number",
+    "regex_size_limit",
+    "dfa_size_limit",
+    "directories",
+    "devices",
+    "no_ext_filter",
+    "one_file_system",
+    "no_messages",
+    "no_smart_defaults",
+    "no_summary",
+];
+
+/// Options of other greps that `grep-rust` does not support, with the reason
+/// reported for them.
+const UNSUPPORTED_ARGS: &[(&str, MessageId)] = &[
+    ("-E", MessageId::LiteralPatternsOnly),
+    ("-G", MessageId::LiteralPatternsOnly),
+    ("-P", MessageId::LiteralPatternsOnly),
+    ("--extended-regexp", MessageId::LiteralPatternsOnly),
+    ("--basic-regexp", MessageId::LiteralPatternsOnly),
+    ("--perl-regexp", MessageId::LiteralPatternsOnly),
+    ("-L", MessageId::NoFilesWithoutMatch),
+    ("--files-without-match", MessageId::NoFilesWithoutMatch),
+    ("-z", MessageId::NewlineSeparatedLines),
+    ("--null-data", MessageId::NewlineSeparatedLines),
+];
+
+/// Rewrites the command-line arguments of other greps into the ones
+/// `grep-rust` understands, so that scripts written for GNU, BSD or POSIX
+/// grep keep working.
+///
+/// Bundled short options such as `-rni` are split up first, so that each of
+/// them can be mapped. Option values and everything after `--` are left
+/// alone.
+///
+/// # Arguments
+/// * `args` - The command-line arguments, starting with the program name.
+///
+/// # Returns
+/// The rewritten arguments, or an error naming an option that other greps
+/// have but `grep-rust` does not support.
+pub fn normalize_args(
+    args: impl IntoIterator<Item = OsString>,
+) -> Result<Vec<OsString>, clap::Error> {
+    let command = Config::command();
+    let takes_value = |short: Option<char>, long: Option<&str>| {
+        command.get_arguments().any(|arg| {
+            ((short.is_some() && arg.get_short() == short)
+                || (long.is_some() && arg.get_long() == long))
+                && arg.get_action().takes_values()
+        })
+    };
+    let unsupported = |arg: &str| {
+        UNSUPPORTED_ARGS
+            .iter()
+            .find(|(name, _)| *name == arg)
+            .map(|&(_, reason)| {
+                command.clone().error(
+                    clap::error::ErrorKind::UnknownArgument,
+                    message(
+                        MessageId::UnsupportedArgument,
+                        &[&arg, &message(reason, &[])],
+                    ),
+                )
+            })
+    };
+    let alias = |arg: &str| {
+        ARG_ALIASES
+            .iter()
+            .find(|(name, _)| *name == arg)
+            .map(|(_, replacement)| replacement.iter().map(OsString::from))
+    };
+
+    let mut args = args.into_iter();
+    let mut normalized: Vec<OsString> = args.next().into_iter().collect();
+    let mut value_follows = false;
+    let mut options_ended = false;
+    for arg in args {
+        let text = match arg.to_str() {
+            Some(text) if !value_follows && !options_ended => text.to_string(),
+            _ => {
+                value_follows = false;
+                normalized.push(arg);
+                continue;
+            }
+        };
+
+        if text == "--" {
+            options_ended = true;
+            normalized.push(arg);
+        } else if let Some(long) = text.strip_prefix("--") {
+            let name = long.split('=').next().unwrap_or(long);
+            if let Some(error) = unsupported(&format!("--{}", name)) {
+                return Err(error);
+            }
+            match alias(&text) {
+                Some(replacement) => normalized.extend(replacement),
+                None => {
+                    value_follows = !long.contains('=') && takes_value(None, Some(name));
+                    normalized.push(arg);
+                }
+            }
+        } else if let Some(shorts) = text.strip_prefix('-').filter(|shorts| !shorts.is_empty()) {
+            for (i, c) in shorts.char_indices() {
+                let short = format!("-{}", c);
+                if let Some(error) = unsupported(&short) {
+                    return Err(error);
+                }
+                if let Some(replacement) = alias(&short) {
+                    normalized.extend(replacement);
+                } else if takes_value(Some(c), None) {
+                    // The rest of the bundle is the option's value, or else
+                    // the value is the next argument.
+                    normalized.push(OsString::from(format!("-{}", &shorts[i..])));
+                    value_follows = i + c.len_utf8() == shorts.len();
+                    break;
+                } else {
+                    normalized.push(OsString::from(short));
+                }
+            }
+        } else {
+            normalized.push(arg);
+        }
+    }
+    Ok(normalized)
+}
+
+/// Parses a human-readable size such as `512`, `64K`, `16M` or `1G` into a
+/// number of bytes. Suffixes are powers of 1024 and are case-insensitive.
+///
+/// # Arguments
+/// * `value` - The size string given on the command line.
+///
+/// # Returns
+/// The size in bytes, or an error message if the string is not a valid size.
+pub fn parse_size(value: &str) -> Result<usize, String> {
+    let value = value.trim();
+    let digits_end = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, suffix) = value.split_at(digits_end);
+
+    let number: usize = number
+        .parse()
+        .map_err(|_| message(MessageId::InvalidSize, &[&value]))?;
+    let multiplier: usize = match suffix.to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        _ => return Err(message(MessageId::InvalidSizeSuffix, &[&suffix])),
+    };
+
+    number
+        .checked_mul(multiplier)
+        .ok_or_else(|| message(MessageId::SizeTooLarge, &[&value]))
+}
+
+/// Parses a human-readable duration such as `500ms`, `30s`, `2m` or `1h`.
+/// A plain number is interpreted as seconds.
+///
+/// # Arguments
+/// * `value` - The duration string given on the command line.
+///
+/// # Returns
+/// The parsed `Duration`, or an error message if the string is not a valid
+/// duration.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let digits_end = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, suffix) = value.split_at(digits_end);
+
+    let number: u64 = number
+        .parse()
+        .map_err(|_| message(MessageId::InvalidDuration, &[&value]))?;
+    match suffix {
+        "ms" => Ok(Duration::from_millis(number)),
+        "" | "s" => Ok(Duration::from_secs(number)),
+        "m" => Ok(Duration::from_secs(number.saturating_mul(60))),
+        "h" => Ok(Duration::from_secs(number.saturating_mul(60 * 60))),
+        _ => Err(message(MessageId::InvalidDurationSuffix, &[&suffix])),
+    }
+}
+
+/// Parses a `--hex-pattern` such as `DE AD BE EF` or `deadbeef`, where
+/// `??` matches any byte.
+///
+/// # Arguments
+/// * `value` - The pattern given on the command line.
+///
+/// # Returns
+/// The parsed `HexPattern`, or an error message if the pattern is empty or
+/// not made of pairs of hex digits.
+pub fn parse_hex_pattern(value: &str) -> Result<HexPattern, String> {
+    let digits: Vec<char> = value.chars().filter(|c| !c.is_whitespace()).collect();
+    if digits.is_empty() || !digits.len().is_multiple_of(2) {
+        return Err(message(MessageId::InvalidHexPattern, &[&value]));
+    }
+    let bytes = digits
+        .chunks(2)
+        .map(|pair| match pair {
+            ['?', '?'] => Ok(None),
+            [high, low] => match (high.to_digit(16), low.to_digit(16)) {
+                (Some(high), Some(low)) => Ok(Some((high * 16 + low) as u8)),
+                _ => Err(message(
+                    MessageId::InvalidHexByte,
+                    &[&format!("{}{}", high, low)],
+                )),
+            },
+            _ => unreachable!("chunks of an even length are pairs"),
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(HexPattern { bytes })
+}
+
+/// Parses a `--record-separator`, reading the escapes `\n`, `\r`, `\t`,
+/// `\f`, `\0` and `\\`.
+///
+/// # Arguments
+/// * `value` - The separator given on the command line.
+///
+/// # Returns
+/// The separator, or an error message if it is empty or has an unknown
+/// escape.
+pub fn parse_separator(value: &str) -> Result<String, String> {
+    let mut separator = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            separator.push(c);
+            continue;
+        }
+        separator.push(match chars.next() {
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('f') => '\x0c',
+            Some('0') => '\0',
+            Some('\\') => '\\',
+            Some(other) => return Err(message(MessageId::UnknownEscape, &[&other])),
+            None => return Err(message(MessageId::LoneBackslash, &[])),
+        });
+    }
+    if separator.is_empty() {
+        return Err(message(MessageId::EmptySeparator, &[]));
+    }
+    Ok(separator)
+}
+
+/// Parses a `--json-path`, either jq-like as in `.request.headers[0]` or as
+/// a JSON pointer as in `/request/headers/0`.
+///
+/// # Arguments
+/// * `value` - The path given on the command line.
+///
+/// # Returns
+/// The parsed `JsonPath`, or an error message if the path is malformed.
+pub fn parse_json_path(value: &str) -> Result<JsonPath, String> {
+    let invalid = || message(MessageId::InvalidJsonPath, &[&value]);
+    if let Some(pointer) = value.strip_prefix('/') {
+        return Ok(JsonPath {
+            segments: pointer
+                .split('/')
+                .map(|segment| segment.replace("~1", "/").replace("~0", "~"))
+                .collect(),
+        });
+    }
+    let mut segments = Vec::new();
+    let mut rest = value.strip_prefix('.').ok_or_else(invalid)?;
+    while !rest.is_empty() {
+        if let Some(indexed) = rest.strip_prefix('[') {
+            let (index, after) = indexed.split_once(']').ok_or_else(invalid)?;
+            index.parse::<usize>().map_err(|_| invalid())?;
+            segments.push(index.to_string());
+            rest = after.strip_prefix('.').unwrap_or(after);
+            continue;
+        }
+        let end = rest.find(['.', '[']).unwrap_or(rest.len());
+        if end == 0 {
+            return Err(invalid());
+        }
+        segments.push(rest[..end].to_string());
+        rest = rest[end..].strip_prefix('.').unwrap_or(&rest[end..]);
+    }
+    Ok(JsonPath { segments })
+}
+
+/// Parses a `--level` filter such as `warn`, or `warn+` for warnings and
+/// everything more severe.
+///
+/// # Arguments
+/// * `value` - The filter given on the command line.
+///
+/// # Returns
+/// The parsed `LevelFilter`, or an error message if the level is unknown.
+pub fn parse_level(value: &str) -> Result<LevelFilter, String> {
+    let (name, or_above) = match value.strip_suffix('+') {
+        Some(name) => (name, true),
+        None => (value, false),
+    };
+    match log_format::level_from_name(name) {
+        Some(level) => Ok(LevelFilter { level, or_above }),
+        None => Err(message(MessageId::UnknownLevel, &[&name])),
+    }
+}
+
+/// Parses a `--pre-glob` mapping of the form `GLOB=COMMAND`.
+///
+/// # Arguments
+/// * `value` - The mapping given on the command line.
+///
+/// # Returns
+/// The parsed `PreGlob`, or an error message if the glob or the command is
+/// missing.
+pub fn parse_pre_glob(value: &str) -> Result<PreGlob, String> {
+    match value.split_once('=') {
+        Some((glob, command)) if !glob.is_empty() && !command.trim().is_empty() => Ok(PreGlob {
+            glob: glob.to_string(),
+            command: command.to_string(),
+        }),
+        _ => Err(message(MessageId::InvalidPreGlob, &[&value])),
+    }
 }
 
 #[cfg(test)]
@@ -69,11 +1426,14 @@ mod tests {
         let args = vec!["grep-rust", "test_query", "test_file.txt"];
         let config = Config::parse_from(args);
         assert_eq!(config.query, "test_query");
-        assert_eq!(config.file_path, "test_file.txt");
+        assert_eq!(config.file_paths, vec!["test_file.txt"]);
         assert!(!config.ignore_case);
         assert!(!config.line_number);
+        assert!(!config.count);
         assert_eq!(config.before_context, None);
         assert_eq!(config.after_context, None);
+        assert_eq!(config.devices, DeviceAction::Skip);
+        assert_eq!(config.directories, DirectoryAction::Read);
     }
 
     #[test]
@@ -81,7 +1441,7 @@ mod tests {
         let args = vec![
             "grep-rust",
             "-i",
-            "-l",
+            "-n",
             "-B",
             "2",
             "-A",
@@ -91,7 +1451,7 @@ mod tests {
         ];
         let config = Config::parse_from(args);
         assert_eq!(config.query, "pattern");
-        assert_eq!(config.file_path, "file.log");
+        assert_eq!(config.file_paths, vec!["file.log"]);
         assert!(config.ignore_case);
         assert!(config.line_number);
         assert_eq!(config.before_context, Some(2));
@@ -105,4 +1465,977 @@ mod tests {
         assert!(config.word_regexp);
         assert_eq!(config.query, "word");
     }
+
+    #[test]
+    fn test_config_with_count_and_multiple_files() {
+        let args = vec![
+            "grep-rust",
+            "-c",
+            "--include-zero",
+            "pattern",
+            "a.txt",
+            "b.txt",
+        ];
+        let config = Config::parse_from(args);
+        assert!(config.count);
+        assert!(config.include_zero);
+        assert_eq!(config.file_paths, vec!["a.txt", "b.txt"]);
+    }
+
+    #[test]
+    fn test_config_with_max_total() {
+        let args = vec!["grep-rust", "--max-total", "5", "pattern", "a.txt"];
+        let config = Config::parse_from(args);
+        assert_eq!(config.max_total, Some(5));
+    }
+
+    #[test]
+    fn test_config_with_early_exit_flags() {
+        let args = vec![
+            "grep-rust",
+            "-q",
+            "--files-with-matches",
+            "pattern",
+            "a.txt",
+        ];
+        let config = Config::parse_from(args);
+        assert!(config.quiet);
+        assert!(config.files_with_matches);
+
+        let args = vec!["grep-rust", "-l", "pattern", "a.txt"];
+        let config = Config::parse_from(args);
+        assert!(config.files_with_matches);
+        assert!(!config.line_number);
+    }
+
+    #[test]
+    fn test_config_with_gnu_short_flags() {
+        let args = vec!["grep-rust", "-invxc", "pattern", "a.txt"];
+        let config = Config::parse_from(args);
+        assert!(config.ignore_case);
+        assert!(config.line_number);
+        assert!(config.invert_match);
+        assert!(config.line_regexp);
+        assert!(config.count);
+    }
+
+    #[test]
+    fn test_config_with_exit_status_flags() {
+        let args = vec!["grep-rust", "pattern", "a.txt"];
+        let config = Config::parse_from(args);
+        assert!(config.succeeded(1));
+        assert!(!config.succeeded(0));
+
+        let args = vec!["grep-rust", "--fail-on-match", "pattern", "a.txt"];
+        let config = Config::parse_from(args);
+        assert!(!config.succeeded(1));
+        assert!(config.succeeded(0));
+
+        let args = vec!["grep-rust", "--fail-if-no-match", "pattern", "a.txt"];
+        let config = Config::parse_from(args);
+        assert!(config.succeeded(1));
+        assert!(!config.succeeded(0));
+    }
+
+    #[test]
+    fn test_config_with_max_allowed() {
+        let args = vec!["grep-rust", "-q", "--max-allowed", "12", "unwrap()", "src"];
+        let config = Config::parse_from(args);
+        assert_eq!(config.max_allowed, Some(12));
+        assert!(config.succeeded(0));
+        assert!(config.succeeded(12));
+        assert!(!config.succeeded(13));
+        assert!(!config.stops_at_first_match());
+    }
+
+    #[test]
+    fn test_config_with_baseline() {
+        let args = vec!["grep-rust", "--baseline", "known.txt", "unwrap()", "src"];
+        let config = Config::parse_from(args);
+        assert_eq!(config.baseline.as_deref(), Some("known.txt"));
+        assert!(!config.write_baseline);
+
+        let args = vec![
+            "grep-rust",
+            "--baseline",
+            "known.txt",
+            "--write-baseline",
+            "unwrap()",
+            "src",
+        ];
+        let config = Config::parse_from(args);
+        assert!(config.write_baseline);
+
+        let args = vec!["grep-rust", "--write-baseline", "unwrap()", "src"];
+        assert!(Config::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn test_config_with_diff() {
+        let args = vec!["grep-rust", "--diff", "dbg!"];
+        let config = Config::parse_from(args);
+        assert!(config.diff);
+        assert!(config.file_paths.is_empty());
+
+        let args = vec!["grep-rust", "dbg!"];
+        assert!(Config::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn test_config_with_staged() {
+        let args = vec!["grep-rust", "--staged", "dbg!"];
+        let config = Config::parse_from(args);
+        assert!(config.staged);
+        assert!(config.file_paths.is_empty());
+
+        let args = vec!["grep-rust", "--staged", "--diff", "dbg!"];
+        assert!(Config::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn test_config_with_patterns() {
+        let args = vec![
+            "grep-rust",
+            "-i",
+            "-e",
+            "(?-i)PANIC",
+            "-e",
+            "(?w)error",
+            "-e",
+            "(?x)",
+            "a.txt",
+        ];
+        let config = Config::parse_from(args).resolve_patterns().unwrap();
+        assert_eq!(config.file_paths, vec!["a.txt"]);
+        assert_eq!(
+            config.search_patterns(),
+            vec![
+                Pattern {
+                    text: "PANIC".to_string(),
+                    ignore_case: false,
+                    word: false,
+                    regex: false,
+                },
+                Pattern {
+                    text: "error".to_string(),
+                    ignore_case: true,
+                    word: true,
+                    regex: false,
+                },
+                Pattern {
+                    text: "(?x)".to_string(),
+                    ignore_case: true,
+                    word: false,
+                    regex: false,
+                },
+            ]
+        );
+
+        let args = vec!["grep-rust", "-e", "error"];
+        assert!(Config::parse_from(args).resolve_patterns().is_err());
+    }
+
+    #[test]
+    fn test_config_with_repl() {
+        let args = vec!["grep-rust", "-i", "repl", "a.log", "b.log"];
+        let config = Config::parse_from(args);
+        assert!(config.ignore_case);
+        assert_eq!(
+            config.command,
+            Some(Command::Repl {
+                file_paths: vec!["a.log".to_string(), "b.log".to_string()]
+            })
+        );
+
+        let args = vec!["grep-rust", "--", "repl", "a.log"];
+        let config = Config::parse_from(args);
+        assert_eq!(config.query, "repl");
+        assert_eq!(config.command, None);
+
+        // Options the prompt does not apply are rejected rather than ignored.
+        let check = |args: &[&str]| {
+            let args: Vec<OsString> = args.iter().map(OsString::from).collect();
+            Config::parse_from(&args).check_repl_options(&args)
+        };
+        assert!(check(&["grep-rust", "-i", "-w", "-d", "recurse", "repl", "logs"]).is_ok());
+        assert!(check(&["grep-rust", "-c", "repl", "a.log"]).is_err());
+        assert!(check(&["grep-rust", "--from", "START", "repl", "a.log"]).is_err());
+        assert!(check(&["grep-rust", "-c", "error", "a.log"]).is_ok());
+    }
+
+    #[test]
+    fn test_config_with_range() {
+        let args = vec![
+            "grep-rust",
+            "--from",
+            "panicked",
+            "--until",
+            "note:",
+            "",
+            "a.log",
+        ];
+        let config = Config::parse_from(args);
+        assert_eq!(config.from.as_deref(), Some("panicked"));
+        assert_eq!(config.until.as_deref(), Some("note:"));
+        assert_eq!(config.query, "");
+    }
+
+    #[test]
+    fn test_config_with_section() {
+        let args = vec!["grep-rust", "--section", "server", "timeout", "app.toml"];
+        let config = Config::parse_from(args);
+        assert_eq!(config.section.as_deref(), Some("server"));
+    }
+
+    #[test]
+    fn test_config_with_strip_ansi() {
+        let args = vec!["grep-rust", "--strip-ansi", "error", "ci.log"];
+        let config = Config::parse_from(args);
+        assert!(config.strip_ansi);
+    }
+
+    #[test]
+    fn test_config_with_normalize() {
+        let args = vec!["grep-rust", "--normalize", "nfkc", "caf\u{e9}", "notes.txt"];
+        let config = Config::parse_from(args);
+        assert_eq!(config.normalize, Some(NormalizationForm::Nfkc));
+    }
+
+    #[test]
+    fn test_config_with_context_skip() {
+        let args = vec![
+            "grep-rust",
+            "-A",
+            "2",
+            "--context-skip-blank",
+            "--context-skip-comments",
+            "pattern",
+            "main.rs",
+        ];
+        let config = Config::parse_from(args);
+        assert!(config.context_skip_blank);
+        assert!(config.context_skip_comments);
+    }
+
+    #[test]
+    fn test_config_with_no_ext_filter() {
+        let args = vec![
+            "grep-rust",
+            "-d",
+            "recurse",
+            "--no-ext-filter",
+            "pattern",
+            ".",
+        ];
+        let config = Config::parse_from(args);
+        assert!(config.no_ext_filter);
+        assert!(!config.one_file_system);
+
+        let args = vec![
+            "grep-rust",
+            "-d",
+            "recurse",
+            "--one-file-system",
+            "pattern",
+            "/",
+        ];
+        let config = Config::parse_from(args);
+        assert!(config.one_file_system);
+    }
+
+    #[test]
+    fn test_config_with_mime() {
+        let args = vec![
+            "grep-rust",
+            "--mime",
+            "text/xml",
+            "--mime",
+            "*json",
+            "id",
+            "data",
+        ];
+        let config = Config::parse_from(args);
+        assert_eq!(config.mime, vec!["text/xml", "*json"]);
+    }
+
+    #[test]
+    fn test_config_with_dedupe_content() {
+        let args = vec![
+            "grep-rust",
+            "-d",
+            "recurse",
+            "--dedupe-content",
+            "MIT",
+            "vendor",
+        ];
+        let config = Config::parse_from(args);
+        assert!(config.dedupe_content);
+    }
+
+    #[test]
+    fn test_config_with_checkpoint() {
+        let args = vec![
+            "grep-rust",
+            "--checkpoint",
+            "scan.checkpoint",
+            "--resume",
+            "secret",
+            "/mnt/share",
+        ];
+        let config = Config::parse_from(args);
+        assert_eq!(config.checkpoint.as_deref(), Some("scan.checkpoint"));
+        assert!(config.resume);
+
+        let args = vec!["grep-rust", "--resume", "secret", "/mnt/share"];
+        assert!(Config::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn test_config_with_throttle() {
+        let args = vec!["grep-rust", "--throttle", "20M", "pattern", "/mnt/share"];
+        let config = Config::parse_from(args);
+        assert_eq!(config.throttle, Some(20 * 1024 * 1024));
+    }
+
+    #[test]
+    fn test_config_with_threads() {
+        let args = vec!["grep-rust", "--threads", "8", "pattern", "big.log"];
+        let config = Config::parse_from(args);
+        assert_eq!(config.threads, Some(8));
+
+        let args = vec!["grep-rust", "--threads", "0", "pattern", "big.log"];
+        assert!(Config::try_parse_from(args).is_err());
+
+        let args = vec!["grep-rust", "--unordered", "pattern", "big.log"];
+        assert!(Config::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn test_config_with_io_backend() {
+        let args = vec!["grep-rust", "pattern", "src"];
+        let config = Config::parse_from(args);
+        assert_eq!(config.io_backend, IoBackend::Std);
+
+        let args = vec!["grep-rust", "--io-backend", "fadvise", "pattern", "src"];
+        let config = Config::parse_from(args);
+        assert_eq!(config.io_backend, IoBackend::Fadvise);
+    }
+
+    #[test]
+    fn test_config_with_chunked() {
+        let args = vec![
+            "grep-rust",
+            "--chunked",
+            "1M",
+            "sourceMappingURL",
+            "app.min.js",
+        ];
+        let config = Config::parse_from(args);
+        assert_eq!(config.chunked, Some(1024 * 1024));
+    }
+
+    #[test]
+    fn test_normalize_args() {
+        let normalize = |args: &[&str]| {
+            normalize_args(args.iter().map(OsString::from)).map(|args| {
+                args.into_iter()
+                    .map(|arg| arg.into_string().unwrap())
+                    .collect::<Vec<_>>()
+            })
+        };
+        assert_eq!(
+            normalize(&[
+                "grep-rust",
+                "-rnF",
+                "-A3",
+                "-e",
+                "-r",
+                "--regexp=-y",
+                "x",
+                "--",
+                "-E"
+            ])
+            .unwrap(),
+            vec![
+                "grep-rust",
+                "--directories=recurse",
+                "-n",
+                "-A3",
+                "-e",
+                "-r",
+                "--regexp=-y",
+                "x",
+                "--",
+                "-E"
+            ]
+        );
+        assert_eq!(
+            normalize(&["grep-rust", "-sy", "--recursive", "x", "."]).unwrap(),
+            vec![
+                "grep-rust",
+                "-s",
+                "--ignore-case",
+                "--directories=recurse",
+                "x",
+                "."
+            ]
+        );
+        assert!(normalize(&["grep-rust", "-iE", "a|b", "f"]).is_err());
+        assert!(normalize(&["grep-rust", "--files-without-match", "a", "f"]).is_err());
+    }
+
+    #[test]
+    fn test_config_with_no_messages() {
+        let args = vec!["grep-rust", "-s", "pattern", "missing.txt"];
+        let config = Config::parse_from(args);
+        assert!(config.no_messages);
+    }
+
+    #[test]
+    fn test_config_with_replace() {
+        let args = vec![
+            "grep-rust",
+            "--replace",
+            "colour",
+            "--preview",
+            "color",
+            "a.txt",
+        ];
+        let config = Config::parse_from(args);
+        assert_eq!(config.replace.as_deref(), Some("colour"));
+        assert!(config.preview);
+
+        let args = vec!["grep-rust", "--preview", "color", "a.txt"];
+        assert!(Config::try_parse_from(args).is_err());
+
+        let args = vec![
+            "grep-rust",
+            "--replace",
+            "colour",
+            "--confirm",
+            "color",
+            "a.txt",
+        ];
+        assert!(Config::parse_from(args).confirm);
+        let args = vec!["grep-rust", "--confirm", "color", "a.txt"];
+        assert!(Config::try_parse_from(args).is_err());
+
+        // Options limiting the search to part of a file would leave the
+        // replacement unlimited.
+        for scope in [
+            &["--from", "START"][..],
+            &["--only-comments"],
+            &["--section", "a"],
+            &["--max-total", "1"],
+            &["-c"],
+            &["--pre-glob", "*.gz=zcat"],
+        ] {
+            let args = [
+                &["grep-rust", "--replace", "colour"],
+                scope,
+                &["color", "a.txt"],
+            ]
+            .concat();
+            assert!(Config::try_parse_from(args).is_err());
+        }
+    }
+
+    #[test]
+    fn test_config_with_undo() {
+        let args = vec!["grep-rust", "--undo", "--backup", ".bak", "a.txt"];
+        let config = Config::parse_from(args).resolve_patterns().unwrap();
+        assert!(config.undo);
+        assert_eq!(config.backup.as_deref(), Some(".bak"));
+        assert_eq!(config.file_paths, vec!["a.txt"]);
+
+        let args = vec!["grep-rust", "--undo", "a.txt"];
+        assert!(Config::try_parse_from(args).is_err());
+
+        // A backup is only kept when files are edited or restored.
+        let args = vec!["grep-rust", "--backup", ".bak", "foo", "a.txt"];
+        assert!(Config::try_parse_from(args).is_err());
+
+        let args = vec![
+            "grep-rust",
+            "--replace",
+            "bar",
+            "--backup",
+            ".bak",
+            "foo",
+            "a.txt",
+        ];
+        assert!(Config::try_parse_from(args).is_ok());
+    }
+
+    #[test]
+    fn test_config_with_anchors() {
+        let args = vec!["grep-rust", "--starts-with", "--ends-with", "TODO", "a.txt"];
+        let config = Config::parse_from(args);
+        assert!(config.starts_with);
+        assert!(config.ends_with);
+    }
+
+    #[test]
+    fn test_config_with_presets() {
+        let args = vec![
+            "grep-rust",
+            "--preset",
+            "email",
+            "--preset",
+            "iso-date",
+            "a.txt",
+        ];
+        let config = Config::parse_from(args).resolve_patterns().unwrap();
+        assert_eq!(config.presets, vec![Preset::Email, Preset::IsoDate]);
+        assert_eq!(config.file_paths, vec!["a.txt"]);
+        assert_eq!(config.query, "email', 'iso-date");
+        assert!(config.search_patterns().iter().all(|pattern| pattern.regex));
+
+        let args = vec!["grep-rust", "-e", "TODO", "--preset", "uuid", "a.txt"];
+        let config = Config::parse_from(args).resolve_patterns().unwrap();
+        assert_eq!(config.search_patterns().len(), 2);
+    }
+
+    #[test]
+    fn test_config_with_count_unique() {
+        let args = vec![
+            "grep-rust",
+            "--count-unique",
+            "--preset",
+            "ipv4",
+            "access.log",
+        ];
+        let config = Config::parse_from(args);
+        assert!(config.count_unique);
+        assert!(config.holds_lines());
+        assert!(!config.counts_only());
+
+        let args = vec!["grep-rust", "--count-unique", "-c", "error", "a.log"];
+        assert!(Config::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn test_config_with_time_range() {
+        let args = vec![
+            "grep-rust",
+            "--since",
+            "1h",
+            "--until-time",
+            "2024-01-31",
+            "--time-format",
+            "%b %e %H:%M:%S",
+            "error",
+            "syslog",
+        ];
+        let config = Config::parse_from(args);
+        assert_eq!(config.since.as_deref(), Some("1h"));
+        assert_eq!(config.until_time.as_deref(), Some("2024-01-31"));
+        assert_eq!(config.time_format.as_deref(), Some("%b %e %H:%M:%S"));
+    }
+
+    #[test]
+    fn test_config_with_level() {
+        let args = vec![
+            "grep-rust",
+            "--log-format",
+            "json",
+            "--level",
+            "WARN+",
+            "disk",
+            "app.log",
+        ];
+        let config = Config::parse_from(args);
+        assert_eq!(config.log_format, Some(LogFormat::Json));
+        let level = config.level.unwrap();
+        assert!(level.allows(Level::Warn));
+        assert!(level.allows(Level::Fatal));
+        assert!(!level.allows(Level::Info));
+
+        let args = vec![
+            "grep-rust",
+            "--log-format",
+            "json",
+            "--level",
+            "loud",
+            "x",
+            "a.log",
+        ];
+        assert!(Config::try_parse_from(args).is_err());
+        let args = vec!["grep-rust", "--level", "warn", "x", "a.log"];
+        assert!(Config::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn test_config_with_exec() {
+        let args = vec!["grep-rust", "-l", "FIXME", "--exec", "code {}", "src"];
+        let config = Config::parse_from(args);
+        assert_eq!(config.exec.as_deref(), Some("code {}"));
+        assert!(!config.exec_per_match);
+
+        let args = vec!["grep-rust", "--exec-per-match", "FIXME", "src"];
+        assert!(Config::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn test_config_with_path_display() {
+        let args = vec!["grep-rust", "--relative-to", "..", "TODO", "src"];
+        let config = Config::parse_from(args);
+        assert_eq!(config.relative_to.as_deref(), Some(".."));
+        assert!(!config.absolute_path);
+
+        let args = vec![
+            "grep-rust",
+            "--absolute-path",
+            "--relative-to",
+            "..",
+            "x",
+            "a",
+        ];
+        assert!(Config::try_parse_from(args).is_err());
+
+        let args = vec!["grep-rust", "--path-style", "tilde", "TODO", "src"];
+        assert_eq!(Config::parse_from(args).path_style, PathStyle::Tilde);
+    }
+
+    #[test]
+    fn test_config_with_watch() {
+        let args = vec!["grep-rust", "--watch", "--clear", "TODO", "notes.md"];
+        let config = Config::parse_from(args);
+        assert!(config.watch);
+        assert!(config.clear);
+
+        let args = vec!["grep-rust", "--clear", "TODO", "notes.md"];
+        assert!(Config::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn test_config_with_record_separator() {
+        let args = vec!["grep-rust", "--record-separator", ";", "x", "a.sql"];
+        let config = Config::parse_from(args);
+        assert_eq!(config.record_separator.as_deref(), Some(";"));
+
+        let args = vec![
+            "grep-rust",
+            "--record-separator",
+            ";",
+            "--chunked",
+            "1M",
+            "x",
+            "a.sql",
+        ];
+        assert!(Config::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn test_config_with_csv() {
+        let args = vec!["grep-rust", "--csv", "--column-name", "email", "x", "a.csv"];
+        let config = Config::parse_from(args);
+        assert!(config.csv);
+        assert_eq!(config.column_name.as_deref(), Some("email"));
+
+        let args = vec![
+            "grep-rust",
+            "--csv",
+            "--column-index",
+            "2",
+            "--print-cell",
+            "x",
+            "a.csv",
+        ];
+        let config = Config::parse_from(args);
+        assert_eq!(config.column_index, Some(2));
+        assert!(config.print_cell);
+
+        for args in [
+            vec!["grep-rust", "--csv", "x", "a.csv"],
+            vec!["grep-rust", "--column-index", "2", "x", "a.csv"],
+            vec!["grep-rust", "--csv", "--column-index", "0", "x", "a.csv"],
+            vec![
+                "grep-rust",
+                "--csv",
+                "--column-index",
+                "1",
+                "--column-name",
+                "a",
+            ],
+        ] {
+            assert!(Config::try_parse_from(args).is_err());
+        }
+    }
+
+    #[test]
+    fn test_config_with_json_path() {
+        let args = vec![
+            "grep-rust",
+            "--json-path",
+            ".request.url",
+            "/api",
+            "a.jsonl",
+        ];
+        let config = Config::parse_from(args);
+        let segments = config.json_path.unwrap().segments;
+        assert_eq!(segments, vec!["request", "url"]);
+
+        let args = vec!["grep-rust", "--json-path", "request", "/api", "a.jsonl"];
+        assert!(Config::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn test_config_with_strip_tags() {
+        let args = vec!["grep-rust", "--strip-tags", "read more", "index.html"];
+        assert!(Config::parse_from(args).strip_tags);
+    }
+
+    #[test]
+    fn test_config_with_markdown_scope() {
+        let args = vec!["grep-rust", "--markdown-scope", "code", "run", "README.md"];
+        let config = Config::parse_from(args);
+        assert_eq!(config.markdown_scope, Some(MarkdownScope::Code));
+
+        let args = vec!["grep-rust", "--markdown-scope", "lists", "run", "README.md"];
+        assert!(Config::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn test_config_with_hex_pattern() {
+        let args = vec!["grep-rust", "--hex-pattern", "DE AD", "fw.bin"];
+        let config = Config::parse_from(args).resolve_patterns().unwrap();
+        assert_eq!(config.query, "DE AD");
+        assert_eq!(config.file_paths, vec!["fw.bin"]);
+    }
+
+    #[test]
+    fn test_config_with_preserve_case() {
+        let args = vec![
+            "grep-rust",
+            "-i",
+            "--replace",
+            "colour",
+            "--preserve-case",
+            "color",
+            "f",
+        ];
+        assert!(Config::parse_from(args).preserve_case);
+
+        let args = vec!["grep-rust", "--preserve-case", "color", "f"];
+        assert!(Config::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn test_config_with_null() {
+        let args = vec![
+            "grep-rust",
+            "--files-with-matches",
+            "-0",
+            "pattern",
+            "a.txt",
+        ];
+        let config = Config::parse_from(args);
+        assert!(config.null);
+    }
+
+    #[test]
+    fn test_config_with_sort() {
+        let args = vec!["grep-rust", "--sortr", "modified", "pattern", "a.txt"];
+        let config = Config::parse_from(args);
+        assert_eq!(config.sort, None);
+        assert_eq!(config.sortr, Some(SortBy::Modified));
+    }
+
+    #[test]
+    fn test_config_with_directory_action() {
+        let args = vec!["grep-rust", "-d", "recurse", "pattern", "src"];
+        let config = Config::parse_from(args);
+        assert_eq!(config.directories, DirectoryAction::Recurse);
+    }
+
+    #[test]
+    fn test_config_with_regex_limits() {
+        let args = vec![
+            "grep-rust",
+            "--regex-size-limit",
+            "100M",
+            "--dfa-size-limit",
+            "10M",
+            "pattern",
+            "a.txt",
+        ];
+        let config = Config::parse_from(args);
+        assert_eq!(config.regex_size_limit, Some(100 * 1024 * 1024));
+        assert_eq!(config.dfa_size_limit, Some(10 * 1024 * 1024));
+    }
+
+    #[test]
+    fn test_config_with_pre_glob() {
+        let args = vec![
+            "grep-rust",
+            "--pre-glob",
+            "*.pdf=pdftotext - -",
+            "--pre-glob",
+            "*.gz=gzip -dc",
+            "pattern",
+            "docs",
+        ];
+        let config = Config::parse_from(args);
+        assert_eq!(config.pre_glob.len(), 2);
+        assert_eq!(config.pre_glob[0].glob, "*.pdf");
+        assert_eq!(config.pre_glob[0].command, "pdftotext - -");
+        assert!(parse_pre_glob("*.pdf").is_err());
+    }
+
+    #[test]
+    fn test_config_with_scopes() {
+        let args = vec!["grep-rust", "--only-comments", "TODO", "src"];
+        let config = Config::parse_from(args);
+        assert!(config.only_comments);
+        assert!(!config.only_strings);
+    }
+
+    #[test]
+    fn test_config_with_output_style() {
+        let args = vec!["grep-rust", "--heading", "pattern", "a.txt", "b.txt"];
+        let config = Config::parse_from(args);
+        assert!(config.heading);
+        assert!(!config.no_smart_defaults);
+
+        let args = vec![
+            "grep-rust",
+            "--no-smart-defaults",
+            "--no-summary",
+            "pattern",
+            "a.txt",
+        ];
+        let config = Config::parse_from(args);
+        assert!(config.no_smart_defaults);
+        assert!(config.no_summary);
+    }
+
+    #[test]
+    fn test_config_with_only_matching_and_byte_offset() {
+        let args = vec!["grep-rust", "-o", "-b", "pattern", "a.txt"];
+        let config = Config::parse_from(args);
+        assert!(config.only_matching);
+        assert!(config.byte_offset);
+    }
+
+    #[test]
+    fn test_config_with_sort_lines() {
+        let args = vec!["grep-rust", "--sort-lines", "--unique", "pattern", "a.txt"];
+        let config = Config::parse_from(args);
+        assert!(config.sort_lines);
+        assert!(config.unique);
+
+        let args = vec!["grep-rust", "--unique", "pattern", "a.txt"];
+        assert!(Config::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn test_config_with_quickfix() {
+        let args = vec!["grep-rust", "--quickfix", "errors.qf", "pattern", "src"];
+        let config = Config::parse_from(args);
+        assert_eq!(config.quickfix.as_deref(), Some("errors.qf"));
+    }
+
+    #[test]
+    fn test_config_with_emacs() {
+        let args = vec!["grep-rust", "--emacs", "pattern", "src"];
+        let config = Config::parse_from(args);
+        assert!(config.emacs);
+    }
+
+    #[test]
+    fn test_config_with_format() {
+        let args = vec!["grep-rust", "pattern", "src"];
+        let config = Config::parse_from(args);
+        assert_eq!(config.format, OutputFormat::Text);
+        assert!(!config.holds_lines());
+
+        let args = vec!["grep-rust", "--format", "codequality", "pattern", "src"];
+        let config = Config::parse_from(args);
+        assert_eq!(config.format, OutputFormat::Codequality);
+        assert!(config.holds_lines());
+
+        let args = vec!["grep-rust", "--format", "json", "pattern", "src"];
+        let config = Config::parse_from(args);
+        assert_eq!(config.format, OutputFormat::Json);
+
+        let args = vec!["grep-rust", "--format", "junit", "pattern", "src"];
+        let config = Config::parse_from(args);
+        assert_eq!(config.format, OutputFormat::Junit);
+    }
+
+    #[test]
+    fn test_config_with_histogram() {
+        let args = vec!["grep-rust", "--histogram", "pattern", "a.txt"];
+        let config = Config::parse_from(args);
+        assert_eq!(config.histogram, Some(HistogramBy::File));
+        assert!(config.counts_only());
+
+        let args = vec!["grep-rust", "--histogram=dir", "pattern", "a.txt"];
+        let config = Config::parse_from(args);
+        assert_eq!(config.histogram, Some(HistogramBy::Dir));
+    }
+
+    #[test]
+    fn test_config_with_top() {
+        let args = vec!["grep-rust", "--top", "5", "pattern", "src"];
+        let config = Config::parse_from(args);
+        assert_eq!(config.top, Some(5));
+        assert!(config.counts_only());
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("64k"), Ok(64 * 1024));
+        assert_eq!(parse_size("16M"), Ok(16 * 1024 * 1024));
+        assert_eq!(parse_size("1GB"), Ok(1024 * 1024 * 1024));
+        assert!(parse_size("12X").is_err());
+        assert!(parse_size("M").is_err());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+        assert_eq!(parse_duration("1h"), Ok(Duration::from_secs(3600)));
+        assert!(parse_duration("5d").is_err());
+    }
+
+    #[test]
+    fn test_parse_json_path() {
+        let segments = |path| parse_json_path(path).map(|path| path.segments);
+        assert_eq!(
+            segments(".a.b[2].c"),
+            Ok(vec!["a".into(), "b".into(), "2".into(), "c".into()])
+        );
+        assert_eq!(segments("/a~1b/0"), Ok(vec!["a/b".into(), "0".into()]));
+        assert_eq!(segments("."), Ok(vec![]));
+        assert!(segments("a.b").is_err());
+        assert!(segments(".a..b").is_err());
+        assert!(segments(".a[x]").is_err());
+    }
+
+    #[test]
+    fn test_parse_hex_pattern() {
+        let pattern = parse_hex_pattern("DE ad ?? ef").unwrap();
+        assert_eq!(
+            pattern.bytes,
+            vec![Some(0xDE), Some(0xAD), None, Some(0xEF)]
+        );
+        assert_eq!(pattern.to_string(), "DE AD ?? EF");
+        assert!(parse_hex_pattern("").is_err());
+        assert!(parse_hex_pattern("ABC").is_err());
+        assert!(parse_hex_pattern("GG").is_err());
+    }
+
+    #[test]
+    fn test_parse_separator() {
+        assert_eq!(parse_separator(";"), Ok(";".to_string()));
+        assert_eq!(parse_separator(r"\f"), Ok("\x0c".to_string()));
+        assert_eq!(parse_separator(r"--\n"), Ok("--\n".to_string()));
+        assert!(parse_separator("").is_err());
+        assert!(parse_separator(r"\q").is_err());
+    }
 }
@@ -0,0 +1,94 @@
+//! Reads rows of comma-separated values, for `--csv`.
+//!
+//! With `--csv`, every line is read as a CSV row, and only matches within
+//! the cell of the column chosen with `--column-name` or `--column-index`
+//! count. Fields are split the way RFC 4180 quotes them, so a comma inside a
+//! quoted field does not shift the columns after it. Quoted fields spanning
+//! several lines are not supported: every line is a row of its own.
+
+use std::ops::Range;
+
+/// Returns the byte ranges of the fields of `row`, inside the quotes of
+/// quoted fields. Doubled quotes within a field are left as they are.
+pub fn fields(row: &str) -> Vec<Range<usize>> {
+    let bytes = row.as_bytes();
+    let mut fields = Vec::new();
+    let mut pos = 0;
+    loop {
+        let end = if bytes.get(pos) == Some(&b'"') {
+            let close = closing_quote(bytes, pos + 1);
+            fields.push(pos + 1..close);
+            // Anything between the closing quote and the next comma is
+            // malformed and skipped.
+            find_comma(bytes, close)
+        } else {
+            let end = find_comma(bytes, pos);
+            fields.push(pos..end);
+            end
+        };
+        if end == bytes.len() {
+            return fields;
+        }
+        pos = end + 1;
+    }
+}
+
+/// Returns the byte range of the cell in column `index`, counting from 0, or
+/// `None` if `row` has fewer columns.
+pub fn cell(row: &str, index: usize) -> Option<Range<usize>> {
+    fields(row).into_iter().nth(index)
+}
+
+/// Returns the index of the column named `name` in the `header` row,
+/// counting from 0.
+pub fn column_index(header: &str, name: &str) -> Option<usize> {
+    fields(header)
+        .into_iter()
+        .position(|field| header[field].replace("\"\"", "\"") == name)
+}
+
+/// Returns the position of the quote closing a quoted field whose content
+/// starts at `pos`, or the end of `bytes` if it is never closed.
+fn closing_quote(bytes: &[u8], mut pos: usize) -> usize {
+    while pos < bytes.len() {
+        if bytes[pos] == b'"' {
+            if bytes.get(pos + 1) != Some(&b'"') {
+                return pos;
+            }
+            pos += 1;
+        }
+        pos += 1;
+    }
+    bytes.len()
+}
+
+/// Returns the position of the first comma from `pos` on, or the end of
+/// `bytes` if there is none.
+fn find_comma(bytes: &[u8], pos: usize) -> usize {
+    bytes[pos.min(bytes.len())..]
+        .iter()
+        .position(|&b| b == b',')
+        .map_or(bytes.len(), |offset| pos + offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fields() {
+        let row = r#"1,"Doe, Jane","say ""hi""",,x"#;
+        let cells: Vec<&str> = fields(row).into_iter().map(|f| &row[f]).collect();
+        assert_eq!(cells, vec!["1", "Doe, Jane", r#"say ""hi"""#, "", "x"]);
+        assert_eq!(fields("").len(), 1);
+        assert_eq!(cell("a,b", 2), None);
+    }
+
+    #[test]
+    fn test_column_index() {
+        let header = r#"id,"full name",email"#;
+        assert_eq!(column_index(header, "full name"), Some(1));
+        assert_eq!(column_index(header, "email"), Some(2));
+        assert_eq!(column_index(header, "phone"), None);
+    }
+}
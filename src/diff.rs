@@ -0,0 +1,189 @@
+//! Reads unified diffs for `--diff`, which searches only the added lines.
+//!
+//! The diff is read from standard input when it is piped in, and otherwise
+//! taken from `git diff`. Only lines added by the diff are searched, and they
+//! are reported with their line numbers in the new version of each file, so
+//! a pre-merge check ignores occurrences that were there before.
+
+use std::{
+    io::{self, IsTerminal, Read},
+    process::Command,
+};
+
+/// The lines a diff adds to a single file.
+#[derive(Debug, PartialEq, Eq)]
+pub struct DiffFile {
+    /// The path of the file in its new version.
+    pub path: String,
+    /// The added lines with their line numbers, in ascending order.
+    pub added: Vec<(usize, String)>,
+}
+
+impl DiffFile {
+    /// Returns whether the line with number `line_num` was added.
+    pub fn is_added(&self, line_num: usize) -> bool {
+        self.added
+            .binary_search_by_key(&line_num, |(num, _)| *num)
+            .is_ok()
+    }
+
+    /// Returns the text to search for this file.
+    ///
+    /// Each added line is placed at its line number, and all other lines are
+    /// left empty, so that the line numbers of matches are those of the new
+    /// version of the file.
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        let mut next_line = 1;
+        for (line_num, line) in &self.added {
+            while next_line < *line_num {
+                text.push('\n');
+                next_line += 1;
+            }
+            text.push_str(line);
+            text.push('\n');
+            next_line += 1;
+        }
+        text
+    }
+}
+
+/// Reads the diff to search.
+///
+/// A diff piped into standard input is read from there. Otherwise, the
+/// uncommitted changes below the current directory are taken from `git diff`,
+/// limited to `paths` if any are given. CI jobs often run with an empty
+/// standard input, which is treated as if nothing was piped in.
+pub fn read_diff(paths: &[String]) -> io::Result<String> {
+    if !io::stdin().is_terminal() {
+        let mut diff = String::new();
+        io::stdin().read_to_string(&mut diff)?;
+        if !diff.is_empty() {
+            return Ok(diff);
+        }
+    }
+
+    // `--relative` gives paths relative to the current directory rather than
+    // to the root of the repository, as the rest of the output uses.
+    let output = Command::new("git")
+        .args(["diff", "--no-color", "--no-ext-diff", "--relative", "--"])
+        .args(paths)
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    String::from_utf8(output.stdout).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Parses a unified diff into the lines it adds to each file.
+///
+/// Deleted files and files without added lines are left out.
+pub fn parse_diff(diff: &str) -> Vec<DiffFile> {
+    let mut files: Vec<DiffFile> = Vec::new();
+    // The number of old and new lines left in the current hunk, and the line
+    // number of the next new line.
+    let mut old_left: usize = 0;
+    let mut new_left: usize = 0;
+    let mut line_num = 0;
+
+    for line in diff.lines() {
+        if old_left > 0 || new_left > 0 {
+            match line.as_bytes().first() {
+                Some(b'+') => {
+                    if let Some(file) = files.last_mut() {
+                        file.added.push((line_num, line[1..].to_string()));
+                    }
+                    new_left = new_left.saturating_sub(1);
+                    line_num += 1;
+                }
+                Some(b'-') => old_left = old_left.saturating_sub(1),
+                // "\ No newline at end of file" belongs to the previous line.
+                Some(b'\\') => {}
+                _ => {
+                    old_left = old_left.saturating_sub(1);
+                    new_left = new_left.saturating_sub(1);
+                    line_num += 1;
+                }
+            }
+        } else if let Some(path) = line.strip_prefix("+++ ") {
+            files.push(DiffFile {
+                path: new_path(path),
+                added: Vec::new(),
+            });
+        } else if let Some(hunk) = line.strip_prefix("@@ ")
+            && let Some((old, new)) = parse_hunk_header(hunk)
+        {
+            (old_left, new_left, line_num) = (old.1, new.1, new.0);
+        }
+    }
+
+    files.retain(|file| file.path != "/dev/null" && !file.added.is_empty());
+    files
+}
+
+/// Returns the path of a `+++` line, without the `b/` prefix git adds and
+/// the timestamp `diff -u` adds.
+fn new_path(path: &str) -> String {
+    let path = path.split('\t').next().unwrap_or(path);
+    path.strip_prefix("b/").unwrap_or(path).to_string()
+}
+
+/// Parses the ranges of a hunk header such as `-1,3 +1,4 @@`.
+///
+/// # Returns
+/// The start and length of the old and the new range, or `None` if the
+/// header is malformed.
+fn parse_hunk_header(hunk: &str) -> Option<((usize, usize), (usize, usize))> {
+    let mut ranges = hunk.split(' ');
+    let old = parse_range(ranges.next()?.strip_prefix('-')?)?;
+    let new = parse_range(ranges.next()?.strip_prefix('+')?)?;
+    Some((old, new))
+}
+
+/// Parses a hunk range such as `12,3`, where a missing length means 1.
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    match range.split_once(',') {
+        Some((start, len)) => Some((start.parse().ok()?, len.parse().ok()?)),
+        None => Some((range.parse().ok()?, 1)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_diff() {
+        let diff = "\
+diff --git a/src/a.rs b/src/a.rs
+--- a/src/a.rs
++++ b/src/a.rs
+@@ -1,3 +1,4 @@
+ fn main() {
+-    old();
++    new();
++++counter;
+ }
+@@ -10 +11,0 @@
+-gone
+diff --git a/old.rs b/old.rs
+--- a/old.rs
++++ /dev/null
+@@ -1 +0,0 @@
+-removed
+";
+        let files = parse_diff(diff);
+        assert_eq!(
+            files,
+            vec![DiffFile {
+                path: "src/a.rs".to_string(),
+                added: vec![(2, "    new();".to_string()), (3, "++counter;".to_string())],
+            }]
+        );
+        assert!(files[0].is_added(3));
+        assert!(!files[0].is_added(1));
+        assert_eq!(files[0].text(), "\n    new();\n++counter;\n");
+    }
+}
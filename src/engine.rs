@@ -0,0 +1,68 @@
+//! The regex engine behind the matcher.
+//!
+//! By default, patterns are compiled with the `regex` crate. Building with
+//! `--no-default-features --features lite` swaps it for `regex-lite`, which
+//! compiles much faster and makes a far smaller binary, at the price of
+//! slower searches and ASCII-only case folding and `\b`. The rest of the
+//! program only uses the engine through this module, so the switch is
+//! transparent to it.
+
+#[cfg(not(any(feature = "regex", feature = "lite")))]
+compile_error!("either the `regex` or the `lite` feature must be enabled");
+
+#[cfg(not(feature = "lite"))]
+pub use regex::{Error, Matches, Regex, RegexBuilder, escape};
+#[cfg(feature = "lite")]
+pub use regex_lite::{Error, Matches, Regex, RegexBuilder, escape};
+
+/// Sets the size limit of the lazy DFA of `builder`. `regex-lite` has no
+/// lazy DFA, so there the limit is ignored.
+#[cfg(not(feature = "lite"))]
+pub fn dfa_size_limit(builder: &mut RegexBuilder, limit: usize) {
+    builder.dfa_size_limit(limit);
+}
+
+/// Sets the size limit of the lazy DFA of `builder`. `regex-lite` has no
+/// lazy DFA, so there the limit is ignored.
+#[cfg(feature = "lite")]
+pub fn dfa_size_limit(_builder: &mut RegexBuilder, _limit: usize) {}
+
+/// Returns whether `error` reports a pattern that compiled to more than the
+/// size limit.
+#[cfg(not(feature = "lite"))]
+pub fn is_too_big(error: &Error) -> bool {
+    matches!(error, Error::CompiledTooBig(_))
+}
+
+/// Returns whether `error` reports a pattern that compiled to more than the
+/// size limit. The errors of `regex-lite` do not tell, so every error is
+/// reported as an invalid pattern.
+#[cfg(feature = "lite")]
+pub fn is_too_big(_error: &Error) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        let regex = Regex::new(&escape("a.b(")).unwrap();
+        assert!(regex.is_match("x a.b( y"));
+        assert!(!regex.is_match("axb("));
+    }
+
+    #[test]
+    fn test_size_limits() {
+        let mut builder = RegexBuilder::new("a{100}b{100}");
+        dfa_size_limit(&mut builder, 1 << 20);
+        assert!(builder.build().is_ok());
+
+        let error = RegexBuilder::new("a{100}b{100}")
+            .size_limit(10)
+            .build()
+            .unwrap_err();
+        assert_eq!(is_too_big(&error), cfg!(not(feature = "lite")));
+    }
+}
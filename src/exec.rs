@@ -0,0 +1,151 @@
+//! Runs a command for every matching file or line, for `--exec`.
+//!
+//! Like `find -exec`, the command is given with a `{}` placeholder, which is
+//! replaced by the path of the matching file. More of the match can be
+//! passed with `{path}`, `{line}`, `{column}`, `{text}` (the matching line)
+//! and `{match}` (the matched text); for a whole file, they describe its
+//! first match. Every value is quoted for the shell as a single word, so
+//! that spaces, quotes or `;` in it reach the command unchanged. A command
+//! without any placeholder gets the path appended, the way `xargs` passes
+//! it. Other text in braces, such as the `${HOME}` of the shell, is left
+//! alone.
+//!
+//! The command runs through the platform's shell, with the terminal of
+//! `grep-rust` as its own, so e.g. `--exec 'code {}'` opens every matching
+//! file in an editor.
+
+use crate::preprocessor::shell_command;
+
+use std::{io, process::ExitStatus};
+
+/// The values of the placeholders of a command.
+pub struct Fields<'a> {
+    /// The path of the matching file.
+    pub path: &'a str,
+    /// The number of the matching line.
+    pub line: Option<usize>,
+    /// The 1-based byte column of the match in the line.
+    pub column: Option<u64>,
+    /// The matching line.
+    pub text: Option<&'a str>,
+    /// The matched text.
+    pub matched: Option<&'a str>,
+}
+
+/// Runs `template` with its placeholders replaced by `fields`.
+///
+/// # Returns
+/// The exit status of the command, or an error if it cannot be started.
+pub fn run(template: &str, fields: &Fields) -> io::Result<ExitStatus> {
+    shell_command(&command_line(template, fields)).status()
+}
+
+/// Returns `template` with its placeholders replaced by the quoted
+/// `fields`. Placeholders of fields that are not known are replaced by an
+/// empty word.
+fn command_line(template: &str, fields: &Fields) -> String {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+    let mut replaced = false;
+    while let Some(open) = rest.find('{') {
+        output.push_str(&rest[..open]);
+        rest = &rest[open..];
+        let Some(close) = rest.find('}') else {
+            break;
+        };
+        let value = match &rest[1..close] {
+            "" | "path" => Some(fields.path.to_string()),
+            "line" => Some(fields.line.map(|line| line.to_string()).unwrap_or_default()),
+            "column" => Some(
+                fields
+                    .column
+                    .map(|column| column.to_string())
+                    .unwrap_or_default(),
+            ),
+            "text" => Some(fields.text.unwrap_or_default().to_string()),
+            "match" => Some(fields.matched.unwrap_or_default().to_string()),
+            _ => None,
+        };
+        match value {
+            Some(value) => {
+                output.push_str(&shell_quote(&value));
+                rest = &rest[close + 1..];
+                replaced = true;
+            }
+            None => {
+                output.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    output.push_str(rest);
+
+    if !replaced {
+        output.push(' ');
+        output.push_str(&shell_quote(fields.path));
+    }
+    output
+}
+
+/// Quotes `text` as a single word for the shell.
+#[cfg(not(windows))]
+pub fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+/// Quotes `text` as a single word for the shell.
+#[cfg(windows)]
+pub fn shell_quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(not(windows))]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("a b.txt"), "'a b.txt'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+    }
+
+    /// Returns the fields of a match of `matched` in `text`.
+    fn fields<'a>(path: &'a str, text: &'a str, matched: &'a str) -> Fields<'a> {
+        Fields {
+            path,
+            line: Some(3),
+            column: text.find(matched).map(|start| start as u64 + 1),
+            text: Some(text),
+            matched: Some(matched),
+        }
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn test_command_line() {
+        let fields = fields("a b.txt", "x = it's", "it's");
+        assert_eq!(
+            command_line("vim +{line} {path} # {column} {match}", &fields),
+            r"vim +'3' 'a b.txt' # '5' 'it'\''s'"
+        );
+        assert_eq!(
+            command_line("echo ${HOME}", &fields),
+            "echo ${HOME} 'a b.txt'"
+        );
+        assert_eq!(command_line("cat {} {", &fields), "cat 'a b.txt' {");
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn test_run() {
+        let fields = fields("it's $HOME", "a;b", "a;b");
+        assert!(run("test -n {}", &fields).unwrap().success());
+        assert!(
+            run("test \"$(printf %s {text})\" = 'a;b'", &fields)
+                .unwrap()
+                .success()
+        );
+        assert!(!run("test -z", &fields).unwrap().success());
+    }
+}
@@ -0,0 +1,167 @@
+//! Provides shell-style glob matching for file paths.
+//!
+//! The supported syntax is the one most shells understand:
+//!
+//! * `*` matches any sequence of characters except `/`.
+//! * `**` matches any sequence of characters, including `/`, so
+//!   `src/**/*.rs` matches Rust files at any depth below `src`.
+//! * `?` matches any single character except `/`.
+//! * `[abc]`, `[a-z]` and `[!abc]` match a single character from (or not
+//!   from) a set.
+//!
+//! Patterns without a `/` are matched against the file name only, so `*.log`
+//! matches `logs/app.log` as well. Patterns containing a `/` are matched
+//! against the whole path.
+
+/// A compiled glob pattern.
+#[derive(Clone, Debug)]
+pub struct Glob {
+    /// The pattern's characters.
+    pattern: Vec<char>,
+    /// Whether the pattern is matched against the whole path rather than
+    /// just the file name.
+    match_full_path: bool,
+}
+
+impl Glob {
+    /// Creates a new `Glob` from a pattern string.
+    pub fn new(pattern: &str) -> Self {
+        Glob {
+            pattern: pattern.chars().collect(),
+            match_full_path: pattern.contains('/'),
+        }
+    }
+
+    /// Returns whether `path` matches this glob.
+    ///
+    /// A leading `./` on the path is ignored, so `./src/main.rs` matches the
+    /// same patterns as `src/main.rs`.
+    pub fn is_match(&self, path: &str) -> bool {
+        let path = path.strip_prefix("./").unwrap_or(path);
+        let subject = if self.match_full_path {
+            path
+        } else {
+            path.rsplit('/').next().unwrap_or(path)
+        };
+        let subject: Vec<char> = subject.chars().collect();
+        matches(&self.pattern, &subject)
+    }
+}
+
+/// Returns whether `text` contains any glob metacharacters.
+pub fn has_glob_chars(text: &str) -> bool {
+    text.contains(['*', '?', '['])
+}
+
+/// Matches `text` against `pattern`, backtracking over `*` and `**`.
+fn matches(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') if pattern.get(1) == Some(&'*') => {
+            let rest = &pattern[2..];
+            // `**/` may also match no directory at all.
+            let rest_without_slash = rest.strip_prefix(&['/']);
+            (0..=text.len()).any(|start| {
+                matches(rest, &text[start..])
+                    || rest_without_slash.is_some_and(|rest| matches(rest, &text[start..]))
+            })
+        }
+        Some('*') => {
+            let rest = &pattern[1..];
+            for start in 0..=text.len() {
+                if matches(rest, &text[start..]) {
+                    return true;
+                }
+                if text.get(start) == Some(&'/') {
+                    break;
+                }
+            }
+            false
+        }
+        Some('?') => {
+            matches!(text.first(), Some(c) if *c != '/') && matches(&pattern[1..], &text[1..])
+        }
+        Some('[') => match parse_class(&pattern[1..]) {
+            Some((class, rest)) => match text.first() {
+                Some(c) if *c != '/' && class.contains(*c) => matches(rest, &text[1..]),
+                _ => false,
+            },
+            // An unterminated `[` is matched literally.
+            None => text.first() == Some(&'[') && matches(&pattern[1..], &text[1..]),
+        },
+        Some(c) => text.first() == Some(c) && matches(&pattern[1..], &text[1..]),
+    }
+}
+
+/// A character class such as `[a-z]` or `[!abc]`.
+struct CharClass<'a> {
+    /// The characters between the brackets, without a leading `!`.
+    items: &'a [char],
+    /// Whether the class was negated with a leading `!`.
+    negated: bool,
+}
+
+impl CharClass<'_> {
+    /// Returns whether `c` belongs to this class.
+    fn contains(&self, c: char) -> bool {
+        let mut found = false;
+        let mut i = 0;
+        while i < self.items.len() {
+            if i + 2 < self.items.len() && self.items[i + 1] == '-' {
+                found |= (self.items[i]..=self.items[i + 2]).contains(&c);
+                i += 3;
+            } else {
+                found |= self.items[i] == c;
+                i += 1;
+            }
+        }
+        found != self.negated
+    }
+}
+
+/// Parses a character class starting right after its `[`.
+///
+/// # Returns
+/// The class and the rest of the pattern after its closing `]`, or `None` if
+/// the class is not terminated.
+fn parse_class(pattern: &[char]) -> Option<(CharClass<'_>, &[char])> {
+    let negated = pattern.first() == Some(&'!');
+    let body = if negated { &pattern[1..] } else { pattern };
+    // A `]` right at the start is part of the set, not its end.
+    let end = body
+        .iter()
+        .skip(1)
+        .position(|&c| c == ']')
+        .map(|position| position + 1)?;
+    Some((
+        CharClass {
+            items: &body[..end],
+            negated,
+        },
+        &body[end + 1..],
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_name_patterns() {
+        let glob = Glob::new("*.log");
+        assert!(glob.is_match("app.log"));
+        assert!(glob.is_match("logs/app.log"));
+        assert!(!glob.is_match("app.log.gz"));
+        assert!(Glob::new("file?.[a-c]").is_match("file1.b"));
+        assert!(!Glob::new("file?.[!a-c]").is_match("file1.b"));
+    }
+
+    #[test]
+    fn test_path_patterns() {
+        let glob = Glob::new("src/**/*.rs");
+        assert!(glob.is_match("src/main.rs"));
+        assert!(glob.is_match("./src/a/b/lib.rs"));
+        assert!(!glob.is_match("tests/main.rs"));
+        assert!(!Glob::new("src/*.rs").is_match("src/a/lib.rs"));
+    }
+}
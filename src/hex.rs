@@ -0,0 +1,240 @@
+//! Searches the raw bytes of files, for `--hex-pattern`.
+//!
+//! Firmware images and binary file formats are not made of lines, so the
+//! pattern is given as hexadecimal bytes, e.g. `'DE AD BE EF'`, with `??`
+//! matching any byte. Every match is shown in an `xxd`-style hex dump: the
+//! rows of 16 bytes holding it, each with its offset, the bytes in hex and
+//! their printable ASCII characters, with the matched bytes highlighted.
+//! Matches in nearby rows share a block, and blocks are separated by `--`.
+
+use crate::config::{Config, HexPattern};
+use crate::interrupt::interrupted;
+use crate::messages::{MessageId, message};
+use crate::paths::fs_path;
+use crate::printer::{
+    Tally, print_file_count, print_file_error, print_file_name, print_heading, print_summary,
+};
+
+use colored::*;
+use memchr::memchr;
+use std::{error::Error, fs, ops::Range, path::Path, time::Instant};
+
+/// The number of bytes in a row of the hex dump.
+const ROW_LEN: usize = 16;
+
+/// Searches the files for the bytes of `pattern` and prints the matches.
+///
+/// # Arguments
+/// * `file_paths` - The files to search.
+/// * `pattern` - The bytes to search for.
+/// * `with_file_name` - Whether to show the names of the files.
+/// * `config` - The search configuration.
+///
+/// # Returns
+/// Whether any match was found. Files that cannot be read are reported
+/// and skipped.
+pub fn search_files(
+    file_paths: &[String],
+    pattern: &HexPattern,
+    with_file_name: bool,
+    config: &Config,
+) -> Result<bool, Box<dyn Error>> {
+    let start = Instant::now();
+    let mut total_matches = 0;
+    let mut matching_files = 0;
+    for path in file_paths {
+        if interrupted() {
+            break;
+        }
+        let data = match fs::read(fs_path(Path::new(path))) {
+            Ok(data) => data,
+            Err(e) => {
+                print_file_error(&message(MessageId::FileError, &[path, &e]));
+                continue;
+            }
+        };
+        let mut matches = find_all(&data, pattern);
+        if let Some(max_total) = config.max_total {
+            matches.truncate(max_total.saturating_sub(total_matches));
+        }
+        if matches.is_empty() {
+            if config.count && !config.quiet && (!with_file_name || config.include_zero) {
+                print_file_count(with_file_name.then_some(path), 0);
+            }
+            continue;
+        }
+        total_matches += matches.len();
+        matching_files += 1;
+
+        if config.quiet {
+            break;
+        } else if config.files_with_matches {
+            print_file_name(path, config.null);
+        } else if config.count {
+            print_file_count(with_file_name.then_some(path), matches.len());
+        } else {
+            if with_file_name {
+                print_heading(path);
+            }
+            for row in dump(&data, &matches, pattern.bytes.len()) {
+                println!("{}", row);
+            }
+        }
+    }
+
+    if !config.quiet && !config.no_summary {
+        let tally = if config.files_with_matches {
+            Tally::Files
+        } else {
+            Tally::Matches
+        };
+        print_summary(tally, total_matches, matching_files, start.elapsed());
+    }
+    Ok(total_matches > 0)
+}
+
+/// Returns the offsets of the non-overlapping matches of `pattern` in
+/// `data`, from first to last.
+///
+/// Candidates are found with `memchr` on the first byte of the pattern that
+/// is not `??`, so that only they have to be compared in full.
+pub fn find_all(data: &[u8], pattern: &HexPattern) -> Vec<usize> {
+    let len = pattern.bytes.len();
+    let anchor = pattern
+        .bytes
+        .iter()
+        .enumerate()
+        .find_map(|(i, byte)| byte.map(|byte| (i, byte)));
+    let mut matches = Vec::new();
+    let mut offset = 0;
+    while offset + len <= data.len() {
+        if let Some((position, byte)) = anchor {
+            match memchr(
+                byte,
+                &data[offset + position..data.len() - len + position + 1],
+            ) {
+                Some(skipped) => offset += skipped,
+                None => break,
+            }
+        }
+        let found = data[offset..offset + len]
+            .iter()
+            .zip(&pattern.bytes)
+            .all(|(&byte, expected)| expected.is_none_or(|expected| byte == expected));
+        if found {
+            matches.push(offset);
+            offset += len;
+        } else {
+            offset += 1;
+        }
+    }
+    matches
+}
+
+/// Returns the rows of the hex dump showing the matches at `matches`, each
+/// `len` bytes long, with `--` between rows that are not adjacent.
+fn dump(data: &[u8], matches: &[usize], len: usize) -> Vec<String> {
+    let ranges: Vec<Range<usize>> = matches.iter().map(|&start| start..start + len).collect();
+    let mut rows: Vec<usize> = Vec::new();
+    for range in &ranges {
+        let first = range.start / ROW_LEN;
+        let last = (range.end.max(range.start + 1) - 1) / ROW_LEN;
+        for row in first..=last {
+            if rows.last().is_none_or(|&previous| previous < row) {
+                rows.push(row);
+            }
+        }
+    }
+
+    let mut output = Vec::new();
+    for (i, &row) in rows.iter().enumerate() {
+        if i > 0 && rows[i - 1] + 1 != row {
+            output.push("--".to_string());
+        }
+        let start = row * ROW_LEN;
+        let bytes = &data[start..(start + ROW_LEN).min(data.len())];
+        // The ranges are sorted, so only those from the first one reaching
+        // into the row on can hold its bytes.
+        let first = ranges.partition_point(|range| range.end <= start);
+        let matched = |offset: usize| {
+            ranges[first..]
+                .iter()
+                .take_while(|range| range.start <= offset)
+                .any(|range| range.contains(&offset))
+        };
+        output.push(dump_row(start, bytes, matched));
+    }
+    output
+}
+
+/// Formats the bytes of a row at `offset` as `xxd` does, highlighting the
+/// bytes whose offset is `matched`.
+fn dump_row(offset: usize, bytes: &[u8], matched: impl Fn(usize) -> bool) -> String {
+    let mut hex = String::new();
+    let mut ascii = String::new();
+    for (i, &byte) in bytes.iter().enumerate() {
+        if i > 0 && i.is_multiple_of(2) {
+            hex.push(' ');
+        }
+        let hex_byte = format!("{:02x}", byte);
+        let ascii_char = if byte.is_ascii_graphic() || byte == b' ' {
+            (byte as char).to_string()
+        } else {
+            ".".to_string()
+        };
+        if matched(offset + i) {
+            hex.push_str(&hex_byte.green().bold().to_string());
+            ascii.push_str(&ascii_char.green().bold().to_string());
+        } else {
+            hex.push_str(&hex_byte);
+            ascii.push_str(&ascii_char);
+        }
+    }
+    // Short last rows are padded, so that their ASCII column lines up.
+    let hex_width = |len: usize| len * 2 + len.saturating_sub(1) / 2;
+    let padding = hex_width(ROW_LEN) - hex_width(bytes.len());
+    format!(
+        "{}: {}{}  {}",
+        format!("{:08x}", offset).blue(),
+        hex,
+        " ".repeat(padding),
+        ascii
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_hex_pattern;
+
+    #[test]
+    fn test_find_all() {
+        let pattern = parse_hex_pattern("AA ?? AA").unwrap();
+        assert_eq!(find_all(&[0xAA, 0, 0xAA, 1, 0xAA, 0xAA], &pattern), vec![0]);
+        assert_eq!(
+            find_all(&[0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA], &pattern),
+            vec![0, 3]
+        );
+        assert!(find_all(&[0xAA], &pattern).is_empty());
+
+        let pattern = parse_hex_pattern("?? BB").unwrap();
+        assert_eq!(find_all(&[0xBB, 0xBB, 1, 0xBB, 0xBB], &pattern), vec![0, 2]);
+        assert!(find_all(&[0xBB], &pattern).is_empty());
+    }
+
+    #[test]
+    fn test_dump() {
+        colored::control::set_override(false);
+        let mut data = b"0123456789abcdef".repeat(4);
+        data.extend_from_slice(b"\xde\xad!");
+        assert_eq!(
+            dump(&data, &[14, 64], 3),
+            vec![
+                "00000000: 3031 3233 3435 3637 3839 6162 6364 6566  0123456789abcdef",
+                "00000010: 3031 3233 3435 3637 3839 6162 6364 6566  0123456789abcdef",
+                "--",
+                format!("00000040: dead 21{}  ..!", " ".repeat(32)).as_str(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,84 @@
+//! Handles Ctrl-C so that an interrupted search stops cleanly.
+//!
+//! Instead of being killed in the middle of writing a line, the process
+//! notes the interrupt and lets the search loop stop at the next line. The
+//! results found so far are then printed as usual and the process exits with
+//! `INTERRUPTED_EXIT_CODE`. A second Ctrl-C terminates the process at once.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// The exit status of an interrupted search, following the shell convention
+/// of 128 plus the signal number of `SIGINT`.
+pub const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+/// Set by the signal handler once Ctrl-C has been pressed.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Returns whether the search has been interrupted.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+/// Installs the Ctrl-C handler for the rest of the process.
+#[cfg(unix)]
+pub fn install() {
+    use std::ffi::c_int;
+
+    const SIGINT: c_int = 2;
+    const SIG_DFL: usize = 0;
+
+    unsafe extern "C" {
+        fn signal(signum: c_int, handler: usize) -> usize;
+    }
+
+    extern "C" fn handle_sigint(_signum: c_int) {
+        INTERRUPTED.store(true, Ordering::Relaxed);
+        // Restore the default action, so that a second Ctrl-C kills the
+        // process even if the search does not stop in time.
+        // SAFETY: `signal` is async-signal-safe.
+        unsafe {
+            signal(SIGINT, SIG_DFL);
+        }
+    }
+
+    // SAFETY: The handler only stores to an atomic and calls `signal`, both
+    // of which are async-signal-safe.
+    unsafe {
+        signal(SIGINT, handle_sigint as extern "C" fn(c_int) as usize);
+    }
+}
+
+/// Installs the Ctrl-C handler for the rest of the process.
+#[cfg(windows)]
+pub fn install() {
+    const CTRL_C_EVENT: u32 = 0;
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn SetConsoleCtrlHandler(
+            handler: Option<unsafe extern "system" fn(u32) -> i32>,
+            add: i32,
+        ) -> i32;
+    }
+
+    unsafe extern "system" fn handle_ctrl_c(event: u32) -> i32 {
+        // A second Ctrl-C falls through to the default handler, which
+        // terminates the process.
+        if event != CTRL_C_EVENT || INTERRUPTED.swap(true, Ordering::Relaxed) {
+            return 0;
+        }
+        1
+    }
+
+    // SAFETY: The handler only touches an atomic.
+    unsafe {
+        SetConsoleCtrlHandler(Some(handle_ctrl_c), 1);
+    }
+}
+
+/// Installs the Ctrl-C handler for the rest of the process.
+///
+/// Interrupts cannot be caught on this platform, so the default behavior of
+/// terminating the process is kept.
+#[cfg(not(any(unix, windows)))]
+pub fn install() {}
@@ -0,0 +1,204 @@
+//! Finds values in JSON lines, for `--log-format json` and `--json-path`.
+//!
+//! JSON lines are scanned rather than parsed: the scanner only finds where
+//! keys and values start and end in the line, as they are written, so that
+//! matches can be limited to a value and still be reported at their place in
+//! the line. Reading stops at the first thing that is not valid JSON.
+
+use crate::config::JsonPath;
+
+use std::ops::Range;
+
+/// A value in a JSON line: the byte range it takes up, and whether it is a
+/// string, whose range includes the quotes.
+pub struct JsonValue {
+    range: Range<usize>,
+    string: bool,
+}
+
+impl JsonValue {
+    /// Returns the byte range of the value, inside the quotes of a string.
+    pub fn content(&self) -> Range<usize> {
+        if self.string {
+            self.range.start + 1..self.range.end - 1
+        } else {
+            self.range.clone()
+        }
+    }
+}
+
+/// Returns the keys and values of the top-level fields of the JSON object
+/// on `line`.
+pub fn fields(line: &str) -> Vec<(&str, JsonValue)> {
+    object_fields(line, skip_whitespace(line.as_bytes(), 0))
+}
+
+/// Returns the value at `path` in the JSON value on `line`, or `None` if
+/// the line has no such value.
+pub fn find(line: &str, path: &JsonPath) -> Option<JsonValue> {
+    let bytes = line.as_bytes();
+    let start = skip_whitespace(bytes, 0);
+    let mut value = JsonValue {
+        range: start..value_end(bytes, start)?,
+        string: bytes[start] == b'"',
+    };
+    for segment in &path.segments {
+        let start = value.range.start;
+        value = match bytes[start] {
+            b'{' => object_fields(line, start)
+                .into_iter()
+                .find(|(key, _)| key == segment)
+                .map(|(_, value)| value)?,
+            b'[' => array_items(line, start)
+                .into_iter()
+                .nth(segment.parse().ok()?)?,
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+/// Returns the keys and values of the fields of the JSON object starting at
+/// `pos` in `line`.
+fn object_fields(line: &str, mut pos: usize) -> Vec<(&str, JsonValue)> {
+    let bytes = line.as_bytes();
+    let mut fields = Vec::new();
+    if bytes.get(pos) != Some(&b'{') {
+        return fields;
+    }
+    pos += 1;
+    loop {
+        pos = skip_whitespace(bytes, pos);
+        let Some(key_end) = string_end(bytes, pos) else {
+            return fields;
+        };
+        let key = &line[pos + 1..key_end - 1];
+        pos = skip_whitespace(bytes, key_end);
+        if bytes.get(pos) != Some(&b':') {
+            return fields;
+        }
+        pos = skip_whitespace(bytes, pos + 1);
+        let Some(value_end) = value_end(bytes, pos) else {
+            return fields;
+        };
+        fields.push((
+            key,
+            JsonValue {
+                range: pos..value_end,
+                string: bytes[pos] == b'"',
+            },
+        ));
+        pos = skip_whitespace(bytes, value_end);
+        if bytes.get(pos) != Some(&b',') {
+            return fields;
+        }
+        pos += 1;
+    }
+}
+
+/// Returns the items of the JSON array starting at `pos` in `line`.
+fn array_items(line: &str, mut pos: usize) -> Vec<JsonValue> {
+    let bytes = line.as_bytes();
+    let mut items = Vec::new();
+    if bytes.get(pos) != Some(&b'[') {
+        return items;
+    }
+    pos += 1;
+    loop {
+        pos = skip_whitespace(bytes, pos);
+        let Some(value_end) = value_end(bytes, pos) else {
+            return items;
+        };
+        items.push(JsonValue {
+            range: pos..value_end,
+            string: bytes[pos] == b'"',
+        });
+        pos = skip_whitespace(bytes, value_end);
+        if bytes.get(pos) != Some(&b',') {
+            return items;
+        }
+        pos += 1;
+    }
+}
+
+/// Returns the position of the first non-whitespace byte from `pos` on.
+fn skip_whitespace(bytes: &[u8], mut pos: usize) -> usize {
+    while bytes.get(pos).is_some_and(u8::is_ascii_whitespace) {
+        pos += 1;
+    }
+    pos
+}
+
+/// Returns the end of the JSON string starting at `pos`, after its closing
+/// quote.
+pub fn string_end(bytes: &[u8], pos: usize) -> Option<usize> {
+    if bytes.get(pos) != Some(&b'"') {
+        return None;
+    }
+    let mut pos = pos + 1;
+    loop {
+        match bytes.get(pos)? {
+            b'\\' => pos += 2,
+            b'"' => return Some(pos + 1),
+            _ => pos += 1,
+        }
+    }
+}
+
+/// Returns the end of the JSON value starting at `pos`.
+fn value_end(bytes: &[u8], pos: usize) -> Option<usize> {
+    match bytes.get(pos)? {
+        b'"' => string_end(bytes, pos),
+        b'{' | b'[' => {
+            let mut depth = 0;
+            let mut pos = pos;
+            loop {
+                match bytes.get(pos)? {
+                    b'"' => {
+                        pos = string_end(bytes, pos)?;
+                        continue;
+                    }
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Some(pos + 1);
+                        }
+                    }
+                    _ => {}
+                }
+                pos += 1;
+            }
+        }
+        _ => {
+            let len = bytes[pos..]
+                .iter()
+                .position(|&b| matches!(b, b',' | b'}' | b']') || b.is_ascii_whitespace())
+                .unwrap_or(bytes.len() - pos);
+            Some(pos + len)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_json_path;
+
+    /// Returns the text of the value at `path` in `line`.
+    fn value<'a>(line: &'a str, path: &str) -> Option<&'a str> {
+        find(line, &parse_json_path(path).unwrap()).map(|value| &line[value.content()])
+    }
+
+    #[test]
+    fn test_find() {
+        let line = r#"{"request": {"url": "/a?b", "tags": ["x", {"id": 7}]}, "url": "top"}"#;
+        assert_eq!(value(line, ".request.url"), Some("/a?b"));
+        assert_eq!(value(line, ".request.tags[1].id"), Some("7"));
+        assert_eq!(value(line, "/request/tags/0"), Some("x"));
+        assert_eq!(value(line, ".url"), Some("top"));
+        assert_eq!(value(line, ".request.method"), None);
+        assert_eq!(value(line, ".url.x"), None);
+        assert_eq!(value("not json", ".url"), None);
+    }
+}
@@ -0,0 +1,409 @@
+//! Provides a line reader that never holds more than a bounded number of
+//! bytes of a single line in memory.
+//!
+//! Files such as minified JavaScript bundles or JSONL dumps can consist of a
+//! single line that is hundreds of megabytes long. Reading such a line with
+//! `BufRead::lines()` allocates it as one `String`. The `ChunkedLines` reader
+//! instead splits over-long lines into chunks of at most `--max-line-length`
+//! bytes. Each chunk after the first repeats the tail of the previous one (the
+//! "overlap window"), so a match spanning a chunk edge is still found.
+//!
+//! Lines end at a newline by default, or at any other separator given with
+//! `--record-separator`, in which case every "line" is a record.
+//!
+//! Chunks are read into a single buffer that is reused for the whole file,
+//! so the hot loop does not allocate per line. Callers that need to keep a
+//! line around, such as the before-context buffer, copy it out themselves.
+
+use memchr::memmem::Finder;
+use std::io::{self, BufRead};
+
+/// A piece of a line produced by `ChunkedLines`.
+///
+/// Lines that fit within the length cap are returned as a single chunk.
+/// Longer lines are returned as several chunks sharing the same line number.
+/// The text borrows the reader's buffer and is only valid until the next
+/// chunk is read.
+#[derive(Debug, PartialEq, Eq)]
+pub struct LineChunk<'a> {
+    /// The text of the chunk, including the overlap window at its start.
+    pub text: &'a str,
+    /// The number of bytes at the start of `text` that repeat the end of the
+    /// previous chunk. Matches lying entirely within them were already
+    /// reported with the previous chunk.
+    pub overlap_len: usize,
+    /// Whether this chunk continues a line started by a previous chunk.
+    pub continues_line: bool,
+    /// The byte offset of the start of `text` in the input.
+    pub offset: u64,
+}
+
+/// A reader over the lines of a buffered reader that splits lines longer
+/// than a maximum length into overlapping chunks.
+pub struct ChunkedLines<R> {
+    /// The underlying buffered reader.
+    reader: R,
+    /// The maximum number of new bytes read into a single chunk.
+    max_len: usize,
+    /// The bytes ending a line.
+    separator: Vec<u8>,
+    /// Finds separators of several bytes.
+    finder: Finder<'static>,
+    /// The number of bytes repeated from the end of one chunk at the start of
+    /// the next one.
+    overlap: usize,
+    /// The buffer holding the current chunk, reused for every chunk.
+    buf: Vec<u8>,
+    /// Whether the previous chunk was cut off by the length cap, in which
+    /// case its tail is kept at the start of `buf` as the overlap window.
+    carry: bool,
+    /// Bytes of a UTF-8 character that was cut in half by the length cap.
+    pending: Vec<u8>,
+    /// The byte offset in the input of the next chunk's new bytes.
+    offset: u64,
+}
+
+/// Describes how reading a chunk ended.
+enum ChunkEnd {
+    /// The input was exhausted before any byte was read.
+    Eof,
+    /// The line was read up to its separator, which was consumed.
+    Line,
+    /// The line was read up to the end of the input, without a separator.
+    LastLine,
+    /// The line was cut off by the length cap and continues in the next chunk.
+    Cut,
+}
+
+impl<R: BufRead> ChunkedLines<R> {
+    /// Creates a new `ChunkedLines` reader.
+    ///
+    /// # Arguments
+    /// * `reader` - The buffered reader to read lines from.
+    /// * `max_len` - The maximum number of bytes of a line read at once, or
+    ///   `None` to always read whole lines.
+    /// * `overlap` - The number of bytes repeated between consecutive chunks
+    ///   of a split line. This should be at least the longest possible match.
+    /// * `separator` - The bytes ending a line, usually `b"\n"`. Must not be
+    ///   empty.
+    pub fn new(reader: R, max_len: Option<usize>, overlap: usize, separator: &[u8]) -> Self {
+        ChunkedLines {
+            reader,
+            // A chunk must be able to hold at least one full UTF-8 character.
+            max_len: max_len.unwrap_or(usize::MAX).max(4),
+            separator: separator.to_vec(),
+            finder: Finder::new(separator).into_owned(),
+            overlap,
+            buf: Vec::new(),
+            carry: false,
+            pending: Vec::new(),
+            offset: 0,
+        }
+    }
+
+    /// Appends the next chunk's new bytes to `buf`, after the first `start`
+    /// bytes that hold the overlap window.
+    fn read_chunk(&mut self, start: usize) -> io::Result<ChunkEnd> {
+        let buf = &mut self.buf;
+        let mut consumed_any = !self.pending.is_empty();
+        buf.append(&mut self.pending);
+
+        loop {
+            let available = self.reader.fill_buf()?;
+            if available.is_empty() {
+                return Ok(if consumed_any {
+                    ChunkEnd::LastLine
+                } else {
+                    ChunkEnd::Eof
+                });
+            }
+
+            let separator = self.separator.as_slice();
+            // A separator of several bytes may have started in the bytes
+            // read before, in which case it ends at the start of these.
+            if let Some(started) = straddling(&buf[start..], available, separator) {
+                buf.truncate(buf.len() - started);
+                self.reader.consume(separator.len() - started);
+                return Ok(ChunkEnd::Line);
+            }
+
+            let room = self.max_len.saturating_sub(buf.len() - start);
+            if room == 0 {
+                // The chunk is full. A separator right after it still ends the
+                // line here, so that no empty chunk is produced for it.
+                if available.starts_with(separator) {
+                    self.reader.consume(separator.len());
+                    return Ok(ChunkEnd::Line);
+                }
+                // The reader may only hold the start of a separator, which
+                // is taken over the cap until its end is known.
+                if available.len() < separator.len() && separator.starts_with(available) {
+                    let taken = available.len();
+                    buf.extend_from_slice(available);
+                    self.reader.consume(taken);
+                    continue;
+                }
+                return Ok(ChunkEnd::Cut);
+            }
+
+            // Only the bytes up to the separator are copied. A separator
+            // starting within the room left may end beyond it.
+            let searched = &available[..available
+                .len()
+                .min(room.saturating_add(separator.len() - 1))];
+            let found = match separator {
+                [byte] => memchr::memchr(*byte, searched),
+                _ => self.finder.find(searched),
+            };
+            match found {
+                Some(line_end) => {
+                    buf.extend_from_slice(&available[..line_end]);
+                    self.reader.consume(line_end + separator.len());
+                    return Ok(ChunkEnd::Line);
+                }
+                None => {
+                    let taken = available.len().min(room);
+                    buf.extend_from_slice(&available[..taken]);
+                    self.reader.consume(taken);
+                    consumed_any = true;
+                }
+            }
+        }
+    }
+
+    /// Reads the next chunk.
+    ///
+    /// # Returns
+    /// The chunk, `None` at the end of the input, or an error if reading
+    /// fails or the line is not valid UTF-8.
+    pub fn next_chunk(&mut self) -> Option<io::Result<LineChunk<'_>>> {
+        // Keep the tail of a cut-off chunk as the overlap window of the next
+        // one, moving it to the front of the buffer.
+        let overlap_len = if self.carry {
+            let text = std::str::from_utf8(&self.buf).unwrap_or_default();
+            let overlap_start = self.buf.len() - tail(text, self.overlap).len();
+            self.buf.drain(..overlap_start);
+            self.buf.len()
+        } else {
+            self.buf.clear();
+            0
+        };
+        let continues_line = self.carry;
+        let offset = self.offset - overlap_len as u64;
+
+        let (line_done, newline_len) = match self.read_chunk(overlap_len) {
+            Ok(ChunkEnd::Eof) => return None,
+            Ok(ChunkEnd::Line) => (true, self.separator.len()),
+            Ok(ChunkEnd::LastLine) => (true, 0),
+            Ok(ChunkEnd::Cut) => (false, 0),
+            Err(e) => return Some(Err(e)),
+        };
+
+        if line_done {
+            self.offset += (self.buf.len() - overlap_len + newline_len) as u64;
+            // Strip the carriage return of a "\r\n" line ending, as
+            // `BufRead::lines()` does.
+            if self.separator == b"\n"
+                && self.buf.len() > overlap_len
+                && self.buf.last() == Some(&b'\r')
+            {
+                self.buf.pop();
+            }
+        } else {
+            // Never split a UTF-8 character across two chunks: move the bytes
+            // of an incomplete trailing character over to the next chunk.
+            let bytes = &self.buf[overlap_len..];
+            let mut start = bytes.len() - 1;
+            while start > 0 && is_continuation_byte(bytes[start]) && bytes.len() - start < 4 {
+                start -= 1;
+            }
+            if utf8_len(bytes[start]) > bytes.len() - start {
+                self.pending.extend_from_slice(&bytes[start..]);
+                self.buf.truncate(overlap_len + start);
+            }
+            self.offset += (self.buf.len() - overlap_len) as u64;
+        }
+        self.carry = !line_done;
+
+        match std::str::from_utf8(&self.buf) {
+            Ok(text) => Some(Ok(LineChunk {
+                text,
+                overlap_len,
+                continues_line,
+                offset,
+            })),
+            Err(_) => Some(Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "stream did not contain valid UTF-8",
+            ))),
+        }
+    }
+}
+
+/// Returns how many bytes at the end of `read` a separator starts with when
+/// it ends in `available`, if it straddles the two.
+fn straddling(read: &[u8], available: &[u8], separator: &[u8]) -> Option<usize> {
+    (1..separator.len().min(read.len() + 1)).find(|&started| {
+        read.ends_with(&separator[..started]) && available.starts_with(&separator[started..])
+    })
+}
+
+/// Returns whether `byte` is a UTF-8 continuation byte (`0b10xx_xxxx`).
+fn is_continuation_byte(byte: u8) -> bool {
+    byte & 0xC0 == 0x80
+}
+
+/// Returns the length of the UTF-8 character starting with the lead byte.
+fn utf8_len(lead: u8) -> usize {
+    match lead {
+        0xF0.. => 4,
+        0xE0.. => 3,
+        0xC0.. => 2,
+        _ => 1,
+    }
+}
+
+/// Returns at most the last `max_len` bytes of `text`, starting on a
+/// character boundary.
+fn tail(text: &str, max_len: usize) -> &str {
+    let mut start = text.len().saturating_sub(max_len);
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    &text[start..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Reads all chunks of `input` as `(text, overlap_len, continues_line)`.
+    fn chunks(input: &str, max_len: Option<usize>, overlap: usize) -> Vec<(String, usize, bool)> {
+        let mut reader = ChunkedLines::new(Cursor::new(input.as_bytes()), max_len, overlap, b"\n");
+        let mut result = Vec::new();
+        while let Some(chunk) = reader.next_chunk() {
+            let chunk = chunk.unwrap();
+            result.push((
+                chunk.text.to_string(),
+                chunk.overlap_len,
+                chunk.continues_line,
+            ));
+        }
+        result
+    }
+
+    #[test]
+    fn test_reads_whole_lines_without_cap() {
+        let result = chunks("one\r\n\nthree", None, 2);
+        let texts: Vec<&str> = result.iter().map(|c| c.0.as_str()).collect();
+        assert_eq!(texts, vec!["one", "", "three"]);
+        assert!(result.iter().all(|c| c.1 == 0 && !c.2));
+    }
+
+    #[test]
+    fn test_splits_long_lines_with_overlap() {
+        let result = chunks("abcdefghij\nxy\n", Some(4), 2);
+        let texts: Vec<&str> = result.iter().map(|c| c.0.as_str()).collect();
+        assert_eq!(texts, vec!["abcd", "cdefgh", "ghij", "xy"]);
+        assert_eq!(result[1].1, 2);
+        assert!(result[1].2 && result[2].2);
+        assert!(!result[3].2);
+    }
+
+    #[test]
+    fn test_reused_buffer_keeps_no_stale_bytes() {
+        // Short lines follow longer ones and a split line, so every chunk
+        // is read into a buffer that held more bytes before.
+        let result = chunks("abcdefghij\nxyz\nq\n\nlmnopqr\ns", Some(6), 2);
+        let texts: Vec<&str> = result.iter().map(|c| c.0.as_str()).collect();
+        assert_eq!(
+            texts,
+            vec!["abcdef", "efghij", "xyz", "q", "", "lmnopq", "pqr", "s"]
+        );
+        let overlaps: Vec<usize> = result.iter().map(|c| c.1).collect();
+        assert_eq!(overlaps, vec![0, 2, 0, 0, 0, 0, 2, 0]);
+    }
+
+    #[test]
+    fn test_tracks_byte_offsets() {
+        let mut reader = ChunkedLines::new(
+            Cursor::new("ab\r\ncdefgh\nij".as_bytes()),
+            Some(4),
+            1,
+            b"\n",
+        );
+        let mut offsets = Vec::new();
+        while let Some(chunk) = reader.next_chunk() {
+            let chunk = chunk.unwrap();
+            offsets.push((chunk.text.to_string(), chunk.offset));
+        }
+        assert_eq!(
+            offsets,
+            vec![
+                ("ab".to_string(), 0),
+                ("cdef".to_string(), 4),
+                ("fgh".to_string(), 7),
+                ("ij".to_string(), 11),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_never_splits_a_character() {
+        let result = chunks("aaé€b", Some(4), 0);
+        let texts: Vec<&str> = result.iter().map(|c| c.0.as_str()).collect();
+        assert_eq!(texts, vec!["aaé", "€b"]);
+    }
+
+    #[test]
+    fn test_splits_at_record_separator() {
+        // A tiny buffer makes the separator straddle two reads.
+        let input = "a;;b\n;;;;c\r;;";
+        let mut reader = ChunkedLines::new(
+            io::BufReader::with_capacity(3, input.as_bytes()),
+            None,
+            0,
+            b";;",
+        );
+        let mut records = Vec::new();
+        while let Some(chunk) = reader.next_chunk() {
+            let chunk = chunk.unwrap();
+            records.push((chunk.text.to_string(), chunk.offset));
+        }
+        assert_eq!(
+            records,
+            vec![
+                ("a".to_string(), 0),
+                ("b\n".to_string(), 3),
+                ("".to_string(), 7),
+                ("c\r".to_string(), 9),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_record_separator_at_length_cap() {
+        // The separator starts within the cap of each chunk but ends past it.
+        let input = "abc<>defg<>h";
+        let mut reader = ChunkedLines::new(
+            io::BufReader::with_capacity(2, input.as_bytes()),
+            Some(4),
+            0,
+            b"<>",
+        );
+        let mut records = Vec::new();
+        while let Some(chunk) = reader.next_chunk() {
+            let chunk = chunk.unwrap();
+            records.push((chunk.text.to_string(), chunk.continues_line));
+        }
+        assert_eq!(
+            records,
+            vec![
+                ("abc".to_string(), false),
+                ("defg".to_string(), false),
+                ("h".to_string(), false),
+            ]
+        );
+    }
+}
@@ -0,0 +1,188 @@
+//! Understands the structure of log lines, for `--log-format` and `--level`.
+//!
+//! With `--log-format`, the level of each log entry is read from its line:
+//! the `level` field of JSON and logfmt lines, and the priority or the first
+//! level keyword of syslog lines. `--level` then searches only the entries
+//! of the given level, or of that level and above. For JSON logs, the query
+//! is only looked for in the `message` field, as it is written in the line,
+//! so that it does not match field names or other metadata.
+
+use crate::config::{Level, LogFormat};
+use crate::json::{self, string_end};
+
+use std::ops::Range;
+
+/// The fields holding the level of an entry, in JSON and logfmt lines.
+const LEVEL_KEYS: &[&str] = &["level", "lvl", "severity"];
+
+/// The fields holding the message of an entry, in JSON lines.
+const MESSAGE_KEYS: &[&str] = &["message", "msg"];
+
+/// What is read from a log line.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct LogLine {
+    /// The level of the entry, if the line has one.
+    pub level: Option<Level>,
+    /// The byte range of the message in the line, for JSON lines.
+    pub message: Option<Range<usize>>,
+}
+
+/// Reads the level and message of `line`, which is in `format`.
+pub fn parse(line: &str, format: LogFormat) -> LogLine {
+    match format {
+        LogFormat::Json => {
+            let mut log_line = LogLine::default();
+            for (key, value) in json::fields(line) {
+                let text = &line[value.content()];
+                if LEVEL_KEYS.contains(&key) && log_line.level.is_none() {
+                    log_line.level = level_from_name(text).or_else(|| numeric_level(text));
+                } else if MESSAGE_KEYS.contains(&key) && log_line.message.is_none() {
+                    log_line.message = Some(value.content());
+                }
+            }
+            log_line
+        }
+        LogFormat::Logfmt => LogLine {
+            level: logfmt_fields(line)
+                .find(|(key, _)| LEVEL_KEYS.contains(key))
+                .and_then(|(_, value)| level_from_name(value)),
+            message: None,
+        },
+        LogFormat::Syslog => LogLine {
+            level: syslog_level(line),
+            message: None,
+        },
+    }
+}
+
+/// Returns the level called `name`, ignoring case, e.g. `warn` or
+/// `WARNING`.
+pub fn level_from_name(name: &str) -> Option<Level> {
+    match name.to_ascii_lowercase().as_str() {
+        "trace" => Some(Level::Trace),
+        "debug" => Some(Level::Debug),
+        "info" | "notice" => Some(Level::Info),
+        "warn" | "warning" => Some(Level::Warn),
+        "error" | "err" => Some(Level::Error),
+        "fatal" | "crit" | "critical" | "alert" | "emerg" | "panic" => Some(Level::Fatal),
+        _ => None,
+    }
+}
+
+/// Returns the level of a numeric level as Bunyan and pino write it, e.g.
+/// `40` for warnings.
+fn numeric_level(text: &str) -> Option<Level> {
+    match text.parse::<u32>().ok()? {
+        0..=10 => Some(Level::Trace),
+        11..=20 => Some(Level::Debug),
+        21..=30 => Some(Level::Info),
+        31..=40 => Some(Level::Warn),
+        41..=50 => Some(Level::Error),
+        _ => Some(Level::Fatal),
+    }
+}
+
+/// Returns the level of a syslog line: its `<PRI>` prefix if it has one,
+/// or else the first word naming a level.
+fn syslog_level(line: &str) -> Option<Level> {
+    if let Some(priority) = line
+        .strip_prefix('<')
+        .and_then(|rest| rest.split_once('>'))
+        .and_then(|(priority, _)| priority.parse::<u32>().ok())
+    {
+        return Some(match priority % 8 {
+            0..=2 => Level::Fatal,
+            3 => Level::Error,
+            4 => Level::Warn,
+            5 | 6 => Level::Info,
+            _ => Level::Debug,
+        });
+    }
+    line.split(|c: char| !c.is_ascii_alphabetic())
+        .find_map(level_from_name)
+}
+
+/// Returns the keys and values of the `key=value` fields of a logfmt line,
+/// with the quotes of quoted values removed.
+fn logfmt_fields(line: &str) -> impl Iterator<Item = (&str, &str)> {
+    let mut rest = line;
+    std::iter::from_fn(move || {
+        loop {
+            rest = rest.trim_start();
+            if rest.is_empty() {
+                return None;
+            }
+            let key_end = rest
+                .find(|c: char| c == '=' || c.is_whitespace())
+                .unwrap_or(rest.len());
+            let key = &rest[..key_end];
+            rest = &rest[key_end..];
+            let Some(value_start) = rest.strip_prefix('=') else {
+                // A key without a value.
+                continue;
+            };
+            let (value, value_len) = match value_start.strip_prefix('"') {
+                Some(quoted) => match string_end(value_start.as_bytes(), 0) {
+                    Some(end) => (&quoted[..end - 2], end),
+                    None => (quoted, value_start.len()),
+                },
+                None => {
+                    let end = value_start
+                        .find(char::is_whitespace)
+                        .unwrap_or(value_start.len());
+                    (&value_start[..end], end)
+                }
+            };
+            rest = &value_start[value_len..];
+            return Some((key, value));
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json() {
+        let line =
+            r#"{"time": 1, "level": "WARN", "ctx": {"message": "x"}, "message": "disk \"full\""}"#;
+        let log_line = parse(line, LogFormat::Json);
+        assert_eq!(log_line.level, Some(Level::Warn));
+        assert_eq!(&line[log_line.message.unwrap()], r#"disk \"full\""#);
+
+        let line = r#"{"level":50,"msg":"failed"}"#;
+        let log_line = parse(line, LogFormat::Json);
+        assert_eq!(log_line.level, Some(Level::Error));
+        assert_eq!(&line[log_line.message.unwrap()], "failed");
+
+        assert_eq!(parse("not json", LogFormat::Json), LogLine::default());
+    }
+
+    #[test]
+    fn test_logfmt() {
+        let line = r#"time=2024-01-01 msg="level=debug inside" level=error"#;
+        assert_eq!(parse(line, LogFormat::Logfmt).level, Some(Level::Error));
+        assert_eq!(parse("msg=hi", LogFormat::Logfmt).level, None);
+    }
+
+    #[test]
+    fn test_syslog() {
+        assert_eq!(
+            parse("<11>Jan  1 00:00:00 host app: boom", LogFormat::Syslog).level,
+            Some(Level::Error)
+        );
+        assert_eq!(
+            parse(
+                "Jan  1 00:00:00 host app[1]: WARNING low disk",
+                LogFormat::Syslog
+            )
+            .level,
+            Some(Level::Warn)
+        );
+        assert_eq!(
+            parse("Jan  1 00:00:00 host app: started", LogFormat::Syslog).level,
+            None
+        );
+    }
+}
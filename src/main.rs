@@ -7,24 +7,95 @@
 use clap::Parser;
 use std::process;
 
+pub mod ansi;
+pub mod baseline;
+pub mod checkpoint;
 pub mod config;
+pub mod csv;
+pub mod diff;
+pub mod engine;
+pub mod exec;
+pub mod glob;
+pub mod hex;
+pub mod interrupt;
+pub mod json;
+pub mod line_reader;
+pub mod log_format;
+pub mod markdown;
+pub mod markup;
+pub mod matcher;
+pub mod messages;
+pub mod mime;
 pub mod my_lib;
+pub mod normalize;
+pub mod parallel;
+pub mod paths;
+pub mod preprocessor;
+pub mod preset;
 pub mod printer;
+pub mod range;
+pub mod readahead;
+pub mod remote;
+pub mod repl;
+pub mod replace;
+pub mod report;
+pub mod section;
+pub mod staged;
+pub mod syntax;
+pub mod throttle;
+pub mod time_range;
+pub mod walker;
+pub mod watch;
 
-use crate::config::Config;
+use crate::config::{Command, Config, normalize_args};
+use crate::interrupt::{INTERRUPTED_EXIT_CODE, interrupted};
+use crate::messages::{MessageId, message};
 use crate::my_lib::run;
+use crate::printer::file_error_reported;
+use crate::repl::run_repl;
+use crate::watch::run_watch;
 
 fn main() {
     // Parse command-line arguments into a Config struct.
     // Clap handles argument parsing and provides helpful error messages
     // if arguments are invalid or missing.
-    let config = Config::parse();
+    // Options spelled the way other greps spell them are rewritten first.
+    let args = normalize_args(std::env::args_os()).unwrap_or_else(|e| e.exit());
+    let parse_config = || {
+        Config::parse_from(args.clone())
+            .resolve_patterns()
+            .and_then(|config| config.check_repl_options(&args))
+            .unwrap_or_else(|e| e.exit())
+    };
+    let mut config = parse_config();
 
-    // Execute the main grep logic. If an error occurs during execution
-    // (e.g., file not found, I/O error), print the error message to stderr
-    // and exit the process with a non-zero status code.
-    if let Err(e) = run(config) {
-        eprintln!("Application error: {}", e);
-        process::exit(1);
+    // Ctrl-C stops the search at the next line instead of killing the
+    // process mid-output.
+    interrupt::install();
+
+    // Execute the main grep logic, or the interactive `repl` subcommand. Like
+    // the classic `grep`, the exit status is 0 when a match was found and 1
+    // when nothing matched, unless `--fail-on-match` turns this around. If an
+    // error occurs during execution (e.g., file not found, I/O error), print the
+    // error message to stderr and exit with status 2. An interrupted search
+    // exits with its own status, whatever it found. Files that could not be
+    // searched make the status 2 as well, unless `--quiet` found a match.
+    let quiet = config.quiet;
+    let result = match config.command.take() {
+        Some(Command::Repl { file_paths }) => run_repl(config, &file_paths),
+        // `--watch` searches again after every change, with the same
+        // arguments parsed anew, since a search consumes its configuration.
+        None if config.watch => run_watch(config, parse_config),
+        None => run(config),
+    };
+    match result {
+        Ok(_) if interrupted() => process::exit(INTERRUPTED_EXIT_CODE),
+        Ok(succeeded) if file_error_reported() && !(quiet && succeeded) => process::exit(2),
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("{}", message(MessageId::ApplicationError, &[&e]));
+            process::exit(2);
+        }
     }
 }
@@ -0,0 +1,122 @@
+//! Restricts the search to one kind of Markdown content, for
+//! `--markdown-scope`.
+//!
+//! A line-based scanner follows the fences of code blocks (```` ``` ```` or
+//! `~~~`), so that the lines of Markdown files can be told apart as code,
+//! headings (`# Title`) or prose, which is everything else. Searching docs
+//! for a function name with `--markdown-scope code` then skips the prose
+//! that merely mentions it. Files that are not Markdown are searched as
+//! usual.
+
+use crate::config::MarkdownScope;
+
+/// The extensions of Markdown files.
+const EXTENSIONS: &[&str] = &["markdown", "md", "mdx", "mkd"];
+
+/// A code fence: its character and how many times it is repeated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Fence {
+    marker: u8,
+    len: usize,
+}
+
+/// Tracks which kind of content the lines of a single Markdown file are.
+pub struct MarkdownTracker {
+    /// The kind of content searched.
+    scope: MarkdownScope,
+    /// The fence of the code block the current line is in, if any.
+    fence: Option<Fence>,
+}
+
+impl MarkdownTracker {
+    /// Creates a tracker for the file at `path`, or `None` if it is not a
+    /// Markdown file.
+    pub fn new(scope: MarkdownScope, path: &str) -> Option<Self> {
+        let extension = path.rsplit_once('.').map(|(_, extension)| extension)?;
+        EXTENSIONS
+            .contains(&extension.to_ascii_lowercase().as_str())
+            .then_some(MarkdownTracker { scope, fence: None })
+    }
+
+    /// Moves on to the next line. Fence lines belong to no scope.
+    ///
+    /// # Returns
+    /// Whether the line is in the searched scope.
+    pub fn advance(&mut self, line: &str) -> bool {
+        let kind = match (self.fence, fence(line)) {
+            (None, Some((fence, _))) => {
+                self.fence = Some(fence);
+                None
+            }
+            (Some(open), Some((fence, info)))
+                if fence.marker == open.marker && fence.len >= open.len && info.is_empty() =>
+            {
+                self.fence = None;
+                None
+            }
+            (Some(_), _) => Some(MarkdownScope::Code),
+            (None, None) if is_heading(line) => Some(MarkdownScope::Headings),
+            (None, None) => Some(MarkdownScope::Prose),
+        };
+        kind == Some(self.scope)
+    }
+}
+
+/// Returns the fence starting `line` and the info string after it, if the
+/// line is a code fence.
+fn fence(line: &str) -> Option<(Fence, &str)> {
+    let trimmed = line.trim_start_matches(' ');
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+    let marker = *trimmed
+        .as_bytes()
+        .first()
+        .filter(|&&b| b == b'`' || b == b'~')?;
+    let len = trimmed.bytes().take_while(|&b| b == marker).count();
+    let info = trimmed[len..].trim();
+    // The info string of a backtick fence cannot hold backticks, or the line
+    // would be inline code.
+    (len >= 3 && !(marker == b'`' && info.contains('`'))).then_some((Fence { marker, len }, info))
+}
+
+/// Returns whether `line` is an ATX heading, such as `## Usage`.
+fn is_heading(line: &str) -> bool {
+    let trimmed = line.trim_start_matches(' ');
+    let level = trimmed.bytes().take_while(|&b| b == b'#').count();
+    line.len() - trimmed.len() <= 3
+        && (1..=6).contains(&level)
+        && trimmed[level..]
+            .chars()
+            .next()
+            .is_none_or(char::is_whitespace)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the numbers of the lines of `text` in `scope`.
+    fn scope_lines(scope: MarkdownScope, text: &str) -> Vec<usize> {
+        let mut tracker = MarkdownTracker::new(scope, "README.md").unwrap();
+        text.lines()
+            .enumerate()
+            .filter(|(_, line)| tracker.advance(line))
+            .map(|(i, _)| i + 1)
+            .collect()
+    }
+
+    #[test]
+    fn test_scopes() {
+        let text = "# Usage\nCall `run`.\n```rust\nrun();\n~~~\n```\n#hashtag\n    ## Not a heading\n~~~~\n```\n~~~~\n";
+        assert_eq!(scope_lines(MarkdownScope::Code, text), vec![4, 5, 10]);
+        assert_eq!(scope_lines(MarkdownScope::Headings, text), vec![1]);
+        assert_eq!(scope_lines(MarkdownScope::Prose, text), vec![2, 7, 8]);
+    }
+
+    #[test]
+    fn test_only_markdown_files() {
+        assert!(MarkdownTracker::new(MarkdownScope::Code, "docs/Guide.MD").is_some());
+        assert!(MarkdownTracker::new(MarkdownScope::Code, "main.rs").is_none());
+    }
+}
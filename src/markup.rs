@@ -0,0 +1,174 @@
+//! Finds the visible text of HTML and XML lines, for `--strip-tags`.
+//!
+//! Tags and comments are left out and character references such as `&amp;`
+//! are decoded, so that the query is matched against the text a browser
+//! would show, e.g. `read more` in `read <b>more</b>`. Every byte of the
+//! visible text remembers where it came from, so matches are reported and
+//! highlighted on the original line. Tags and comments may span lines.
+
+use std::ops::Range;
+
+/// The named character references that are decoded.
+const ENTITIES: &[(&str, char)] = &[
+    ("amp", '&'),
+    ("apos", '\''),
+    ("gt", '>'),
+    ("lt", '<'),
+    ("nbsp", ' '),
+    ("quot", '"'),
+];
+
+/// Where the scanner is within the markup.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum State {
+    /// In text, outside of any tag.
+    #[default]
+    Text,
+    /// Inside a tag, and inside a quoted attribute value if the quote is
+    /// given.
+    Tag(Option<u8>),
+    /// Inside a `<!-- ... -->` comment.
+    Comment,
+}
+
+/// The visible text of a line.
+#[derive(Debug, PartialEq, Eq)]
+pub struct VisibleText {
+    /// The text without tags and comments, with references decoded.
+    pub text: String,
+    /// For every byte of `text`, the bytes of the line it came from.
+    spans: Vec<Range<usize>>,
+}
+
+impl VisibleText {
+    /// Returns the bytes of the line that the bytes `range` of the visible
+    /// text came from.
+    pub fn original_range(&self, range: Range<usize>) -> Range<usize> {
+        let start = self
+            .spans
+            .get(range.start)
+            .map_or(self.spans.last().map_or(0, |span| span.end), |span| {
+                span.start
+            });
+        let end = match range.end.checked_sub(1) {
+            Some(last) if range.end > range.start => self.spans[last].end,
+            _ => start,
+        };
+        start..end
+    }
+}
+
+/// Strips the markup from one line after another, keeping track of tags and
+/// comments left open at the end of a line.
+#[derive(Debug, Default)]
+pub struct TagStripper {
+    state: State,
+}
+
+impl TagStripper {
+    /// Creates a stripper for a new file.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the visible text of the next line.
+    pub fn strip(&mut self, line: &str) -> VisibleText {
+        let bytes = line.as_bytes();
+        let mut visible = VisibleText {
+            text: String::with_capacity(line.len()),
+            spans: Vec::with_capacity(line.len()),
+        };
+        let mut pos = 0;
+        while pos < bytes.len() {
+            match self.state {
+                State::Comment => match line[pos..].find("-->") {
+                    Some(end) => {
+                        pos += end + 3;
+                        self.state = State::Text;
+                    }
+                    None => pos = bytes.len(),
+                },
+                State::Tag(Some(quote)) => {
+                    if bytes[pos] == quote {
+                        self.state = State::Tag(None);
+                    }
+                    pos += 1;
+                }
+                State::Tag(None) => {
+                    match bytes[pos] {
+                        b'"' | b'\'' => self.state = State::Tag(Some(bytes[pos])),
+                        b'>' => self.state = State::Text,
+                        _ => {}
+                    }
+                    pos += 1;
+                }
+                State::Text => {
+                    let rest = &line[pos..];
+                    if rest.starts_with("<!--") {
+                        self.state = State::Comment;
+                        pos += 4;
+                    } else if rest.starts_with('<')
+                        && rest[1..]
+                            .starts_with(|c: char| c.is_ascii_alphabetic() || "/!?".contains(c))
+                    {
+                        self.state = State::Tag(None);
+                        pos += 1;
+                    } else if let Some((decoded, len)) = reference(rest) {
+                        // Every byte of the decoded character comes from the
+                        // whole reference.
+                        visible.text.push(decoded);
+                        visible.spans.resize(visible.text.len(), pos..pos + len);
+                        pos += len;
+                    } else {
+                        let c = rest.chars().next().unwrap_or_default();
+                        visible.text.push(c);
+                        let len = c.len_utf8();
+                        visible.spans.extend((pos..pos + len).map(|b| b..b + 1));
+                        pos += len;
+                    }
+                }
+            }
+        }
+        visible
+    }
+}
+
+/// Decodes the character reference at the start of `text`, such as `&amp;`
+/// or `&#x41;`, returning the character and the length of the reference.
+fn reference(text: &str) -> Option<(char, usize)> {
+    let rest = text.strip_prefix('&')?;
+    let end = rest.find(';').filter(|&end| end <= 10)?;
+    let name = &rest[..end];
+    let decoded = if let Some(number) = name.strip_prefix('#') {
+        let code = match number.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => number.parse().ok()?,
+        };
+        char::from_u32(code)?
+    } else {
+        ENTITIES
+            .iter()
+            .find(|(entity, _)| *entity == name)
+            .map(|&(_, c)| c)?
+    };
+    Some((decoded, end + 2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip() {
+        let mut stripper = TagStripper::new();
+        let line = r#"<p class="a>b">Tom &amp; <b>Jerry</b>&#33;</p>"#;
+        let visible = stripper.strip(line);
+        assert_eq!(visible.text, "Tom & Jerry!");
+        assert_eq!(&line[visible.original_range(0..5)], "Tom &amp;");
+        assert_eq!(&line[visible.original_range(4..11)], "&amp; <b>Jerry");
+
+        assert_eq!(stripper.strip("a <!-- hidden").text, "a ");
+        assert_eq!(stripper.strip("still --> shown <br").text, " shown ");
+        assert_eq!(stripper.strip("/> x < y").text, " x < y");
+    }
+}
@@ -0,0 +1,162 @@
+//! Finds occurrences of the search query in a line.
+//!
+//! The query and the `-e` patterns are combined into a single regex for the
+//! engine (see `engine`). Case-insensitive plain queries are left to the
+//! engine as well: it finds them with a vectorized search for all of their
+//! case variants, which a hand-written case-folded scan does not beat.
+
+use crate::config::Config;
+use crate::engine::{self, Regex, RegexBuilder};
+use crate::messages::{MessageId, message};
+
+use std::{error::Error, ops::Range};
+
+/// Finds the query in lines.
+pub struct Matcher {
+    /// The regex built from the query.
+    regex: Regex,
+}
+
+impl Matcher {
+    /// Builds the matcher for the query and options in `config`.
+    ///
+    /// # Arguments
+    /// * `config` - The search configuration.
+    ///
+    /// # Returns
+    /// The matcher, or an error if the pattern fails to compile.
+    pub fn new(config: &Config) -> Result<Self, Box<dyn Error>> {
+        // Prepare the regex pattern string. Each pattern is escaped (unless it
+        // is a `--preset` regex), gets word boundaries (`\b`) if only whole
+        // words should match, and is made case-insensitive on its own if case
+        // is ignored for it.
+        let patterns = config.search_patterns();
+        let pattern_string = patterns
+            .iter()
+            .map(|pattern| {
+                let escaped = if pattern.regex {
+                    format!("(?:{})", pattern.text)
+                } else {
+                    engine::escape(&pattern.text)
+                };
+                let escaped = if pattern.word {
+                    format!(r"\b{}\b", escaped)
+                } else {
+                    escaped
+                };
+                if pattern.ignore_case {
+                    format!("(?i:{})", escaped)
+                } else {
+                    escaped
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("|");
+        // With `--line-regexp`, a pattern has to cover the whole line, and
+        // with `--starts-with` or `--ends-with` one end of it.
+        let anchored_start = config.line_regexp || config.starts_with;
+        let anchored_end = config.line_regexp || config.ends_with;
+        let pattern_string = format!(
+            "{}(?:{}){}",
+            if anchored_start { "^" } else { "" },
+            pattern_string,
+            if anchored_end { "$" } else { "" }
+        );
+
+        // The size limits default to those of the `regex` crate, and can be
+        // raised for very large patterns that would otherwise fail to compile.
+        let mut regex_builder = RegexBuilder::new(&pattern_string);
+        if let Some(limit) = config.regex_size_limit {
+            regex_builder.size_limit(limit);
+        }
+        if let Some(limit) = config.dfa_size_limit {
+            engine::dfa_size_limit(&mut regex_builder, limit);
+        }
+        let regex = regex_builder.build().map_err(|e| {
+            if engine::is_too_big(&e) {
+                message(MessageId::PatternTooBig, &[&e])
+            } else {
+                message(MessageId::InvalidPattern, &[&e])
+            }
+        })?;
+
+        Ok(Matcher { regex })
+    }
+
+    /// Returns the byte ranges of the successive non-overlapping matches in
+    /// `text`, from left to right.
+    pub fn find_iter(&self, text: &str) -> impl Iterator<Item = Range<usize>> {
+        self.regex.find_iter(text).map(|m| m.range())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn matcher(args: &[&str]) -> Matcher {
+        let config = Config::parse_from(args).resolve_patterns().unwrap();
+        Matcher::new(&config).unwrap()
+    }
+
+    #[test]
+    fn test_ignore_case() {
+        let literal = matcher(&["grep-rust", "-i", "Error", "log.txt"]);
+        assert_eq!(
+            literal.find_iter("ERROR: error, eRRor").collect::<Vec<_>>(),
+            vec![0..5, 7..12, 14..19]
+        );
+        assert_eq!(literal.find_iter("err or").next(), None);
+        let word = matcher(&["grep-rust", "-i", "-w", "error", "log.txt"]);
+        assert_eq!(
+            word.find_iter("errors ERROR").collect::<Vec<_>>(),
+            vec![7..12]
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "lite"))]
+    fn test_ignore_case_folds_unicode() {
+        // U+212A KELVIN SIGN folds to `k`, and U+017F LATIN SMALL LETTER
+        // LONG S to `s`.
+        let kelvin = matcher(&["grep-rust", "-i", "k", "log.txt"]);
+        assert_eq!(kelvin.find_iter("\u{212A}").count(), 1);
+        let long_s = matcher(&["grep-rust", "-i", "as", "log.txt"]);
+        assert_eq!(long_s.find_iter("a\u{017F} AS").count(), 2);
+    }
+
+    #[test]
+    fn test_anchored_patterns() {
+        let starts = matcher(&["grep-rust", "--starts-with", "-i", "a.b", "log.txt"]);
+        assert_eq!(starts.find_iter("A.b a.b").collect::<Vec<_>>(), vec![0..3]);
+        assert_eq!(starts.find_iter(" a.b").next(), None);
+        assert_eq!(starts.find_iter("axb").next(), None);
+
+        let ends = matcher(&["grep-rust", "--ends-with", "a.b", "log.txt"]);
+        assert_eq!(ends.find_iter("a.b a.b").collect::<Vec<_>>(), vec![4..7]);
+
+        let both = matcher(&["grep-rust", "--starts-with", "--ends-with", "ab", "log.txt"]);
+        assert_eq!(both.find_iter("ab").count(), 1);
+        assert_eq!(both.find_iter("ab ab").count(), 0);
+    }
+
+    #[test]
+    fn test_presets_with_patterns() {
+        let matcher = matcher(&["grep-rust", "-e", "ip", "--preset", "ipv4", "log.txt"]);
+        assert_eq!(
+            matcher.find_iter("ip 10.0.0.1, ip.1").collect::<Vec<_>>(),
+            vec![0..2, 3..11, 13..15]
+        );
+    }
+
+    #[test]
+    fn test_patterns_with_modifiers() {
+        let matcher = matcher(&["grep-rust", "-e", "(?i)error", "-e", "PANIC", "log.txt"]);
+        assert_eq!(matcher.find_iter("ERROR").count(), 1);
+        assert_eq!(
+            matcher.find_iter("PANIC panic").collect::<Vec<_>>(),
+            vec![0..5]
+        );
+    }
+}
@@ -0,0 +1,439 @@
+//! Provides the user-facing messages of `grep-rust` in the user's language.
+//!
+//! Every banner, warning and error text is looked up in a small message
+//! catalog by its `MessageId`. The language is detected once from the
+//! `LC_ALL`, `LC_MESSAGES` and `LANG` environment variables, falling back to
+//! English for locales without a translation.
+
+use std::{env, fmt::Display, fmt::Write, sync::OnceLock};
+
+/// The languages the message catalog is available in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Locale {
+    /// English, the default language.
+    English,
+    /// Indonesian (Bahasa Indonesia).
+    Indonesian,
+}
+
+impl Locale {
+    /// Detects the locale from the environment.
+    ///
+    /// The variables are checked in the order POSIX gives them precedence:
+    /// `LC_ALL`, then `LC_MESSAGES`, then `LANG`. The first one that is set
+    /// to a non-empty value decides the locale.
+    pub fn from_env() -> Self {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|name| env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .map(|value| Locale::from_tag(&value))
+            .unwrap_or(Locale::English)
+    }
+
+    /// Maps a locale tag such as `id_ID.UTF-8` or `en_US` to a `Locale`.
+    ///
+    /// Only the language part of the tag is considered. Unknown languages
+    /// fall back to English.
+    pub fn from_tag(tag: &str) -> Self {
+        let language = tag
+            .split(['_', '-', '.', '@'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        match language.as_str() {
+            "id" | "in" => Locale::Indonesian,
+            _ => Locale::English,
+        }
+    }
+}
+
+/// Identifies a user-facing message in the catalog.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MessageId {
+    /// The banner line for a search of a single file: query, file.
+    SearchingInFile,
+    /// The banner line for a search of several files: query, files.
+    SearchingInFiles,
+    /// The banner note for case-insensitive searches.
+    CaseInsensitive,
+    /// The banner note for enabled line numbers.
+    LineNumbersEnabled,
+    /// The banner note for before context: number of lines.
+    ContextBefore,
+    /// The banner note for after context: number of lines.
+    ContextAfter,
+    /// The banner note for `--max-total`: number of matches.
+    StoppingAfterTotal,
+    /// A fatal error: error description.
+    ApplicationError,
+    /// A pattern that failed to compile: error description.
+    InvalidPattern,
+    /// A pattern that exceeded the size limit: error description.
+    PatternTooBig,
+    /// A file abandoned by `--file-timeout`: file, timeout.
+    FileTimedOut,
+    /// A search stopped by `--timeout`: file, timeout.
+    SearchTimedOut,
+    /// A directory given while `--directories read` is in effect: path.
+    IsADirectory,
+    /// A file name that cannot be represented as UTF-8: path.
+    InvalidFileName,
+    /// The note printed when the search was stopped with Ctrl-C.
+    Interrupted,
+    /// The end-of-run summary: what was counted, matching files, elapsed
+    /// time.
+    Summary,
+    /// The end-of-run summary of `--files-with-matches`: matching files,
+    /// elapsed time.
+    FilesSummary,
+    /// A number of matching lines, in singular and plural: count.
+    MatchingLines,
+    /// A number of matches, in singular and plural: count.
+    Matches,
+    /// A number of replacements, in singular and plural: count.
+    Replacements,
+    /// A number of files, in singular and plural: count.
+    Files,
+    /// A number of matching files, in singular and plural: count.
+    MatchingFiles,
+    /// The note printed when `--max-allowed` is exceeded: matches, allowed.
+    MaxAllowedExceeded,
+    /// A malformed entry in a `--baseline` file: path, line number.
+    InvalidBaseline,
+    /// The greeting of the `repl` subcommand, in singular and plural: number
+    /// of loaded files.
+    ReplReady,
+    /// The note on files skipped by `--dedupe-content`, in singular and
+    /// plural: number of files.
+    DuplicatesSkipped,
+    /// A file `--replace` cannot edit since it is not text: path.
+    NotEdited,
+    /// The question asked for each change by `--confirm`.
+    ConfirmReplace,
+    /// The note printed by `--undo`, in singular and plural: number of files
+    /// restored.
+    Restored,
+    /// A `--since` or `--until-time` that cannot be read: the value.
+    InvalidTime,
+    /// A `--column-name` missing from the CSV header: column name.
+    ColumnNotFound,
+    /// A file that cannot be searched: path, error description.
+    FileError,
+    /// An `--exec` command that failed: path, exit status.
+    ExecFailed,
+    /// A `--pre-glob` command that failed without an error message: exit status.
+    PreprocessorFailed,
+    /// An option given together with the `repl` subcommand that it does not
+    /// apply: the option.
+    ReplUnsupported,
+    /// An option of other greps that `grep-rust` does not support: option,
+    /// reason.
+    UnsupportedArgument,
+    /// The reason `-E`, `-G` and `-P` are not supported.
+    LiteralPatternsOnly,
+    /// The reason `-L` is not supported.
+    NoFilesWithoutMatch,
+    /// The reason `-z` is not supported.
+    NewlineSeparatedLines,
+    /// A size that cannot be read: the value.
+    InvalidSize,
+    /// A size with an unknown unit: the unit.
+    InvalidSizeSuffix,
+    /// A size too large to be held: the value.
+    SizeTooLarge,
+    /// A duration that cannot be read: the value.
+    InvalidDuration,
+    /// A duration with an unknown unit: the unit.
+    InvalidDurationSuffix,
+    /// A `--hex-pattern` that is not made of pairs of digits: the value.
+    InvalidHexPattern,
+    /// A byte of a `--hex-pattern` that is not hexadecimal: the byte.
+    InvalidHexByte,
+    /// An unknown escape in a `--record-separator`: the escaped character.
+    UnknownEscape,
+    /// A `--record-separator` ending in a backslash.
+    LoneBackslash,
+    /// An empty `--record-separator`.
+    EmptySeparator,
+    /// A malformed `--json-path`: the value.
+    InvalidJsonPath,
+    /// An unknown `--level`: the level.
+    UnknownLevel,
+    /// A `--pre-glob` that is not of the form `GLOB=COMMAND`: the value.
+    InvalidPreGlob,
+}
+
+impl MessageId {
+    /// Returns the template of this message in `locale`.
+    ///
+    /// Templates contain `{}` placeholders that are filled in order by
+    /// `format_message`. The templates of counts hold their singular and
+    /// plural forms separated by `|`, for `count_message` to choose from.
+    pub fn template(self, locale: Locale) -> &'static str {
+        match locale {
+            Locale::English => match self {
+                MessageId::SearchingInFile => "Searching for '{}' in file {}...",
+                MessageId::SearchingInFiles => "Searching for '{}' in files {}...",
+                MessageId::CaseInsensitive => "(Case-insensitive search)",
+                MessageId::LineNumbersEnabled => "(Line numbers enabled)",
+                MessageId::ContextBefore => "(Context before: {} lines)",
+                MessageId::ContextAfter => "(Context after: {} lines)",
+                MessageId::StoppingAfterTotal => "(Stopping after {} matches in total)",
+                MessageId::ApplicationError => "Application error: {}",
+                MessageId::InvalidPattern => "Invalid regex pattern: {}",
+                MessageId::PatternTooBig => {
+                    "Invalid regex pattern: {} Try raising --regex-size-limit."
+                }
+                MessageId::FileTimedOut => "{}: search timed out after {}",
+                MessageId::SearchTimedOut => {
+                    "{}: search timed out after {}, remaining files were not searched"
+                }
+                MessageId::IsADirectory => "{}: Is a directory",
+                MessageId::InvalidFileName => "{}: file name is not valid UTF-8",
+                MessageId::Interrupted => "Interrupted, showing the results found so far",
+                MessageId::Summary => "{} in {} ({})",
+                MessageId::FilesSummary => "{} ({})",
+                MessageId::MatchingLines => "{} matching line|{} matching lines",
+                MessageId::Matches => "{} match|{} matches",
+                MessageId::Replacements => "{} replacement|{} replacements",
+                MessageId::Files => "{} file|{} files",
+                MessageId::MatchingFiles => "{} matching file|{} matching files",
+                MessageId::MaxAllowedExceeded => "{} matches found, more than the {} allowed",
+                MessageId::InvalidBaseline => "{}: invalid baseline entry on line {}",
+                MessageId::ReplReady => concat!(
+                    "Loaded {} file. Enter a pattern per line, or an empty line to quit.|",
+                    "Loaded {} files. Enter a pattern per line, or an empty line to quit."
+                ),
+                MessageId::DuplicatesSkipped => concat!(
+                    "{} file skipped as a duplicate of a file already searched|",
+                    "{} files skipped as duplicates of files already searched"
+                ),
+                MessageId::NotEdited => "{}: not valid UTF-8, left unchanged",
+                MessageId::ConfirmReplace => "Replace this? [y,n,a,q] ",
+                MessageId::Restored => {
+                    "{} file restored from its backup|{} files restored from their backups"
+                }
+                MessageId::InvalidTime => {
+                    "invalid time '{}', expected a duration such as 1h or a timestamp"
+                }
+                MessageId::ColumnNotFound => "no column '{}' in the CSV header",
+                MessageId::FileError => "{}: {}",
+                MessageId::ExecFailed => "{}: --exec command failed with {}",
+                MessageId::PreprocessorFailed => "preprocessor failed with {}",
+                MessageId::ReplUnsupported => "the argument '{}' cannot be used with 'repl'",
+                MessageId::UnsupportedArgument => "'{}' is not supported: {}",
+                MessageId::LiteralPatternsOnly => "patterns are searched as literal text",
+                MessageId::NoFilesWithoutMatch => "files without matches cannot be listed",
+                MessageId::NewlineSeparatedLines => "input lines are always separated by newlines",
+                MessageId::InvalidSize => "invalid size '{}'",
+                MessageId::InvalidSizeSuffix => "invalid size suffix '{}'",
+                MessageId::SizeTooLarge => "size '{}' is too large",
+                MessageId::InvalidDuration => "invalid duration '{}'",
+                MessageId::InvalidDurationSuffix => "invalid duration suffix '{}'",
+                MessageId::InvalidHexPattern => {
+                    "invalid hex pattern '{}', expected pairs of hex digits"
+                }
+                MessageId::InvalidHexByte => "invalid hex byte '{}'",
+                MessageId::UnknownEscape => "unknown escape '\\{}'",
+                MessageId::LoneBackslash => "separator ends with a lone '\\'",
+                MessageId::EmptySeparator => "the record separator must not be empty",
+                MessageId::InvalidJsonPath => "invalid JSON path '{}', expected e.g. .request.url",
+                MessageId::UnknownLevel => {
+                    "unknown level '{}', expected trace, debug, info, warn, error or fatal"
+                }
+                MessageId::InvalidPreGlob => "expected GLOB=COMMAND, got '{}'",
+            },
+            Locale::Indonesian => match self {
+                MessageId::SearchingInFile => "Mencari '{}' di berkas {}...",
+                MessageId::SearchingInFiles => "Mencari '{}' di berkas-berkas {}...",
+                MessageId::CaseInsensitive => "(Pencarian tanpa membedakan huruf besar/kecil)",
+                MessageId::LineNumbersEnabled => "(Nomor baris ditampilkan)",
+                MessageId::ContextBefore => "(Konteks sebelum: {} baris)",
+                MessageId::ContextAfter => "(Konteks sesudah: {} baris)",
+                MessageId::StoppingAfterTotal => "(Berhenti setelah total {} kecocokan)",
+                MessageId::ApplicationError => "Kesalahan aplikasi: {}",
+                MessageId::InvalidPattern => "Pola regex tidak valid: {}",
+                MessageId::PatternTooBig => {
+                    "Pola regex tidak valid: {} Coba naikkan --regex-size-limit."
+                }
+                MessageId::FileTimedOut => "{}: pencarian melewati batas waktu {}",
+                MessageId::SearchTimedOut => {
+                    "{}: pencarian melewati batas waktu {}, berkas lainnya tidak dicari"
+                }
+                MessageId::IsADirectory => "{}: Merupakan direktori",
+                MessageId::InvalidFileName => "{}: nama berkas bukan UTF-8 yang valid",
+                MessageId::Interrupted => "Dihentikan, menampilkan hasil yang ditemukan sejauh ini",
+                MessageId::Summary => "{} di {} ({})",
+                MessageId::FilesSummary => "{} ({})",
+                MessageId::MatchingLines => "{} baris cocok",
+                MessageId::Matches => "{} kecocokan",
+                MessageId::Replacements => "{} penggantian",
+                MessageId::Files => "{} berkas",
+                MessageId::MatchingFiles => "{} berkas cocok",
+                MessageId::MaxAllowedExceeded => {
+                    "{} kecocokan ditemukan, lebih dari {} yang diizinkan"
+                }
+                MessageId::InvalidBaseline => "{}: entri baseline tidak valid pada baris {}",
+                MessageId::ReplReady => {
+                    "{} berkas dimuat. Masukkan satu pola per baris, atau baris kosong untuk keluar."
+                }
+                MessageId::DuplicatesSkipped => {
+                    "{} berkas dilewati karena isinya sama dengan berkas yang sudah dicari"
+                }
+                MessageId::NotEdited => "{}: bukan UTF-8 yang valid, tidak diubah",
+                MessageId::ConfirmReplace => "Ganti ini? [y,n,a,q] ",
+                MessageId::Restored => "{} berkas dipulihkan dari cadangannya",
+                MessageId::InvalidTime => {
+                    "waktu '{}' tidak valid, seharusnya durasi seperti 1h atau stempel waktu"
+                }
+                MessageId::ColumnNotFound => "tidak ada kolom '{}' di header CSV",
+                MessageId::FileError => "{}: {}",
+                MessageId::ExecFailed => "{}: perintah --exec gagal dengan {}",
+                MessageId::PreprocessorFailed => "praprosesor gagal dengan {}",
+                MessageId::ReplUnsupported => "argumen '{}' tidak dapat dipakai dengan 'repl'",
+                MessageId::UnsupportedArgument => "'{}' tidak didukung: {}",
+                MessageId::LiteralPatternsOnly => "pola selalu dicari sebagai teks literal",
+                MessageId::NoFilesWithoutMatch => "berkas tanpa kecocokan tidak dapat ditampilkan",
+                MessageId::NewlineSeparatedLines => {
+                    "baris masukan selalu dipisahkan oleh baris baru"
+                }
+                MessageId::InvalidSize => "ukuran '{}' tidak valid",
+                MessageId::InvalidSizeSuffix => "satuan ukuran '{}' tidak valid",
+                MessageId::SizeTooLarge => "ukuran '{}' terlalu besar",
+                MessageId::InvalidDuration => "durasi '{}' tidak valid",
+                MessageId::InvalidDurationSuffix => "satuan durasi '{}' tidak valid",
+                MessageId::InvalidHexPattern => {
+                    "pola hex '{}' tidak valid, seharusnya pasangan digit hex"
+                }
+                MessageId::InvalidHexByte => "byte hex '{}' tidak valid",
+                MessageId::UnknownEscape => "escape '\\{}' tidak dikenal",
+                MessageId::LoneBackslash => "pemisah diakhiri dengan '\\' tunggal",
+                MessageId::EmptySeparator => "pemisah record tidak boleh kosong",
+                MessageId::InvalidJsonPath => {
+                    "path JSON '{}' tidak valid, seharusnya misalnya .request.url"
+                }
+                MessageId::UnknownLevel => {
+                    "level '{}' tidak dikenal, seharusnya trace, debug, info, warn, error atau fatal"
+                }
+                MessageId::InvalidPreGlob => "seharusnya GLOB=COMMAND, bukan '{}'",
+            },
+        }
+    }
+}
+
+/// Returns the locale of the current process, detecting it on first use.
+pub fn current_locale() -> Locale {
+    static LOCALE: OnceLock<Locale> = OnceLock::new();
+    *LOCALE.get_or_init(Locale::from_env)
+}
+
+/// Returns the message `id` in the current locale, with `args` filled into
+/// its placeholders in order.
+///
+/// # Arguments
+/// * `id` - The message to look up.
+/// * `args` - The values for the message's `{}` placeholders.
+pub fn message(id: MessageId, args: &[&dyn Display]) -> String {
+    format_message(id.template(current_locale()), args)
+}
+
+/// Returns the count message `id` in the current locale for `count`, in the
+/// singular or plural form that fits it.
+///
+/// # Arguments
+/// * `id` - The count message to look up.
+/// * `count` - The number to fill in.
+pub fn count_message(id: MessageId, count: usize) -> String {
+    format_message(plural_form(id.template(current_locale()), count), &[&count])
+}
+
+/// Picks the form of a count template that fits `count`: the first one for
+/// one, the last one otherwise. Languages that do not inflect for number
+/// have a single form.
+fn plural_form(template: &str, count: usize) -> &str {
+    let mut forms = template.split('|');
+    let singular = forms.next().unwrap_or_default();
+    match forms.next_back() {
+        Some(plural) if count != 1 => plural,
+        _ => singular,
+    }
+}
+
+/// Fills the `{}` placeholders of `template` with `args` in order.
+///
+/// Placeholders without a matching argument are left empty, and surplus
+/// arguments are ignored.
+///
+/// # Arguments
+/// * `template` - The message template.
+/// * `args` - The values for the template's placeholders.
+pub fn format_message(template: &str, args: &[&dyn Display]) -> String {
+    let mut output = String::with_capacity(template.len());
+    let mut args = args.iter();
+    let mut parts = template.split("{}");
+
+    if let Some(first) = parts.next() {
+        output.push_str(first);
+    }
+    for part in parts {
+        if let Some(arg) = args.next() {
+            write!(&mut output, "{}", arg).unwrap();
+        }
+        output.push_str(part);
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locale_from_tag() {
+        assert_eq!(Locale::from_tag("id_ID.UTF-8"), Locale::Indonesian);
+        assert_eq!(Locale::from_tag("en_US.UTF-8"), Locale::English);
+        assert_eq!(Locale::from_tag("C"), Locale::English);
+        assert_eq!(Locale::from_tag("fr_FR"), Locale::English);
+    }
+
+    #[test]
+    fn test_format_message() {
+        let template = MessageId::ContextBefore.template(Locale::Indonesian);
+        assert_eq!(
+            format_message(template, &[&3]),
+            "(Konteks sebelum: 3 baris)"
+        );
+        assert_eq!(format_message("{}: {} and {}", &[&"a", &1]), "a: 1 and ");
+        let template = MessageId::UnknownEscape.template(Locale::Indonesian);
+        assert_eq!(
+            format_message(template, &[&'q']),
+            "escape '\\q' tidak dikenal"
+        );
+    }
+
+    #[test]
+    fn test_plural_form() {
+        let template = MessageId::MatchingLines.template(Locale::English);
+        assert_eq!(plural_form(template, 1), "{} matching line");
+        assert_eq!(plural_form(template, 0), "{} matching lines");
+        assert_eq!(plural_form(template, 2), "{} matching lines");
+        let template = MessageId::MatchingLines.template(Locale::Indonesian);
+        assert_eq!(plural_form(template, 1), "{} baris cocok");
+        assert_eq!(plural_form(template, 2), "{} baris cocok");
+        let template = MessageId::ReplReady.template(Locale::English);
+        assert!(plural_form(template, 1).starts_with("Loaded {} file. "));
+        assert!(plural_form(template, 3).starts_with("Loaded {} files. "));
+        let template = MessageId::DuplicatesSkipped.template(Locale::English);
+        assert!(plural_form(template, 1).starts_with("{} file skipped "));
+        assert!(plural_form(template, 2).starts_with("{} files skipped "));
+        let template = MessageId::Restored.template(Locale::English);
+        assert_eq!(plural_form(template, 1), "{} file restored from its backup");
+        assert_eq!(
+            plural_form(template, 0),
+            "{} files restored from their backups"
+        );
+    }
+}
@@ -0,0 +1,145 @@
+//! Detects the content type and encoding of files from their first bytes,
+//! for `--mime` and the JSON output.
+//!
+//! Data directories often hold files with missing or misleading extensions.
+//! The type is told from the content instead: a few well-known magic byte
+//! sequences identify binary formats, and text files are told apart by how
+//! their first non-blank characters look. Types are reported as MIME types,
+//! such as `text/xml` or `image/png`.
+
+use crate::glob::Glob;
+use crate::paths::fs_path;
+
+use std::{fs::File, io::Read, path::Path};
+
+/// The number of bytes read from the start of a file to detect its type.
+const HEADER_LEN: usize = 1024;
+
+/// Magic byte sequences at the start of a file, and the types they mark.
+const MAGIC: &[(&[u8], &str)] = &[
+    (b"%PDF-", "application/pdf"),
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"BZh", "application/x-bzip2"),
+    (b"\xfd7zXZ\0", "application/x-xz"),
+    (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+    (b"\x7fELF", "application/x-executable"),
+    (b"MZ", "application/x-msdownload"),
+    (b"\xca\xfe\xba\xbe", "application/java-vm"),
+    (b"\0asm", "application/wasm"),
+    (b"SQLite format 3\0", "application/vnd.sqlite3"),
+];
+
+/// Returns the type of content starting with `header`.
+pub fn detect(header: &[u8]) -> &'static str {
+    if let Some((_, mime)) = MAGIC.iter().find(|(magic, _)| header.starts_with(magic)) {
+        return mime;
+    }
+    if header.contains(&0) {
+        return "application/octet-stream";
+    }
+
+    // The header may end in the middle of a character, which is not a
+    // reason to consider the file binary.
+    let text = match std::str::from_utf8(header) {
+        Ok(text) => text,
+        Err(e) if e.error_len().is_none() => {
+            std::str::from_utf8(&header[..e.valid_up_to()]).unwrap_or_default()
+        }
+        Err(_) => return "application/octet-stream",
+    };
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    let lowercase = text
+        .get(..text.len().min(64))
+        .unwrap_or(text)
+        .to_ascii_lowercase();
+    if lowercase.starts_with("<!doctype html") || lowercase.starts_with("<html") {
+        "text/html"
+    } else if lowercase.starts_with("<svg") || (text.starts_with('<') && text.contains("<svg")) {
+        "image/svg+xml"
+    } else if text.starts_with("<?xml") || text.starts_with('<') {
+        "text/xml"
+    } else if text.starts_with('{') || text.starts_with('[') {
+        "application/json"
+    } else if text.starts_with("#!") {
+        "text/x-script"
+    } else {
+        "text/plain"
+    }
+}
+
+/// Returns whether the content of the file at `path` has a type matching
+/// one of `patterns`, which may use glob wildcards such as `text/*`.
+///
+/// Files that cannot be read are kept, so that opening them for the search
+/// reports the actual error.
+pub fn file_matches(path: &str, patterns: &[String]) -> bool {
+    let Some(header) = read_header(path) else {
+        return true;
+    };
+    let mime = detect(&header);
+    patterns
+        .iter()
+        .any(|pattern| Glob::new(pattern).is_match(mime))
+}
+
+/// Returns the text encoding of content starting with `header`, told from
+/// its byte order mark, or from whether it is valid UTF-8 otherwise.
+pub fn encoding(header: &[u8]) -> &'static str {
+    if header.starts_with(b"\xef\xbb\xbf") {
+        "utf-8-bom"
+    } else if header.starts_with(b"\xff\xfe") {
+        "utf-16le"
+    } else if header.starts_with(b"\xfe\xff") {
+        "utf-16be"
+    } else if header.is_ascii() {
+        "ascii"
+    } else {
+        match std::str::from_utf8(header) {
+            Ok(_) => "utf-8",
+            // A character cut in half at the end of the header is still UTF-8.
+            Err(e) if e.error_len().is_none() => "utf-8",
+            Err(_) => "unknown",
+        }
+    }
+}
+
+/// Reads the first bytes of the file at `path`.
+///
+/// # Returns
+/// The bytes, or `None` if the file cannot be read.
+pub fn read_header(path: &str) -> Option<Vec<u8>> {
+    let file = File::open(fs_path(Path::new(path))).ok()?;
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    file.take(HEADER_LEN as u64).read_to_end(&mut header).ok()?;
+    Some(header)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(detect(b"\x89PNG\r\n\x1a\n\0\0"), "image/png");
+        assert_eq!(detect(b"\xef\xbb\xbf  <?xml version=\"1.0\"?>"), "text/xml");
+        assert_eq!(detect(b"<?xml version=\"1.0\"?>\n<svg>"), "image/svg+xml");
+        assert_eq!(detect(b"<!DOCTYPE html>"), "text/html");
+        assert_eq!(detect(b"\n{\"a\": 1}"), "application/json");
+        assert_eq!(detect("caf\u{e9}".as_bytes()), "text/plain");
+        assert_eq!(detect(&"caf\u{e9}".as_bytes()[..4]), "text/plain");
+        assert_eq!(detect(b"a\0b"), "application/octet-stream");
+    }
+
+    #[test]
+    fn test_encoding() {
+        assert_eq!(encoding(b"plain"), "ascii");
+        assert_eq!(encoding("caf\u{e9}".as_bytes()), "utf-8");
+        assert_eq!(encoding(b"\xff\xfea\0"), "utf-16le");
+        assert_eq!(encoding(b"caf\xe9 au lait"), "unknown");
+    }
+}
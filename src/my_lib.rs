@@ -4,16 +4,57 @@
 //! state and the `run` function, which orchestrates file reading, pattern
 //! matching, and context handling.
 
-use regex::Regex;
-
-use crate::config::Config;
-use crate::printer::{print_highlighted_line, print_line, print_search_info};
+use crate::ansi::StripAnsi;
+use crate::baseline::{Baseline, KnownMatches, line_hash, write_baseline};
+use crate::checkpoint::Checkpoint;
+use crate::config::{
+    Config, DeviceAction, DirectoryAction, HistogramBy, IoBackend, LogFormat, OutputFormat, SortBy,
+};
+use crate::csv;
+use crate::diff::{DiffFile, parse_diff, read_diff};
+use crate::exec;
+use crate::hex;
+use crate::interrupt::interrupted;
+use crate::json;
+use crate::line_reader::ChunkedLines;
+use crate::log_format::{self, LogLine};
+use crate::markdown::MarkdownTracker;
+use crate::markup::{TagStripper, VisibleText};
+use crate::matcher::Matcher;
+use crate::messages::{MessageId, message};
+use crate::mime;
+use crate::normalize::{NormalizeLines, normalize};
+use crate::parallel;
+use crate::paths::fs_path;
+use crate::preprocessor::Preprocessor;
+use crate::preset;
+use crate::printer::{
+    PathBase, PathDisplay, Tally, display_path, print_context_line, print_duplicates_skipped,
+    print_file_count, print_file_error, print_file_name, print_heading, print_highlighted_line,
+    print_histogram, print_match, print_search_info, print_summary, print_unique_counts,
+    print_warning, set_no_messages, set_nul_after_file_name, set_path_display, set_plain_output,
+};
+use crate::range::LineRange;
+use crate::readahead::advise_sequential;
+use crate::remote::{is_url, open_url};
+use crate::replace::{replace_files, undo_files};
+use crate::report::{FileInfo, ReportEntry, fnv1a, write_codequality, write_json, write_junit};
+use crate::section::SectionTracker;
+use crate::staged::{open_staged, staged_paths};
+use crate::syntax::{Region, RegionKind, SyntaxScanner};
+use crate::throttle::Throttle;
+use crate::time_range::TimeRange;
+use crate::walker::expand_paths;
 
 use std::{
-    collections::VecDeque,
+    collections::{HashMap, VecDeque},
     error::Error,
-    fs::File,
-    io::{BufRead, BufReader},
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Cursor, IsTerminal, Read, Write},
+    iter,
+    ops::Range,
+    path::Path,
+    time::{Instant, UNIX_EPOCH},
 };
 
 /// Represents the mutable state of the grep operation as it processes lines.
@@ -23,14 +64,22 @@ use std::{
 struct GrepState {
     /// The current line number being processed from the input file.
     line_count: usize,
-    /// A buffer holding lines encountered before a match, used for `--before-context`.
-    before_context_buffer: VecDeque<(usize, String)>,
+    /// A buffer holding lines encountered before a match, used for `--before-context`,
+    /// along with their line numbers, byte offsets and whether they count
+    /// towards the context size.
+    before_context_buffer: VecDeque<(usize, u64, String, bool)>,
     /// A counter indicating how many lines of "after context" still need to be printed.
     lines_after_match: usize,
     /// A flag indicating if the current output block is active (i.e., we are
     /// printing a match or its context lines). This helps manage context printing
     /// across consecutive matches
     printing_block_active: bool,
+    /// The number of the last line counted as a match, or zero if none was.
+    /// Used to count a line split into several chunks only once.
+    last_match_line: usize,
+    /// The number of the last line skipped as a known `--baseline` match, or
+    /// zero if none was.
+    suppressed_line: usize,
 }
 
 impl GrepState {
@@ -43,62 +92,924 @@ impl GrepState {
             before_context_buffer: VecDeque::new(),
             lines_after_match: 0,
             printing_block_active: false,
+            last_match_line: 0,
+            suppressed_line: 0,
         }
     }
 }
 
+/// The outcome of searching a single file.
+struct FileResult {
+    /// The number of matching lines found in the file.
+    match_count: usize,
+    /// Whether the search of the file was abandoned because a timeout expired.
+    timed_out: bool,
+    /// The matching lines held back by `--sort-lines` or `--format`, to be
+    /// output once every file has been searched.
+    held_lines: Vec<HeldLine>,
+    /// The entries for the `--quickfix` file, also kept for `--exec`.
+    quickfix_entries: Vec<QuickfixEntry>,
+    /// The content hashes of the matching lines, for `--write-baseline`.
+    line_hashes: Vec<u64>,
+}
+
+/// The first match of a matching line, as written to the `--quickfix` file
+/// and passed to `--exec`.
+struct QuickfixEntry {
+    /// The number of the line in its file.
+    line_num: usize,
+    /// The 1-based byte column of the first match in the line.
+    column: u64,
+    /// The matching line.
+    text: String,
+    /// The text of the first match.
+    matched: String,
+}
+
+/// The part of a single file that is searched, and the limits the search
+/// runs under.
+struct FileScope<'a> {
+    /// The maximum number of matching lines to report before the search
+    /// stops, or `None` for no limit. Trailing context of the last reported
+    /// match is still printed.
+    max_matches: Option<usize>,
+    /// The point in time at which the search of the file is abandoned, or
+    /// `None` for no time limit.
+    deadline: Option<Instant>,
+    /// The lines added to the file by the `--diff`, which are searched
+    /// instead of the file's content.
+    diff: Option<&'a DiffFile>,
+    /// The `--from` and `--until` patterns delimiting the lines searched.
+    range: Option<&'a LineRange>,
+    /// The `--since` and `--until-time` span the searched lines are from.
+    time_range: Option<&'a TimeRange>,
+}
+
+/// A matching line, or a single match with `--only-matching`, held back by
+/// `--sort-lines` or `--format`.
+struct HeldLine {
+    /// The number of the line in its file.
+    line_num: usize,
+    /// The byte offset of the text in its file, if `--byte-offset` is set.
+    byte_offset: Option<u64>,
+    /// The matching line, or the matched text.
+    text: String,
+    /// The byte ranges of the matches in `text` that made the line count, so
+    /// that scope filters such as `--only-comments` apply to them as well.
+    spans: Vec<Range<usize>>,
+}
+
 /// Executes the main grep search logic based on the provided configuration.
 ///
-/// This function reads the specified file line by line, performs pattern
-/// matching, and prints lines along with their before and after context
-/// according to the `Config`.
+/// This function searches every file listed in the configuration in order,
+/// printing matching lines along with their before and after context, or the
+/// per-file match counts when `--count` is requested. Files are searched one
+/// after another, so results always appear in the order the files were given.
 ///
 /// # Arguments
 /// * `config` - A `Config` struct containing all parsed command-line arguments
-///              and search options.
+///   and search options.
 ///
 /// # Returns
-/// A `Result` indicating success (`Ok(())`) or an error (`Err(Box<dyn Error>)`)
-/// if an issue occurs during file operations or other processes.
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
+/// A `Result` holding whether the search succeeded (`Ok(bool)`), which by
+/// default means that at least one match was found, or an error
+/// (`Err(Box<dyn Error>)`) if an issue occurs during file operations or other
+/// processes.
+pub fn run(mut config: Config) -> Result<bool, Box<dyn Error>> {
+    let start = Instant::now();
     let before_context_num = config.before_context.unwrap_or(0);
     let after_context_num = config.after_context.unwrap_or(0);
 
-    // Print initial search information using the printer module.
-    print_search_info(&config, before_context_num, after_context_num);
+    // Unless `--no-smart-defaults` is given, the output adapts to where it
+    // goes: a terminal gets line numbers and file headings, while a pipe gets
+    // plain `path:line:text` lines without the banner.
+    let piped = !config.no_smart_defaults && !io::stdout().is_terminal();
+    if !config.no_smart_defaults {
+        config.line_number = true;
+        config.heading |= !piped;
+        set_plain_output(piped);
+    }
+
+    // `--emacs` produces exactly what Emacs' `grep-mode` parses: every line
+    // starts with the file name, a NUL byte and the line number, without
+    // colors, headings, banner or summary.
+    if config.emacs {
+        config.null = true;
+        config.line_number = true;
+        config.heading = false;
+        config.no_summary = true;
+        colored::control::set_override(false);
+        set_plain_output(true);
+    }
+    set_nul_after_file_name(config.null);
+    set_no_messages(config.no_messages);
+    set_path_display(PathDisplay {
+        base: if config.absolute_path {
+            PathBase::Absolute
+        } else if let Some(dir) = &config.relative_to {
+            PathBase::RelativeTo(dir.clone())
+        } else {
+            PathBase::AsGiven
+        },
+        style: config.path_style,
+    });
+
+    // `--count-unique` tallies the matches themselves, not the lines holding
+    // them.
+    if config.count_unique {
+        config.only_matching = true;
+    }
+
+    // `--chunked` treats the input as a stream of bytes rather than of lines,
+    // so matches are reported on their own, at their byte offsets.
+    if config.chunked.is_some() {
+        config.only_matching = true;
+        config.byte_offset = true;
+        config.line_number = false;
+    }
+
+    // `--unordered` prints the pieces of a large file out of order, before
+    // the lines in front of them are counted, so matches are located by
+    // their byte offsets.
+    if config.unordered {
+        config.byte_offset = true;
+        config.line_number = false;
+    }
+
+    // Print initial search information using the printer module. Quiet mode
+    // promises no output at all, NUL-separated output is meant for `xargs -0`
+    // and reports are meant for other programs, so the banner is skipped in
+    // all of these cases.
+    if !config.quiet
+        && !config.null
+        && !piped
+        && config.format == OutputFormat::Text
+        && config.replace.is_none()
+        && !config.undo
+    {
+        print_search_info(&config, before_context_num, after_context_num);
+    }
+
+    // With `--normalize`, the query is brought into the normalization form
+    // the input lines are brought into, so that equivalent strings match.
+    if let Some(form) = config.normalize {
+        config.query = normalize(&config.query, form);
+        for pattern in &mut config.patterns {
+            *pattern = normalize(pattern, form);
+        }
+    }
+
+    // The matcher combines the query and the patterns into a single regex.
+    let matcher = Matcher::new(&config)?;
+    let range = LineRange::new(&config)?;
+    let time_range = TimeRange::new(&config)?;
+
+    // Directories given on the command line are expanded (or rejected)
+    // according to `--directories` before anything is searched.
+    // With `--diff`, the files changed by the diff are searched instead, and
+    // with `--staged` the files with staged changes.
+    let diff_files = if config.diff {
+        parse_diff(&read_diff(&config.file_paths)?)
+    } else {
+        Vec::new()
+    };
+    let file_paths = if config.diff {
+        diff_files.iter().map(|diff| diff.path.clone()).collect()
+    } else if config.staged {
+        staged_paths(&config.file_paths)?
+    } else {
+        // Binary files are what `--hex-pattern` is looking into.
+        expand_paths(
+            &config.file_paths,
+            config.directories,
+            !config.no_ext_filter && config.hex_pattern.is_none(),
+            config.one_file_system,
+        )
+    };
+    let file_paths = sort_file_paths(file_paths, &config);
+
+    // `--replace` edits the files instead of printing the matching lines,
+    // and `--undo` takes the edits back.
+    if config.replace.is_some() {
+        return replace_files(&file_paths, &config, &matcher);
+    }
+    if config.undo {
+        return undo_files(&file_paths, &config);
+    }
+
+    // File names are only shown when more than one file is searched, or when
+    // directories are searched recursively, the same way the classic `grep`
+    // behaves. Emacs needs them on every line to jump to a match, and a diff
+    // or a commit usually spans several files.
+    let with_file_name = file_paths.len() > 1
+        || config.directories == DirectoryAction::Recurse
+        || config.emacs
+        || config.diff
+        || config.staged;
+
+    // `--hex-pattern` searches the raw bytes of the files instead of their
+    // lines.
+    if let Some(pattern) = &config.hex_pattern {
+        return hex::search_files(&file_paths, pattern, with_file_name, &config);
+    }
+
+    // Match counts are collected per file so that `--count` can report them
+    // once every file has been searched.
+    let mut file_counts: Vec<(&str, usize)> = Vec::with_capacity(file_paths.len());
+    let mut total_matches = 0;
+    let mut held_lines = Vec::new();
+    let mut quickfix = match &config.quickfix {
+        Some(path) => Some(BufWriter::new(File::create(path)?)),
+        None => None,
+    };
+    // With `--write-baseline`, the current matches are recorded instead of
+    // being suppressed.
+    let mut baseline = match &config.baseline {
+        Some(path) if !config.write_baseline => Some(Baseline::load(path)?),
+        _ => None,
+    };
+    let mut baseline_matches = Vec::new();
+    let mut checkpoint = match &config.checkpoint {
+        Some(path) => Some(Checkpoint::open(path, config.resume)?),
+        None => None,
+    };
+    let mut pending_completions = Vec::new();
+    let mut searched_contents: HashMap<_, Vec<&str>> = HashMap::new();
+    let mut duplicate_files = 0;
+    let overall_deadline = config.timeout.map(|timeout| Instant::now() + timeout);
+    let preprocessors = Preprocessor::from_mappings(&config.pre_glob);
+    for file_path in file_paths.iter().map(String::as_str) {
+        // After Ctrl-C, no further files are searched; the results found so
+        // far are still reported below.
+        if interrupted() {
+            break;
+        }
+
+        // Files completed before a `--resume` were already reported.
+        if checkpoint
+            .as_ref()
+            .is_some_and(|checkpoint| checkpoint.is_completed(file_path))
+        {
+            continue;
+        }
+
+        // FIFOs, sockets and devices are skipped unless `-D read` asks for
+        // them, since opening a FIFO with no writer blocks forever.
+        if config.devices == DeviceAction::Skip && is_special_file(file_path) {
+            continue;
+        }
+
+        // With `--dedupe-content`, copies of a file that was already searched
+        // under another name are skipped as well, and counted for the summary.
+        // Files with the same length and start are compared in full.
+        if config.dedupe_content
+            && let Some(key) = content_key(file_path)
+        {
+            let same_key = searched_contents.entry(key).or_insert_with(Vec::new);
+            if same_key
+                .iter()
+                .any(|&searched| same_content(searched, file_path))
+            {
+                duplicate_files += 1;
+                continue;
+            }
+            same_key.push(file_path);
+        }
+
+        // With `--mime`, only files whose content has one of the given types
+        // are searched, whatever their extension.
+        if !config.mime.is_empty() && !mime::file_matches(file_path, &config.mime) {
+            continue;
+        }
+
+        // With `--max-total`, each file only gets whatever is left of the
+        // global match budget, and the search ends once it is used up.
+        let remaining_matches = config
+            .max_total
+            .map(|max_total| max_total.saturating_sub(total_matches));
+        if remaining_matches == Some(0) {
+            break;
+        }
+
+        // Each file has to finish before both its own `--file-timeout` and
+        // the overall `--timeout` expire, whichever comes first.
+        let file_deadline = config
+            .file_timeout
+            .map(|file_timeout| Instant::now() + file_timeout);
+        let deadline = match (overall_deadline, file_deadline) {
+            (Some(overall), Some(file)) => Some(overall.min(file)),
+            (overall, file) => overall.or(file),
+        };
+
+        let mut known = match baseline.as_mut() {
+            Some(baseline) => baseline.take_file(file_path),
+            None => KnownMatches::default(),
+        };
+        let file_name = with_file_name.then_some(file_path);
+        let scope = FileScope {
+            max_matches: remaining_matches,
+            deadline,
+            diff: diff_files.iter().find(|diff| diff.path == file_path),
+            range: range.as_ref(),
+            time_range: time_range.as_ref(),
+        };
+        // With `--threads`, a large file is split into pieces that are
+        // searched side by side.
+        let threads = config.threads.unwrap_or(1) as usize;
+        let searched =
+            if threads > 1 && parallel::supports(&config) && parallel::is_large(file_path) {
+                parallel::search_file(file_path, file_name, &config, &matcher, threads).map(
+                    |match_count| FileResult {
+                        match_count,
+                        timed_out: false,
+                        held_lines: Vec::new(),
+                        quickfix_entries: Vec::new(),
+                        line_hashes: Vec::new(),
+                    },
+                )
+            } else {
+                search_file(
+                    file_path,
+                    file_name,
+                    &config,
+                    &matcher,
+                    scope,
+                    &mut known,
+                    &preprocessors,
+                )
+            };
+        let result = match searched {
+            Ok(result) => result,
+            // Unreadable files are reported and skipped, silently with
+            // `--no-messages`, and the search goes on with the others.
+            Err(e) => {
+                print_file_error(&message(MessageId::FileError, &[&file_path, &e]));
+                continue;
+            }
+        };
+        let count = result.match_count;
+        total_matches += count;
+        file_counts.push((file_path, count));
+        held_lines.extend(
+            result
+                .held_lines
+                .into_iter()
+                .map(|held_line| (file_path, held_line)),
+        );
+        baseline_matches.extend(result.line_hashes.into_iter().map(|hash| (file_path, hash)));
+        if let Some(quickfix) = quickfix.as_mut() {
+            for entry in &result.quickfix_entries {
+                writeln!(
+                    quickfix,
+                    "{}:{}:{}: {}",
+                    display_path(file_path),
+                    entry.line_num,
+                    entry.column,
+                    entry.text
+                )?;
+            }
+        }
+
+        // `--exec` runs its command once the file has been searched, so that
+        // the command sees the file as it was searched. Like `find -exec`,
+        // a failing command does not stop the search, but makes it fail.
+        if let Some(command) = &config.exec {
+            let run_exec = |entry| -> io::Result<()> {
+                let status = exec::run(command, &exec_fields(file_path, entry))?;
+                if !status.success() {
+                    print_file_error(&message(MessageId::ExecFailed, &[&file_path, &status]));
+                }
+                Ok(())
+            };
+            if config.exec_per_match {
+                for entry in &result.quickfix_entries {
+                    run_exec(Some(entry))?;
+                }
+            } else if count > 0 {
+                run_exec(result.quickfix_entries.first())?;
+            }
+        }
+
+        // In quiet mode the answer is known as soon as anything matched, so
+        // the remaining files are not searched at all.
+        if config.quiet && config.stops_at_first_match() && count > 0 {
+            break;
+        }
+        if config.files_with_matches && count > 0 {
+            print_file_name(file_path, config.null);
+        }
+
+        // A file cut short by Ctrl-C or a timeout is searched again on resume.
+        // A file is only recorded once its results are out, so those held
+        // back until the end of the run are recorded after they are printed.
+        if let Some(checkpoint) = checkpoint.as_mut()
+            && !result.timed_out
+            && !interrupted()
+        {
+            if config.holds_output() {
+                pending_completions.push(file_path);
+            } else {
+                if let Some(quickfix) = quickfix.as_mut() {
+                    quickfix.flush()?;
+                }
+                io::stdout().flush()?;
+                checkpoint.complete(file_path)?;
+            }
+        }
+
+        if result.timed_out {
+            if overall_deadline.is_some_and(|overall| Instant::now() >= overall) {
+                let timeout = format!("{:?}", config.timeout.unwrap_or_default());
+                print_warning(&message(MessageId::SearchTimedOut, &[&file_path, &timeout]));
+                break;
+            }
+            let file_timeout = format!("{:?}", config.file_timeout.unwrap_or_default());
+            print_warning(&message(
+                MessageId::FileTimedOut,
+                &[&file_path, &file_timeout],
+            ));
+        }
+    }
 
-    // Prepare the regex pattern string. If `word_regexp` is enabled,
-    // word boundaries (`\b`) are added around the escaped query.
-    let pattern_string = if config.word_regexp {
-        format!(r"\b{}\b", regex::escape(&config.query))
+    if interrupted() {
+        print_warning(&message(MessageId::Interrupted, &[]));
+    }
+
+    if config.count && !config.quiet {
+        for &(file_path, count) in &file_counts {
+            if !with_file_name {
+                print_file_count(None, count);
+            } else if count > 0 || config.include_zero {
+                print_file_count(Some(file_path), count);
+            }
+        }
+    }
+
+    if config.sort_lines {
+        sort_held_lines(&mut held_lines, config.unique);
+    }
+    match config.format {
+        OutputFormat::Text => {
+            if config.count_unique {
+                if !config.quiet {
+                    print_unique_counts(&unique_counts(&held_lines));
+                }
+            } else if config.sort_lines {
+                print_held_lines(&held_lines, with_file_name, &config, &matcher);
+            }
+        }
+        OutputFormat::Json => {
+            let entries = report_entries(&held_lines);
+            write_json(&mut io::stdout().lock(), &entries, file_info)?;
+        }
+        OutputFormat::Codequality => {
+            let entries = report_entries(&held_lines);
+            write_codequality(&mut io::stdout().lock(), &entries)?;
+        }
+        OutputFormat::Junit => {
+            let entries = report_entries(&held_lines);
+            write_junit(&mut io::stdout().lock(), &entries)?;
+        }
+    }
+
+    if let Some(top) = config.top
+        && !config.quiet
+    {
+        // The sort is stable, so files with equal counts keep the search
+        // order.
+        let mut ranked: Vec<_> = file_counts.iter().filter(|(_, count)| *count > 0).collect();
+        ranked.sort_by_key(|&&(_, count)| std::cmp::Reverse(count));
+        for &(file_path, count) in ranked.into_iter().take(top) {
+            print_file_count(Some(file_path), count);
+        }
+    }
+
+    if let Some(by) = config.histogram
+        && !config.quiet
+    {
+        print_histogram(&histogram_rows(&file_counts, by));
+    }
+
+    if !config.quiet && !config.no_summary {
+        let matching_files = file_counts.iter().filter(|(_, count)| *count > 0).count();
+        // `--files-with-matches` stops at the first match of each file, so
+        // only the files are worth counting.
+        let tally = if config.files_with_matches {
+            Tally::Files
+        } else {
+            Tally::Lines
+        };
+        print_summary(tally, total_matches, matching_files, start.elapsed());
+        if duplicate_files > 0 {
+            print_duplicates_skipped(duplicate_files);
+        }
+    }
+
+    if let Some(max_allowed) = config.max_allowed
+        && total_matches > max_allowed
+    {
+        print_warning(&message(
+            MessageId::MaxAllowedExceeded,
+            &[&total_matches, &max_allowed],
+        ));
+    }
+
+    if let Some(path) = &config.baseline
+        && config.write_baseline
+    {
+        let mut out = BufWriter::new(File::create(path)?);
+        write_baseline(&mut out, &baseline_matches)?;
+        out.flush()?;
+    }
+    if let Some(mut quickfix) = quickfix {
+        quickfix.flush()?;
+    }
+    io::stdout().flush()?;
+    if let Some(checkpoint) = checkpoint.as_mut() {
+        for file_path in pending_completions {
+            checkpoint.complete(file_path)?;
+        }
+    }
+    Ok(config.succeeded(total_matches))
+}
+
+/// Searches a single file, printing matching lines and their context.
+///
+/// When `--count`, `--files-with-matches` or `--quiet` is enabled nothing is
+/// printed here; the caller is responsible for reporting the result. The last
+/// two only need to know whether the file matches at all, so reading stops at
+/// the first match.
+///
+/// # Arguments
+/// * `file_path` - The path of the file to search.
+/// * `file_name` - The name to prefix output lines with (or to print as a
+///   heading with `--heading`), or `None` if file names should not be shown.
+/// * `config` - The search configuration.
+/// * `matcher` - The matcher to find the query in each line with.
+/// * `scope` - The match limit, deadline, `--diff` lines and `--from` range of
+///   the search.
+/// * `known` - The matches recorded in the `--baseline`, which are skipped.
+/// * `preprocessors` - The `--pre-glob` preprocessors. If one matches the
+///   file, its output is searched instead of the file's content.
+///
+/// # Returns
+/// A `FileResult` describing the matches found in the file.
+fn search_file(
+    file_path: &str,
+    file_name: Option<&str>,
+    config: &Config,
+    matcher: &Matcher,
+    scope: FileScope,
+    known: &mut KnownMatches,
+    preprocessors: &[Preprocessor],
+) -> Result<FileResult, Box<dyn Error>> {
+    // `--only-matching` prints the matches alone, without any context, and
+    // context would be meaningless next to lines reordered by `--sort-lines`.
+    let (before_context_num, after_context_num) =
+        if config.only_matching || config.holds_lines() || config.diff {
+            (0, 0)
+        } else {
+            (
+                config.before_context.unwrap_or(0),
+                config.after_context.unwrap_or(0),
+            )
+        };
+
+    // With `--heading`, the file name is printed once before the first line
+    // that is output, instead of in front of every line.
+    let (file_name, mut heading) = if config.heading {
+        (None, file_name)
     } else {
-        regex::escape(&config.query)
+        (file_name, None)
     };
 
-    let search_regex = if config.ignore_case {
-        Regex::new(&format!("(?i){}", pattern_string))
-            .map_err(|e| format!("Invalid regex pattern: {}", e))?
+    // With `--only-comments` or `--only-strings`, matches must lie inside a
+    // comment or string literal. Files in languages without known lexing
+    // rules cannot contain any, so they are not read at all.
+    // `--context-skip-comments` needs the lexer as well, but does not
+    // restrict the matches.
+    let scoped = config.only_comments || config.only_strings;
+    let mut scanner = None;
+    if scoped || config.context_skip_comments {
+        scanner = SyntaxScanner::for_path(file_path);
+        if scoped && scanner.is_none() {
+            return Ok(FileResult {
+                match_count: 0,
+                timed_out: false,
+                held_lines: Vec::new(),
+                quickfix_entries: Vec::new(),
+                line_hashes: Vec::new(),
+            });
+        }
+    }
+
+    // Open the file (or the output of its preprocessor, the body of a URL,
+    // its lines added by the `--diff` or its `--staged` content) and create a
+    // buffered reader for efficient line-by-line reading. The `?` operator
+    // handles potential file opening errors.
+    let mut preprocessed = if scope.diff.is_some() {
+        None
+    } else if config.staged {
+        Some(open_staged(file_path)?)
+    } else if is_url(file_path) {
+        Some(open_url(file_path)?)
     } else {
-        // If not case-insensitive, just use the built regex as is.
-        Regex::new(&pattern_string).map_err(|e| format!("Invalid regex pattern: {}", e))?
+        match Preprocessor::find(preprocessors, file_path) {
+            Some(preprocessor) => Some(preprocessor.spawn(file_path)?),
+            None => None,
+        }
+    };
+    let reader: Box<dyn BufRead + '_> = match (scope.diff, preprocessed.as_mut()) {
+        (Some(diff), _) => Box::new(Cursor::new(diff.text())),
+        (None, Some(output)) => Box::new(&mut output.reader),
+        (None, None) => {
+            let file = File::open(fs_path(Path::new(file_path)))?;
+            if config.io_backend == IoBackend::Fadvise {
+                advise_sequential(&file);
+            }
+            match config.throttle {
+                Some(rate) => Box::new(BufReader::new(Throttle::new(file, rate as u64))),
+                None => Box::new(BufReader::new(file)),
+            }
+        }
+    };
+    // With `--normalize`, the lines are brought into the same normalization
+    // form as the query before anything else sees them.
+    let reader: Box<dyn BufRead + '_> = match config.normalize {
+        Some(form) => Box::new(BufReader::new(NormalizeLines::new(reader, form))),
+        None => reader,
+    };
+    // With `--strip-ansi`, escape sequences are dropped before the input is
+    // split into lines, so that they never reach the matcher or the output.
+    let reader: Box<dyn BufRead + '_> = if config.strip_ansi {
+        Box::new(BufReader::new(StripAnsi::new(reader)))
+    } else {
+        reader
     };
 
-    // Open the file and create a buffered reader for efficient line-by-line reading.
-    // The `?` operator handles potential file opening errors.
-    let file = File::open(config.file_path)?;
-    let reader = BufReader::new(file);
+    // Over-long lines are read in chunks. Every chunk repeats enough of the
+    // previous one to hold a whole match plus the character in front of it,
+    // which word boundaries need to look at. A literal pattern matches at
+    // most four bytes per character, even with case folding.
+    let longest_pattern = config
+        .search_patterns()
+        .iter()
+        .map(|pattern| {
+            if pattern.regex {
+                preset::LONGEST_MATCH
+            } else {
+                pattern.text.chars().count()
+            }
+        })
+        .max()
+        .unwrap_or(0);
+    let overlap = (longest_pattern + 1) * 4;
+    let max_line_length = effective_max_line_length(config, overlap);
+    let separator = config.record_separator.as_deref().unwrap_or("\n");
+    let mut chunks = ChunkedLines::new(reader, max_line_length, overlap, separator.as_bytes());
 
     let mut state = GrepState::new();
+    let mut range = scope.range.map(LineRange::tracker);
+    let mut time_range = scope.time_range.map(TimeRange::tracker);
+    let mut in_time_range = true;
+    // Like timestamps, levels hold for the lines below theirs that have
+    // none. With JSON logs, only matches within the message count.
+    let mut in_level = config.level.is_none();
+    let mut log_message = None;
+    // With `--column-name`, the column is only known once the header row
+    // has been read.
+    let mut csv_column = config.column_index.map(|index| index as usize - 1);
+    let mut csv_cell = None;
+    let mut json_value = None;
+    let mut tag_stripper = config.strip_tags.then(TagStripper::new);
+    let mut section = config
+        .section
+        .as_deref()
+        .map(|name| SectionTracker::new(name, file_path));
+    let mut in_section = true;
+    let mut markdown = config
+        .markdown_scope
+        .and_then(|scope| MarkdownTracker::new(scope, file_path));
+    let mut in_markdown_scope = true;
+    let mut match_count = 0;
+    let mut held_lines = Vec::new();
+    let mut quickfix_entries = Vec::new();
+    let mut line_hashes = Vec::new();
+    let mut line_offset = 0;
+    let stop_at_first_match = config.stops_at_first_match();
 
-    // Iterate through each line of the file.
-    for line_result in reader.lines() {
-        state.line_count += 1; // Increment line count for each line processed
-        let line = line_result?; // Get the current line content
-        let current_line_ref = &line;
+    // Iterate through each line (or chunk of an over-long line) of the file.
+    while let Some(chunk_result) = chunks.next_chunk() {
+        // Once the match limit is reached, only the pending after-context
+        // lines of the last match are still of interest.
+        let limit_reached = scope.max_matches.is_some_and(|max| match_count >= max);
+        if limit_reached && state.lines_after_match == 0 {
+            break;
+        }
 
-        // Check if the current line matches the processed query.
-        // `find().is_some()` returns true if the regex finds at least one match.
-        let is_match = search_regex.find(current_line_ref).is_some();
+        if interrupted() {
+            break;
+        }
+        if scope
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return Ok(FileResult {
+                match_count,
+                timed_out: true,
+                held_lines,
+                quickfix_entries,
+                line_hashes,
+            });
+        }
+
+        let chunk = chunk_result?; // Get the current line content
+        if !chunk.continues_line {
+            state.line_count += 1; // Increment line count for each line processed
+            line_offset = chunk.offset;
+        }
+        let line = chunk.text;
+        let visible = tag_stripper.as_mut().map(|stripper| stripper.strip(line));
+        let byte_offset = config.byte_offset.then_some(chunk.offset);
+        if !chunk.continues_line
+            && let Some(section) = section.as_mut()
+        {
+            in_section = section.advance(line);
+        }
+        if !chunk.continues_line
+            && let Some(markdown) = markdown.as_mut()
+        {
+            in_markdown_scope = markdown.advance(line);
+        }
+        let in_range = range
+            .as_mut()
+            .is_none_or(|range| range.advance(state.line_count, line, !chunk.continues_line));
+        if !chunk.continues_line
+            && let Some(time_range) = time_range.as_mut()
+        {
+            in_time_range = time_range.advance(line);
+        }
+        if let Some(log_format) = config.log_format {
+            let log_line = if chunk.continues_line {
+                LogLine::default()
+            } else {
+                log_format::parse(line, log_format)
+            };
+            if let (Some(filter), Some(level)) = (config.level, log_line.level) {
+                in_level = filter.allows(level);
+            }
+            log_message = log_line.message;
+        }
+        if let Some(path) = &config.json_path {
+            json_value = json::find(line, path)
+                .filter(|_| !chunk.continues_line)
+                .map(|value| value.content());
+        }
+        if config.csv {
+            if csv_column.is_none() {
+                let name = config.column_name.as_deref().unwrap_or_default();
+                let index = csv::column_index(line, name)
+                    .ok_or_else(|| message(MessageId::ColumnNotFound, &[&name]))?;
+                csv_column = Some(index);
+                continue;
+            }
+            csv_cell = csv_column
+                .filter(|_| !chunk.continues_line)
+                .and_then(|column| csv::cell(line, column));
+        }
+
+        // The lexer sees every chunk, even after the match limit is reached,
+        // so that its state stays in step with the file.
+        let regions = scanner
+            .as_mut()
+            .map(|scanner| scoped_regions(scanner, line, chunk.overlap_len, chunk.continues_line));
+
+        // Check if the current line matches the processed query. Matches lying
+        // entirely within the overlap window of a continuation chunk were
+        // already seen in the previous chunk of the same line, so they are
+        // skipped here.
+        let counts = |m: &Range<usize>| {
+            (!chunk.continues_line || m.end > chunk.overlap_len)
+                && (config.log_format != Some(LogFormat::Json) || within(&log_message, m))
+                && (!config.csv || within(&csv_cell, m))
+                && (config.json_path.is_none() || within(&json_value, m))
+                && regions
+                    .as_ref()
+                    .filter(|_| scoped)
+                    .is_none_or(|regions| in_scope(regions, m.start, m.end, config))
+        };
+        let mut is_match = !limit_reached
+            && in_range
+            && in_time_range
+            && in_level
+            && in_section
+            && in_markdown_scope
+            && scope
+                .diff
+                .is_none_or(|diff| diff.is_added(state.line_count))
+            && line_matches(matcher, line, visible.as_ref()).any(|m| counts(&m))
+                != config.invert_match;
+
+        // Lines recorded in the `--baseline` are not reported, however many
+        // chunks of them match.
+        if is_match && state.suppressed_line == state.line_count {
+            is_match = false;
+        } else if is_match && state.last_match_line != state.line_count && known.suppress(line) {
+            state.suppressed_line = state.line_count;
+            is_match = false;
+        }
+
+        if is_match && stop_at_first_match {
+            return Ok(FileResult {
+                match_count: 1,
+                timed_out: false,
+                held_lines: Vec::new(),
+                quickfix_entries: Vec::new(),
+                line_hashes: Vec::new(),
+            });
+        }
+
+        // A line split into several chunks still counts as a single matching
+        // line, however many of its chunks match.
+        let is_new_matching_line = is_match && state.last_match_line != state.line_count;
+        if is_new_matching_line {
+            match_count += 1;
+            state.last_match_line = state.line_count;
+            if config.write_baseline {
+                line_hashes.push(line_hash(line));
+            }
+
+            // A whole-file `--exec` only needs the first match.
+            if (config.quickfix.is_some()
+                || config.exec_per_match
+                || (config.exec.is_some() && quickfix_entries.is_empty()))
+                && let Some(first) =
+                    line_matches(matcher, line, visible.as_ref()).find(|m| counts(m))
+            {
+                quickfix_entries.push(QuickfixEntry {
+                    line_num: state.line_count,
+                    column: chunk.offset - line_offset + first.start as u64 + 1,
+                    text: line.to_string(),
+                    matched: line[first].to_string(),
+                });
+            }
+        }
+
+        // `--print-cell` shows the cell of the column instead of the row.
+        let line = match &csv_cell {
+            Some(cell) if config.print_cell => &line[cell.clone()],
+            None if config.print_cell => "",
+            _ => line,
+        };
+
+        if config.counts_only() || config.quiet || config.files_with_matches {
+            // Only the number of matching lines is needed, so no context
+            // tracking or printing takes place. This also covers `--quiet`
+            // and `--files-with-matches` when `--max-allowed` keeps them
+            // searching past the first match.
+            continue;
+        }
+
+        if config.holds_lines() {
+            // Matching lines are held back, to be sorted or reported together
+            // with those of every other file. Only the lines that passed
+            // every filter are held, as when they are printed right away.
+            if config.only_matching && is_match {
+                let matches = line_matches(matcher, line, visible.as_ref());
+                held_lines.extend(matches.filter(counts).map(|m| HeldLine {
+                    line_num: state.line_count,
+                    byte_offset: byte_offset.map(|offset| offset + m.start as u64),
+                    text: line[m.start..m.end].to_string(),
+                    spans: iter::once(0..m.len()).collect(),
+                }));
+            } else if is_match {
+                // The matches are found in the whole chunk, while `line` may
+                // be just its `--print-cell` cell.
+                let shift = match &csv_cell {
+                    Some(cell) if config.print_cell => cell.start,
+                    _ => 0,
+                };
+                held_lines.push(HeldLine {
+                    line_num: state.line_count,
+                    byte_offset,
+                    text: line.to_string(),
+                    spans: line_matches(matcher, chunk.text, visible.as_ref())
+                        .filter(counts)
+                        .map(|m| m.start - shift..m.end - shift)
+                        .collect(),
+                });
+            }
+            continue;
+        }
+
+        // Blank and comment lines can be left out of the context size, and
+        // are then only printed between lines that count.
+        let skipped_in_context = (config.context_skip_blank && line.trim().is_empty())
+            || (config.context_skip_comments
+                && regions
+                    .as_ref()
+                    .is_some_and(|regions| is_comment_line(regions, line)));
+        let counts_as_context = !skipped_in_context;
 
         // Use a match statement to handle different scenarios based on `is_match`
         // and whether we are currently printing "after context" lines.
@@ -106,13 +1017,24 @@ pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
             // Scenario 1: Current line is a match.
             // This branch handles printing the matching line and its "before context".
             (true, _) => {
+                if let Some(name) = heading.take() {
+                    print_heading(name);
+                }
+
                 // If we are starting a new printing block (i.e., not a continuation
                 // from a previous match's context) and before context is requested,
                 // print all lines currently in the before-context buffer.
                 if !state.printing_block_active && before_context_num > 0 {
-                    for (buffered_line_num, buffered_line) in state.before_context_buffer.drain(..)
+                    for (buffered_line_num, buffered_offset, buffered_line, _) in
+                        state.before_context_buffer.drain(..)
                     {
-                        print_line(buffered_line_num, &buffered_line, config.line_number);
+                        print_context_line(
+                            file_name,
+                            buffered_line_num,
+                            &buffered_line,
+                            config.line_number,
+                            config.byte_offset.then_some(buffered_offset),
+                        );
                     }
                 }
 
@@ -120,8 +1042,28 @@ pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
                 // before-context was needed for this match.
                 state.before_context_buffer.clear();
 
-                // Print the matching line itself with highlighting.
-                print_highlighted_line(state.line_count, &line, config.line_number, &search_regex);
+                // Print the matching line itself with highlighting, or just
+                // its matches with `--only-matching`.
+                if config.only_matching {
+                    for m in line_matches(matcher, line, visible.as_ref()).filter(counts) {
+                        print_match(
+                            file_name,
+                            state.line_count,
+                            &line[m.start..m.end],
+                            config.line_number,
+                            byte_offset.map(|offset| offset + m.start as u64),
+                        );
+                    }
+                } else {
+                    print_highlighted_line(
+                        file_name,
+                        state.line_count,
+                        line,
+                        config.line_number,
+                        byte_offset,
+                        line_matches(matcher, line, visible.as_ref()),
+                    );
+                }
 
                 // Reset the counter for after-context lines and activate the printing block.
                 state.lines_after_match = after_context_num;
@@ -131,22 +1073,48 @@ pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
             // This branch handles printing lines that follow a previous match as context.
             (false, true) => {
                 // Print the current line as part of the after-context.
-                print_line(state.line_count, &line, config.line_number);
-                state.lines_after_match -= 1; // Decrement the after-context counter
+                print_context_line(
+                    file_name,
+                    state.line_count,
+                    line,
+                    config.line_number,
+                    byte_offset,
+                );
+                if counts_as_context {
+                    state.lines_after_match -= 1; // Decrement the after-context counter
+                }
                 state.printing_block_active = true; // Stay in active printing block
             }
             // Scenario 3: Current line is neither a match nor part of active after-context.
             // This branch handles lines that are potential "before context" for future matches.
             (false, false) => {
                 //Add this line to the before-context buffer.
-                // `line` can be moved here as it's not used further in this iteration.
-                state
-                    .before_context_buffer
-                    .push_back((state.line_count, line));
+                // The line only borrows the reader's buffer, so it is copied
+                // out here; this is the only place lines are kept around.
+                state.before_context_buffer.push_back((
+                    state.line_count,
+                    chunk.offset,
+                    line.to_string(),
+                    counts_as_context,
+                ));
 
                 // Ensure the buffer does not exceed the specified before-context size.
-                // If it does, remove the oldest line from the front.
-                if state.before_context_buffer.len() > before_context_num {
+                // If it does, remove the oldest lines from the front, along
+                // with uncounted lines no longer followed by enough counted ones.
+                let mut counted = state
+                    .before_context_buffer
+                    .iter()
+                    .filter(|(.., counts)| *counts)
+                    .count();
+                while let Some(&(.., front_counts)) = state.before_context_buffer.front() {
+                    if counted < before_context_num
+                        || (counted == before_context_num && front_counts)
+                    {
+                        break;
+                    }
+                    if front_counts {
+                        counted -= 1;
+                    }
                     state.before_context_buffer.pop_front();
                 }
                 state.printing_block_active = false; // Not in an active printing block
@@ -154,5 +1122,394 @@ pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
         }
     }
 
-    Ok(())
+    // A preprocessor, download or `git show` that failed left its output
+    // incomplete, so the file was not really searched.
+    drop(chunks);
+    if let Some(output) = preprocessed {
+        output.finish()?;
+    }
+
+    Ok(FileResult {
+        match_count,
+        timed_out: false,
+        held_lines,
+        quickfix_entries,
+        line_hashes,
+    })
+}
+
+/// Lexes the part of a chunk not seen before and returns the comments and
+/// string literals in the whole chunk.
+///
+/// # Arguments
+/// * `scanner` - The lexer of the file being searched.
+/// * `chunk` - The line, or chunk of an over-long line.
+/// * `overlap_len` - The number of bytes at the start of `chunk` repeated from
+///   the previous chunk.
+/// * `continues_line` - Whether `chunk` continues the previous chunk.
+///
+/// # Returns
+/// The regions, as byte ranges of `chunk`.
+fn scoped_regions(
+    scanner: &mut SyntaxScanner,
+    chunk: &str,
+    overlap_len: usize,
+    continues_line: bool,
+) -> Vec<Region> {
+    let mut regions = scanner.scan_line(&chunk[overlap_len..], continues_line);
+    for region in &mut regions {
+        region.start += overlap_len;
+        region.end += overlap_len;
+    }
+    // A region carried over from the previous chunk also covers the overlap,
+    // so that matches straddling the chunk boundary are not lost.
+    if let Some(first) = regions.first_mut()
+        && first.start == overlap_len
+    {
+        first.start = 0;
+    }
+    regions
+}
+
+/// Returns the matches in `line`, or with `--strip-tags` those in its
+/// visible text, mapped back onto the line.
+fn line_matches<'a>(
+    matcher: &'a Matcher,
+    line: &'a str,
+    visible: Option<&'a VisibleText>,
+) -> impl Iterator<Item = Range<usize>> + 'a {
+    let (plain, stripped) = match visible {
+        Some(visible) => (
+            None,
+            Some(
+                matcher
+                    .find_iter(&visible.text)
+                    .map(|m| visible.original_range(m)),
+            ),
+        ),
+        None => (Some(matcher.find_iter(line)), None),
+    };
+    plain
+        .into_iter()
+        .flatten()
+        .chain(stripped.into_iter().flatten())
+}
+
+/// Returns whether the match `m` lies within `field`, such as the message
+/// of a log line or a CSV cell. No match lies within a missing field.
+fn within(field: &Option<Range<usize>>, m: &Range<usize>) -> bool {
+    field
+        .as_ref()
+        .is_some_and(|field| field.start <= m.start && m.end <= field.end)
+}
+
+/// Returns whether `line` holds nothing but comments and whitespace.
+fn is_comment_line(regions: &[Region], line: &str) -> bool {
+    !line.trim().is_empty()
+        && line
+            .char_indices()
+            .filter(|(_, c)| !c.is_whitespace())
+            .all(|(i, _)| {
+                regions.iter().any(|region| {
+                    region.kind == RegionKind::Comment && region.start <= i && i < region.end
+                })
+            })
+}
+
+/// Returns whether the match at `start..end` lies inside a region of a kind
+/// requested by `--only-comments` or `--only-strings`.
+fn in_scope(regions: &[Region], start: usize, end: usize, config: &Config) -> bool {
+    regions.iter().any(|region| {
+        let wanted = match region.kind {
+            RegionKind::Comment => config.only_comments,
+            RegionKind::String => config.only_strings,
+        };
+        wanted && region.start <= start && end <= region.end
+    })
+}
+
+/// Sorts the lines held back by `--sort-lines` in lexical order.
+///
+/// Lines with equal text keep the order they were found in. With `--unique`,
+/// only the first of them is kept.
+///
+/// # Arguments
+/// * `held_lines` - The held-back lines, with the paths of their files.
+/// * `unique` - Whether to drop lines equal to an earlier one.
+fn sort_held_lines(held_lines: &mut Vec<(&str, HeldLine)>, unique: bool) {
+    held_lines.sort_by(|(_, a), (_, b)| a.text.cmp(&b.text));
+    if unique {
+        held_lines.dedup_by(|(_, a), (_, b)| a.text == b.text);
+    }
+}
+
+/// Prints the held-back lines as text.
+///
+/// # Arguments
+/// * `held_lines` - The held-back lines, with the paths of their files.
+/// * `with_file_name` - Whether to prefix the lines with their file names.
+/// * `config` - The search configuration.
+/// * `matcher` - The matcher used to highlight the matches.
+fn print_held_lines(
+    held_lines: &[(&str, HeldLine)],
+    with_file_name: bool,
+    config: &Config,
+    matcher: &Matcher,
+) {
+    for (file_path, held_line) in held_lines {
+        let file_name = with_file_name.then_some(*file_path);
+        if config.only_matching {
+            print_match(
+                file_name,
+                held_line.line_num,
+                &held_line.text,
+                config.line_number,
+                held_line.byte_offset,
+            );
+        } else {
+            let visible = config
+                .strip_tags
+                .then(|| TagStripper::new().strip(&held_line.text));
+            print_highlighted_line(
+                file_name,
+                held_line.line_num,
+                &held_line.text,
+                config.line_number,
+                held_line.byte_offset,
+                line_matches(matcher, &held_line.text, visible.as_ref()),
+            );
+        }
+    }
+}
+
+/// Turns the held-back lines into the entries of a `--format` report, with
+/// the matches that made each line count.
+fn report_entries<'a>(held_lines: &'a [(&'a str, HeldLine)]) -> Vec<ReportEntry<'a>> {
+    held_lines
+        .iter()
+        .map(|(path, held_line)| ReportEntry {
+            path,
+            line_num: held_line.line_num,
+            text: &held_line.text,
+            spans: held_line
+                .spans
+                .iter()
+                .map(|span| (span.start, span.end))
+                .collect(),
+        })
+        .collect()
+}
+
+/// Returns the metadata of the file at `path` for the JSON output. Inputs
+/// that are not local files, such as URLs, get no metadata.
+fn file_info(path: &str) -> FileInfo {
+    let metadata = fs::metadata(fs_path(Path::new(path))).ok();
+    FileInfo {
+        size: metadata.as_ref().map(|metadata| metadata.len()),
+        modified: metadata
+            .and_then(|metadata| metadata.modified().ok())
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|since_epoch| since_epoch.as_secs()),
+        encoding: mime::read_header(path).map(|header| mime::encoding(&header)),
+    }
+}
+
+/// Groups the per-file match counts into the rows of `--histogram`.
+///
+/// Files without matches are left out, and the rows are ordered by count,
+/// highest first, so the hotspots come first.
+///
+/// # Arguments
+/// * `file_counts` - The match count of each searched file.
+/// * `by` - Whether to report files or their directories.
+///
+/// # Returns
+/// The labels and counts of the histogram's bars.
+fn histogram_rows(file_counts: &[(&str, usize)], by: HistogramBy) -> Vec<(String, usize)> {
+    let mut rows: Vec<(String, usize)> = Vec::new();
+    for &(file_path, count) in file_counts.iter().filter(|(_, count)| *count > 0) {
+        let label = match by {
+            HistogramBy::File => display_path(file_path).into_owned(),
+            HistogramBy::Dir => match Path::new(file_path).parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => {
+                    parent.to_string_lossy().into_owned()
+                }
+                _ => ".".to_string(),
+            },
+        };
+        match rows.iter_mut().find(|(existing, _)| *existing == label) {
+            Some((_, total)) => *total += count,
+            None => rows.push((label, count)),
+        }
+    }
+
+    // The sort is stable, so rows with equal counts keep the search order.
+    rows.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+    rows
+}
+
+/// Returns the `--exec` placeholder values of a match in the file at
+/// `file_path`, or of the file alone if the match is not known.
+fn exec_fields<'a>(file_path: &'a str, entry: Option<&'a QuickfixEntry>) -> exec::Fields<'a> {
+    exec::Fields {
+        path: file_path,
+        line: entry.map(|entry| entry.line_num),
+        column: entry.map(|entry| entry.column),
+        text: entry.map(|entry| entry.text.as_str()),
+        matched: entry.map(|entry| entry.matched.as_str()),
+    }
+}
+
+/// Tallies the held-back matches of `--count-unique`.
+///
+/// # Arguments
+/// * `held_lines` - The held-back matches, with the paths of their files.
+///
+/// # Returns
+/// Each distinct match with the number of times it occurs, the most frequent
+/// first and equally frequent ones in lexical order.
+fn unique_counts(held_lines: &[(&str, HeldLine)]) -> Vec<(String, usize)> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for (_, held_line) in held_lines {
+        *counts.entry(&held_line.text).or_default() += 1;
+    }
+    let mut rows: Vec<(String, usize)> = counts
+        .into_iter()
+        .map(|(text, count)| (text.to_string(), count))
+        .collect();
+    rows.sort_by(|(a_text, a_count), (b_text, b_count)| {
+        b_count.cmp(a_count).then_with(|| a_text.cmp(b_text))
+    });
+    rows
+}
+
+/// Works out how many bytes of a line may be read at once.
+///
+/// This is the `--max-line-length` cap, tightened when `--max-memory` is set
+/// so that the before-context buffer and the line being matched together fit
+/// into the memory budget.
+///
+/// # Arguments
+/// * `config` - The search configuration.
+/// * `overlap` - The number of bytes each chunk repeats from the previous one.
+///
+/// # Returns
+/// The maximum chunk length in bytes, or `None` if lines are read whole.
+fn effective_max_line_length(config: &Config, overlap: usize) -> Option<usize> {
+    let budget_cap = config.max_memory.map(|max_memory| {
+        let lines_held = config.before_context.unwrap_or(0) + 1;
+        (max_memory / lines_held).saturating_sub(overlap).max(1)
+    });
+
+    [config.max_line_length, config.chunked, budget_cap]
+        .into_iter()
+        .flatten()
+        .min()
+}
+
+/// Orders the files to search as requested by `--sort` or `--sortr`.
+///
+/// Without either option the files keep the order they were given in. Files
+/// whose metadata cannot be read sort as if their size or modification time
+/// were unknown, which places them first in ascending order; opening them
+/// later reports the actual error.
+///
+/// # Arguments
+/// * `file_paths` - The files to search.
+/// * `config` - The search configuration.
+///
+/// # Returns
+/// The file paths in the order they should be searched.
+fn sort_file_paths(mut file_paths: Vec<String>, config: &Config) -> Vec<String> {
+    let (sort_by, reverse) = match (config.sort, config.sortr) {
+        (Some(sort_by), _) => (sort_by, false),
+        (None, Some(sort_by)) => (sort_by, true),
+        (None, None) => return file_paths,
+    };
+
+    match sort_by {
+        SortBy::Path => file_paths.sort(),
+        SortBy::Modified => file_paths.sort_by_cached_key(|path| {
+            fs::metadata(fs_path(Path::new(path)))
+                .and_then(|metadata| metadata.modified())
+                .ok()
+        }),
+        SortBy::Size => file_paths.sort_by_cached_key(|path| {
+            fs::metadata(fs_path(Path::new(path))).map(|m| m.len()).ok()
+        }),
+    }
+
+    if reverse {
+        file_paths.reverse();
+    }
+    file_paths
+}
+
+/// Returns the length of the file behind `path` together with a hash of its
+/// first 64 KiB. Files with different keys differ in content, while files
+/// with the same key still have to be compared with `same_content`.
+///
+/// Only regular files have a key. Files that cannot be read yield `None` as
+/// well, and are never treated as duplicates; opening them later reports
+/// the actual error.
+fn content_key(path: &str) -> Option<(u64, u64)> {
+    const HASHED_LEN: u64 = 64 * 1024;
+
+    let file = File::open(fs_path(Path::new(path))).ok()?;
+    let metadata = file.metadata().ok()?;
+    if !metadata.is_file() {
+        return None;
+    }
+    let mut head = Vec::new();
+    file.take(HASHED_LEN).read_to_end(&mut head).ok()?;
+    Some((metadata.len(), fnv1a(&head)))
+}
+
+/// Returns whether the files behind `a` and `b` have the same content.
+/// Files that cannot be read are never the same.
+fn same_content(a: &str, b: &str) -> bool {
+    let open = |path: &str| File::open(fs_path(Path::new(path))).map(BufReader::new);
+    let (Ok(mut a), Ok(mut b)) = (open(a), open(b)) else {
+        return false;
+    };
+    loop {
+        let (Ok(a_bytes), Ok(b_bytes)) = (a.fill_buf(), b.fill_buf()) else {
+            return false;
+        };
+        if a_bytes.is_empty() || b_bytes.is_empty() {
+            return a_bytes.is_empty() && b_bytes.is_empty();
+        }
+        let len = a_bytes.len().min(b_bytes.len());
+        if a_bytes[..len] != b_bytes[..len] {
+            return false;
+        }
+        a.consume(len);
+        b.consume(len);
+    }
+}
+
+/// Returns whether `path` is a FIFO, socket, or character or block device.
+///
+/// Files whose metadata cannot be read are not considered special; opening
+/// them later reports the actual error.
+#[cfg(unix)]
+pub fn is_special_file(path: &str) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+
+    fs::metadata(fs_path(Path::new(path))).is_ok_and(|metadata| {
+        let file_type = metadata.file_type();
+        file_type.is_fifo()
+            || file_type.is_socket()
+            || file_type.is_char_device()
+            || file_type.is_block_device()
+    })
+}
+
+/// Returns whether `path` is a FIFO, socket, or character or block device.
+///
+/// Such files cannot be told apart on this platform, so this always returns
+/// `false`.
+#[cfg(not(unix))]
+pub fn is_special_file(_path: &str) -> bool {
+    false
 }
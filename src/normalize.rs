@@ -0,0 +1,123 @@
+//! Normalizes Unicode text, for `--normalize`.
+//!
+//! The same accented letter can be stored precomposed (`é`, as Linux and
+//! Windows tools usually write it) or decomposed into a letter and a
+//! combining mark (`e` + `◌́`, as macOS file systems do). With `--normalize`,
+//! the query and every input line are brought into the same normalization
+//! form first, so that such canonically equivalent strings match each other.
+//! NFKC additionally folds compatibility characters such as ligatures or
+//! fullwidth letters into their plain equivalents. Normalization follows the Unicode standard through the
+//! `unicode-normalization` crate.
+
+use crate::config::NormalizationForm;
+
+use std::io::{self, BufRead, Read};
+use unicode_normalization::UnicodeNormalization;
+
+/// Returns `text` in the normalization form `form`.
+pub fn normalize(text: &str, form: NormalizationForm) -> String {
+    if text.is_ascii() {
+        return text.to_string();
+    }
+    match form {
+        NormalizationForm::Nfc => text.nfc().collect(),
+        NormalizationForm::Nfkc => text.nfkc().collect(),
+    }
+}
+
+/// A reader that normalizes each line of another reader.
+///
+/// Lines are read whole, so `--max-line-length` does not bound the memory
+/// used for a single line. Lines that are not valid UTF-8 are passed through
+/// unchanged, to be reported by the line reader.
+pub struct NormalizeLines<R> {
+    /// The underlying reader.
+    inner: R,
+    /// The normalization form to bring the lines into.
+    form: NormalizationForm,
+    /// The current line, normalized.
+    line: Vec<u8>,
+    /// The number of bytes of `line` already returned.
+    position: usize,
+}
+
+impl<R: BufRead> NormalizeLines<R> {
+    /// Creates a reader normalizing the lines of `inner` into `form`.
+    pub fn new(inner: R, form: NormalizationForm) -> Self {
+        NormalizeLines {
+            inner,
+            form,
+            line: Vec::new(),
+            position: 0,
+        }
+    }
+}
+
+impl<R: BufRead> Read for NormalizeLines<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position == self.line.len() {
+            self.line.clear();
+            self.position = 0;
+            if self.inner.read_until(b'\n', &mut self.line)? == 0 {
+                return Ok(0);
+            }
+            if let Ok(text) = std::str::from_utf8(&self.line)
+                && !text.is_ascii()
+            {
+                self.line = normalize(text, self.form).into_bytes();
+            }
+        }
+        let len = buf.len().min(self.line.len() - self.position);
+        buf[..len].copy_from_slice(&self.line[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nfc() {
+        // Decomposed é, and a dot below and an acute accent in the wrong
+        // order, which must be reordered before composing.
+        assert_eq!(
+            normalize("cafe\u{301}", NormalizationForm::Nfc),
+            "caf\u{E9}"
+        );
+        assert_eq!(
+            normalize("o\u{301}\u{323}", NormalizationForm::Nfc),
+            "\u{1ECD}\u{301}"
+        );
+        assert_eq!(normalize("\u{FB01}", NormalizationForm::Nfc), "\u{FB01}");
+    }
+
+    #[test]
+    fn test_nfc_hangul() {
+        // The syllable 한 as macOS stores it, decomposed into its jamo.
+        assert_eq!(
+            normalize("\u{1112}\u{1161}\u{11AB}", NormalizationForm::Nfc),
+            "\u{D55C}"
+        );
+        assert_eq!(normalize("\u{D55C}", NormalizationForm::Nfc), "\u{D55C}");
+    }
+
+    #[test]
+    fn test_nfkc() {
+        assert_eq!(
+            normalize("\u{FB01}le \u{FF21}2\u{B2}", NormalizationForm::Nfkc),
+            "file A22"
+        );
+    }
+
+    #[test]
+    fn test_normalize_lines() {
+        let input = "re\u{301}sume\u{301}\nplain\n";
+        let mut output = String::new();
+        NormalizeLines::new(input.as_bytes(), NormalizationForm::Nfc)
+            .read_to_string(&mut output)
+            .unwrap();
+        assert_eq!(output, "r\u{E9}sum\u{E9}\nplain\n");
+    }
+}
@@ -0,0 +1,343 @@
+//! Searches a single large file on several threads, for `--threads`.
+//!
+//! Searching many files in turn keeps one core busy, and so does a single
+//! huge log. With `--threads`, a file of at least `MIN_FILE_LEN` bytes is
+//! split into pieces of about `PIECE_LEN` bytes, each ending right after a
+//! newline, so that no line is split. Worker threads take the pieces in
+//! turn and collect their matching lines, while the main thread prints the
+//! pieces in file order as soon as each one and all before it are done,
+//! numbering the lines by the line counts of the earlier pieces. With
+//! `--unordered`, each piece is printed as soon as it is done instead, with
+//! byte offsets in place of line numbers.
+//!
+//! Only plain searches are split. Options that carry state from one line to
+//! the next, such as context lines, `--from` ranges or `--section`, or that
+//! need the whole file, such as `--sort-lines` or the reports, keep the
+//! file searched on a single thread.
+
+use crate::config::Config;
+use crate::interrupt::interrupted;
+use crate::line_reader::ChunkedLines;
+use crate::matcher::Matcher;
+use crate::paths::fs_path;
+use crate::printer::{print_heading, print_highlighted_line, print_match};
+
+use std::{
+    collections::BTreeMap,
+    env,
+    error::Error,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom},
+    ops::Range,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
+};
+
+/// The size from which a file is split into pieces.
+pub const MIN_FILE_LEN: u64 = 64 << 20;
+
+/// The size of the pieces a file is split into, before they are extended to
+/// the end of their last line.
+const PIECE_LEN: u64 = 16 << 20;
+
+/// The environment variable overriding `PIECE_LEN`, so that the tests can
+/// split small files. `MIN_FILE_LEN` is scaled along with it.
+const PIECE_LEN_VAR: &str = "GREP_RUST_PIECE_LEN";
+
+/// Returns the size of the pieces a file is split into: `PIECE_LEN`, or
+/// the positive number of bytes given in `PIECE_LEN_VAR`.
+fn piece_len() -> u64 {
+    env::var(PIECE_LEN_VAR)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|&len| len > 0)
+        .unwrap_or(PIECE_LEN)
+}
+
+/// A line selected in a piece.
+struct FoundLine {
+    /// The number of the line within its piece, counting from 1.
+    line_num: usize,
+    /// The byte offset of the line in the file.
+    offset: u64,
+    /// The line, unless only the number of lines is needed.
+    text: String,
+    /// The byte ranges of the matches in `text`.
+    matches: Vec<Range<usize>>,
+}
+
+/// What was found in a piece.
+struct Piece {
+    /// The number of lines in the piece.
+    line_count: usize,
+    /// The selected lines, in order.
+    found: Vec<FoundLine>,
+}
+
+/// Returns whether a search with `config` can be split up, i.e. whether
+/// every line can be matched and printed without looking at other lines.
+pub fn supports(config: &Config) -> bool {
+    config.before_context.is_none()
+        && config.after_context.is_none()
+        && !config.holds_lines()
+        && !config.quiet
+        && !config.files_with_matches
+        && config.max_total.is_none()
+        && config.quickfix.is_none()
+        && config.exec.is_none()
+        && config.from.is_none()
+        && config.until.is_none()
+        && config.since.is_none()
+        && config.until_time.is_none()
+        && config.log_format.is_none()
+        && config.level.is_none()
+        && config.json_path.is_none()
+        && !config.csv
+        && !config.strip_ansi
+        && !config.strip_tags
+        && config.normalize.is_none()
+        && config.section.is_none()
+        && config.markdown_scope.is_none()
+        && !config.diff
+        && !config.staged
+        && config.baseline.is_none()
+        && config.max_line_length.is_none()
+        && config.record_separator.is_none()
+        && config.chunked.is_none()
+        && config.max_memory.is_none()
+        && config.throttle.is_none()
+        && config.timeout.is_none()
+        && config.file_timeout.is_none()
+        && config.pre_glob.is_empty()
+        && !config.only_comments
+        && !config.only_strings
+        && !config.context_skip_comments
+}
+
+/// Returns whether the file at `file_path` is large enough to be split.
+pub fn is_large(file_path: &str) -> bool {
+    fs::metadata(fs_path(Path::new(file_path))).is_ok_and(|metadata| {
+        metadata.is_file() && metadata.len() >= MIN_FILE_LEN / PIECE_LEN * piece_len()
+    })
+}
+
+/// Searches the file at `file_path` on `threads` threads and prints what
+/// is found, as the sequential search would.
+///
+/// # Arguments
+/// * `file_path` - The path of the file to search.
+/// * `file_name` - The name shown for the file, or `None` when only a single
+///   file is being searched.
+/// * `config` - The search configuration.
+/// * `matcher` - The matcher finding the query.
+/// * `threads` - The number of threads to search on.
+///
+/// # Returns
+/// The number of matching lines, or an error if the file cannot be read.
+pub fn search_file(
+    file_path: &str,
+    file_name: Option<&str>,
+    config: &Config,
+    matcher: &Matcher,
+    threads: usize,
+) -> Result<usize, Box<dyn Error>> {
+    let pieces = split(file_path, piece_len())?;
+    let next_piece = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+
+    // With `--heading`, the file name is printed once before the first line
+    // that is output, instead of in front of every line.
+    let (file_name, mut heading) = if config.heading {
+        (None, file_name)
+    } else {
+        (file_name, None)
+    };
+
+    thread::scope(|scope| {
+        for _ in 0..threads.min(pieces.len()) {
+            let sender = sender.clone();
+            let (pieces, next_piece) = (&pieces, &next_piece);
+            scope.spawn(move || {
+                loop {
+                    let index = next_piece.fetch_add(1, Ordering::Relaxed);
+                    if index >= pieces.len() || interrupted() {
+                        break;
+                    }
+                    let piece = search_piece(file_path, pieces[index].clone(), config, matcher);
+                    // The receiver only hangs up after an error, when the
+                    // remaining pieces are of no interest anymore.
+                    if sender.send((index, piece)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        let mut output = |piece: &Piece, lines_before: usize| {
+            if !config.counts_only() {
+                if !piece.found.is_empty()
+                    && let Some(name) = heading.take()
+                {
+                    print_heading(name);
+                }
+                print_piece(piece, lines_before, file_name, config);
+            }
+        };
+
+        // The pieces finish in any order, but are printed in file order
+        // unless `--unordered` is given.
+        let mut finished = BTreeMap::new();
+        let mut next_printed = 0;
+        let mut lines_before = 0;
+        let mut match_count = 0;
+        for (index, piece) in receiver {
+            let piece = piece?;
+            if config.unordered {
+                match_count += piece.found.len();
+                output(&piece, 0);
+                continue;
+            }
+            finished.insert(index, piece);
+            while let Some(piece) = finished.remove(&next_printed) {
+                match_count += piece.found.len();
+                output(&piece, lines_before);
+                lines_before += piece.line_count;
+                next_printed += 1;
+            }
+        }
+        Ok(match_count)
+    })
+}
+
+/// Returns the byte ranges of the pieces of the file at `file_path`, each
+/// of about `piece_len` bytes. Every piece but the last ends right after a
+/// newline.
+fn split(file_path: &str, piece_len: u64) -> io::Result<Vec<Range<u64>>> {
+    let mut file = File::open(fs_path(Path::new(file_path)))?;
+    let len = file.metadata()?.len();
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    while start < len {
+        let mut end = (start + piece_len).min(len);
+        if end < len {
+            file.seek(SeekFrom::Start(end))?;
+            let mut rest_of_line = Vec::new();
+            BufReader::new(&mut file).read_until(b'\n', &mut rest_of_line)?;
+            end += rest_of_line.len() as u64;
+        }
+        pieces.push(start..end);
+        start = end;
+    }
+    Ok(pieces)
+}
+
+/// Searches the bytes `range` of the file at `file_path`.
+fn search_piece(
+    file_path: &str,
+    range: Range<u64>,
+    config: &Config,
+    matcher: &Matcher,
+) -> io::Result<Piece> {
+    let mut file = File::open(fs_path(Path::new(file_path)))?;
+    file.seek(SeekFrom::Start(range.start))?;
+    let reader = BufReader::new(file.take(range.end - range.start));
+    let mut lines = ChunkedLines::new(reader, None, 0, b"\n");
+
+    let mut piece = Piece {
+        line_count: 0,
+        found: Vec::new(),
+    };
+    while let Some(line) = lines.next_chunk() {
+        let line = line?;
+        piece.line_count += 1;
+        let mut matches = matcher.find_iter(line.text).peekable();
+        if matches.peek().is_some() == config.invert_match {
+            continue;
+        }
+        piece.found.push(FoundLine {
+            line_num: piece.line_count,
+            offset: range.start + line.offset,
+            text: if config.counts_only() {
+                String::new()
+            } else {
+                line.text.to_string()
+            },
+            matches: matches.collect(),
+        });
+    }
+    Ok(piece)
+}
+
+/// Prints the lines found in `piece`, which follows `lines_before` lines of
+/// the file.
+fn print_piece(piece: &Piece, lines_before: usize, file_name: Option<&str>, config: &Config) {
+    for found in &piece.found {
+        let line_num = lines_before + found.line_num;
+        let byte_offset = config.byte_offset.then_some(found.offset);
+        if config.only_matching {
+            for m in &found.matches {
+                print_match(
+                    file_name,
+                    line_num,
+                    &found.text[m.clone()],
+                    config.line_number,
+                    byte_offset.map(|offset| offset + m.start as u64),
+                );
+            }
+        } else {
+            print_highlighted_line(
+                file_name,
+                line_num,
+                &found.text,
+                config.line_number,
+                byte_offset,
+                found.matches.iter().cloned(),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_pieces_cover_the_file_by_whole_lines() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let line = "x".repeat(6) + "\n";
+        let text = line.repeat(36);
+        fs::write(file.path(), &text).unwrap();
+        let path_str = file.path().to_str().unwrap();
+
+        let pieces = split(path_str, 100).unwrap();
+        assert_eq!(pieces.len(), 3);
+        assert_eq!(pieces.last().unwrap().end, text.len() as u64);
+        for pair in pieces.windows(2) {
+            assert_eq!(pair[0].end, pair[1].start);
+            assert_eq!(text.as_bytes()[pair[0].end as usize - 1], b'\n');
+        }
+
+        let config = Config::parse_from(["grep-rust", "xx", path_str]);
+        let matcher = Matcher::new(&config).unwrap();
+        let lines: usize = pieces
+            .into_iter()
+            .map(|range| search_piece(path_str, range, &config, &matcher).unwrap())
+            .map(|piece| piece.line_count)
+            .sum();
+        assert_eq!(lines, text.len() / 7);
+    }
+
+    #[test]
+    fn test_supports() {
+        let config = Config::parse_from(["grep-rust", "-n", "error", "big.log"]);
+        assert!(supports(&config));
+        let config = Config::parse_from(["grep-rust", "-A", "2", "error", "big.log"]);
+        assert!(!supports(&config));
+    }
+}
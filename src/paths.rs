@@ -0,0 +1,201 @@
+//! Adapts file paths for the platform's file system calls, and for display.
+//!
+//! On Windows, paths longer than `MAX_PATH` (260 characters) make most file
+//! system calls fail, which is easy to hit in deep `node_modules` trees.
+//! Such paths are passed to the file system in their verbatim form, with a
+//! `\\?\` prefix (or `\\?\UNC\` for network shares), which lifts the limit.
+//! The paths shown to the user are left unchanged by this.
+//!
+//! For display, paths can be made absolute (`--absolute-path`) or relative
+//! to another directory (`--relative-to`). Both work on the text of the
+//! paths alone, without resolving symbolic links. They can then be
+//! abbreviated with `--path-style`.
+
+use std::{
+    borrow::Cow,
+    path::{Component, Path, PathBuf},
+};
+
+/// The length from which a path is converted to its verbatim form. Windows
+/// limits directory paths to 248 characters, a little below `MAX_PATH`, so
+/// that a file name still fits below them.
+#[cfg(windows)]
+const LONG_PATH_THRESHOLD: usize = 248;
+
+/// Returns the form of `path` to hand to file system calls.
+///
+/// Long paths are made absolute and given the verbatim prefix. Short paths,
+/// and paths that cannot be converted, are returned unchanged.
+#[cfg(windows)]
+pub fn fs_path(path: &Path) -> Cow<'_, Path> {
+    let Some(text) = path.to_str() else {
+        return Cow::Borrowed(path);
+    };
+    if text.len() < LONG_PATH_THRESHOLD || text.starts_with(r"\\?\") {
+        return Cow::Borrowed(path);
+    }
+
+    // `absolute` also resolves `.` and `..` components and turns forward
+    // slashes into backslashes, neither of which verbatim paths allow.
+    match std::path::absolute(path) {
+        Ok(absolute) => match absolute.to_str() {
+            Some(absolute) => Cow::Owned(verbatim_path(absolute).into()),
+            None => Cow::Borrowed(path),
+        },
+        Err(_) => Cow::Borrowed(path),
+    }
+}
+
+/// Returns the form of `path` to hand to file system calls.
+///
+/// Paths need no adapting on this platform, so `path` is returned as is.
+#[cfg(not(windows))]
+pub fn fs_path(path: &Path) -> Cow<'_, Path> {
+    Cow::Borrowed(path)
+}
+
+/// Adds the verbatim prefix to an absolute, normalized Windows path.
+///
+/// `C:\dir` becomes `\\?\C:\dir`, and the UNC path `\\server\share\dir`
+/// becomes `\\?\UNC\server\share\dir`.
+#[cfg(any(windows, test))]
+fn verbatim_path(absolute: &str) -> String {
+    match absolute.strip_prefix(r"\\") {
+        Some(unc) => format!(r"\\?\UNC\{}", unc),
+        None => format!(r"\\?\{}", absolute),
+    }
+}
+
+/// Returns `path` as an absolute path, with `.` and `..` components
+/// resolved, or unchanged if the current directory cannot be determined.
+pub fn absolute_path(path: &str) -> String {
+    std::path::absolute(path)
+        .map(|absolute| normalize(&absolute).to_string_lossy().into_owned())
+        .unwrap_or_else(|_| path.to_string())
+}
+
+/// Returns `path` relative to the directory `base`, e.g. `../lib/a.rs` for
+/// `lib/a.rs` relative to `src`. Paths on another drive than `base` are
+/// returned absolute.
+pub fn relative_path(path: &str, base: &str) -> String {
+    let (Ok(path), Ok(base)) = (std::path::absolute(path), std::path::absolute(base)) else {
+        return path.to_string();
+    };
+    let path = normalize(&path);
+    let base = normalize(&base);
+
+    let mut path_components = path.components().peekable();
+    let mut base_components = base.components().peekable();
+    if path_components.peek() != base_components.peek() {
+        // Different drives or shares have no relative path between them.
+        return path.to_string_lossy().into_owned();
+    }
+    while path_components.peek().is_some() && path_components.peek() == base_components.peek() {
+        path_components.next();
+        base_components.next();
+    }
+
+    let mut relative: PathBuf = base_components.map(|_| Component::ParentDir).collect();
+    relative.extend(path_components);
+    if relative.as_os_str().is_empty() {
+        ".".to_string()
+    } else {
+        relative.to_string_lossy().into_owned()
+    }
+}
+
+/// Returns `path` with the directories between its first and its last
+/// component left out, e.g. `src/.../module.rs` for
+/// `src/parser/expr/module.rs`. Paths of up to three components are
+/// returned unchanged.
+pub fn short_path(path: &str) -> String {
+    let components: Vec<Component> = Path::new(path).components().collect();
+    // A leading `/` is a component of its own, which stays with the first
+    // directory.
+    let first = match components.first() {
+        Some(Component::RootDir | Component::Prefix(_)) => 2,
+        _ => 1,
+    };
+    if components.len() <= first + 2 {
+        return path.to_string();
+    }
+    let mut short: PathBuf = components[..first].iter().collect();
+    short.push("...");
+    short.push(components[components.len() - 1]);
+    short.to_string_lossy().into_owned()
+}
+
+/// Returns `path` with the home directory at its start written as `~`.
+/// Other paths are returned unchanged.
+pub fn tilde_path(path: &str) -> String {
+    let home = std::env::var_os(if cfg!(windows) { "USERPROFILE" } else { "HOME" });
+    match home.and_then(|home| {
+        Path::new(path)
+            .strip_prefix(home)
+            .ok()
+            .map(Path::to_path_buf)
+    }) {
+        Some(rest) if rest.as_os_str().is_empty() => "~".to_string(),
+        Some(rest) => Path::new("~").join(rest).to_string_lossy().into_owned(),
+        None => path.to_string(),
+    }
+}
+
+/// Removes the `.` components of an absolute path, and the `..` components
+/// along with the directories they leave.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verbatim_path() {
+        assert_eq!(verbatim_path(r"C:\src\main.rs"), r"\\?\C:\src\main.rs");
+        assert_eq!(
+            verbatim_path(r"\\server\share\logs\app.log"),
+            r"\\?\UNC\server\share\logs\app.log"
+        );
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn test_relative_path() {
+        assert_eq!(relative_path("/repo/src/a.rs", "/repo"), "src/a.rs");
+        assert_eq!(relative_path("/repo/lib/a.rs", "/repo/src"), "../lib/a.rs");
+        assert_eq!(relative_path("/repo/./src/../a.rs", "/repo/"), "a.rs");
+        assert_eq!(relative_path("/repo", "/repo"), ".");
+        assert_eq!(absolute_path("/repo/src/../a.rs"), "/repo/a.rs");
+        assert!(absolute_path("a.rs").starts_with('/'));
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn test_path_styles() {
+        assert_eq!(short_path("src/parser/expr/module.rs"), "src/.../module.rs");
+        assert_eq!(short_path("/home/jo/src/a.rs"), "/home/.../a.rs");
+        assert_eq!(short_path("src/parser/module.rs"), "src/parser/module.rs");
+
+        let home = std::env::var("HOME").unwrap();
+        assert_eq!(tilde_path(&format!("{}/src/a.rs", home)), "~/src/a.rs");
+        assert_eq!(tilde_path("src/a.rs"), "src/a.rs");
+    }
+
+    #[test]
+    fn test_short_paths_are_unchanged() {
+        let path = Path::new("src/main.rs");
+        assert_eq!(fs_path(path), Cow::Borrowed(path));
+    }
+}
@@ -0,0 +1,146 @@
+//! Runs external preprocessor commands that turn files into searchable text.
+//!
+//! With `--pre-glob 'GLOB=COMMAND'`, files matching `GLOB` are not read
+//! directly. Instead, `COMMAND` is run through the shell with the file on its
+//! standard input, and its standard output is searched. Several mappings can
+//! be given, so e.g. PDF and compressed files each get their own extraction
+//! command in one recursive run.
+
+use crate::config::PreGlob;
+use crate::glob::Glob;
+use crate::messages::{MessageId, message};
+use crate::paths::fs_path;
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Read},
+    path::Path,
+    process::{Child, ChildStdout, Command, Stdio},
+    thread::{self, JoinHandle},
+};
+
+/// A compiled `--pre-glob` mapping.
+pub struct Preprocessor {
+    /// The glob selecting the files this preprocessor applies to.
+    glob: Glob,
+    /// The shell command that extracts the text of a file.
+    command: String,
+}
+
+impl Preprocessor {
+    /// Compiles the `--pre-glob` mappings given on the command line.
+    pub fn from_mappings(mappings: &[PreGlob]) -> Vec<Preprocessor> {
+        mappings
+            .iter()
+            .map(|mapping| Preprocessor {
+                glob: Glob::new(&mapping.glob),
+                command: mapping.command.clone(),
+            })
+            .collect()
+    }
+
+    /// Returns the first preprocessor whose glob matches `path`, if any.
+    pub fn find<'a>(preprocessors: &'a [Preprocessor], path: &str) -> Option<&'a Preprocessor> {
+        preprocessors.iter().find(|pre| pre.glob.is_match(path))
+    }
+
+    /// Starts the preprocessor command with the file at `path` on its
+    /// standard input.
+    ///
+    /// # Returns
+    /// A `PreprocessorOutput` to read the extracted text from.
+    pub fn spawn(&self, path: &str) -> io::Result<PreprocessorOutput> {
+        let input = File::open(fs_path(Path::new(path)))?;
+        PreprocessorOutput::spawn(shell_command(&self.command).stdin(input))
+    }
+}
+
+/// The running preprocessor of a single file.
+///
+/// The process is killed and reaped when this value is dropped, so a search
+/// that stops early (e.g. at the first match) never leaves it behind. A
+/// search that reads all of the output calls `finish` instead, to learn
+/// whether the process succeeded.
+pub struct PreprocessorOutput {
+    /// The buffered standard output of the preprocessor.
+    pub reader: BufReader<ChildStdout>,
+    /// The preprocessor process.
+    child: Child,
+    /// Collects the standard error of the preprocessor, which is read on its
+    /// own thread so that the process never blocks on a full pipe.
+    stderr: Option<JoinHandle<Vec<u8>>>,
+}
+
+impl PreprocessorOutput {
+    /// Starts `command` with its standard output piped, to be read instead
+    /// of a file.
+    pub fn spawn(command: &mut Command) -> io::Result<PreprocessorOutput> {
+        let mut child = command
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let mut stderr = child.stderr.take().expect("stderr is piped");
+        let stderr = thread::spawn(move || {
+            let mut message = Vec::new();
+            let _ = stderr.read_to_end(&mut message);
+            message
+        });
+
+        Ok(PreprocessorOutput {
+            reader: BufReader::new(stdout),
+            child,
+            stderr: Some(stderr),
+        })
+    }
+
+    /// Waits for the process once its output was read to the end. If the
+    /// search stopped before that, the process is killed instead.
+    ///
+    /// # Returns
+    /// An error holding what the process printed to its standard error if
+    /// it failed, since its output is then missing or incomplete.
+    pub fn finish(mut self) -> io::Result<()> {
+        if !self.reader.fill_buf()?.is_empty() {
+            return Ok(());
+        }
+        let status = self.child.wait()?;
+        let stderr = self
+            .stderr
+            .take()
+            .and_then(|stderr| stderr.join().ok())
+            .unwrap_or_default();
+        if status.success() {
+            return Ok(());
+        }
+        Err(io::Error::other(
+            match String::from_utf8_lossy(&stderr).trim() {
+                "" => message(MessageId::PreprocessorFailed, &[&status]),
+                stderr => stderr.to_string(),
+            },
+        ))
+    }
+}
+
+impl Drop for PreprocessorOutput {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Builds a command that runs `command_line` through the platform's shell.
+#[cfg(not(windows))]
+pub fn shell_command(command_line: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(command_line);
+    command
+}
+
+/// Builds a command that runs `command_line` through the platform's shell.
+#[cfg(windows)]
+pub fn shell_command(command_line: &str) -> Command {
+    let mut command = Command::new("cmd");
+    command.arg("/C").arg(command_line);
+    command
+}
@@ -0,0 +1,219 @@
+//! The regular expressions behind `--preset`.
+//!
+//! Queries are always searched for literally, which leaves no way to look
+//! for things like "any email address". `--preset NAME` adds one of the
+//! patterns below to the search instead. They are kept here, next to the
+//! tests that pin down what they accept and reject, so that nobody has to
+//! copy a half-working email regex from the web again.
+//!
+//! The patterns favour finding the values as they appear in logs and
+//! documents over validating them exactly: an IPv4 address is checked octet
+//! by octet, but an email address is only checked for its general shape.
+
+use crate::config::Preset;
+
+/// The most characters a preset is expected to match, used as the overlap
+/// when over-long lines are read in chunks. Longer matches, which only URLs
+/// reasonably reach, may be missed where a line is split.
+pub const LONGEST_MATCH: usize = 2048;
+
+/// A group of 1 to 4 hexadecimal digits in an IPv6 address.
+macro_rules! h16 {
+    () => {
+        "[0-9A-Fa-f]{1,4}"
+    };
+}
+
+/// An octet of an IPv4 address, from 0 to 255 without leading zeros.
+macro_rules! octet {
+    () => {
+        "(?:25[0-5]|2[0-4][0-9]|1[0-9]{2}|[1-9]?[0-9])"
+    };
+}
+
+const EMAIL: &str = concat!(
+    r"\b[A-Za-z0-9._%+-]+@",
+    r"[A-Za-z0-9](?:[A-Za-z0-9-]{0,61}[A-Za-z0-9])?",
+    r"(?:\.[A-Za-z0-9](?:[A-Za-z0-9-]{0,61}[A-Za-z0-9])?)*",
+    r"\.[A-Za-z]{2,63}\b",
+);
+
+const IPV4: &str = concat!(r"\b(?:", octet!(), r"\.){3}", octet!(), r"\b");
+
+// The regex engine takes the first alternative that matches rather than the
+// longest, so the forms with a `::` are ordered by how many groups can
+// follow it, most first. A leading `::` must not follow a word character, so
+// that e.g. `std::` is not taken for an address.
+const IPV6: &str = concat!(
+    r"\b(?:",
+    "(?:",
+    h16!(),
+    ":){7}",
+    h16!(),
+    "|",
+    h16!(),
+    ":(?::",
+    h16!(),
+    "){1,6}",
+    "|(?:",
+    h16!(),
+    ":){1,2}(?::",
+    h16!(),
+    "){1,5}",
+    "|(?:",
+    h16!(),
+    ":){1,3}(?::",
+    h16!(),
+    "){1,4}",
+    "|(?:",
+    h16!(),
+    ":){1,4}(?::",
+    h16!(),
+    "){1,3}",
+    "|(?:",
+    h16!(),
+    ":){1,5}(?::",
+    h16!(),
+    "){1,2}",
+    "|(?:",
+    h16!(),
+    ":){1,6}:",
+    h16!(),
+    "|(?:",
+    h16!(),
+    ":){1,7}:",
+    r")|\B::",
+    h16!(),
+    "(?::",
+    h16!(),
+    "){0,6}",
+);
+
+const UUID: &str =
+    r"\b[0-9A-Fa-f]{8}-[0-9A-Fa-f]{4}-[0-9A-Fa-f]{4}-[0-9A-Fa-f]{4}-[0-9A-Fa-f]{12}\b";
+
+// Punctuation ending a sentence or closing parentheses around a URL is not
+// taken as part of it.
+const URL: &str = concat!(
+    r"\b(?:https?|ftp)://",
+    r"(?:[A-Za-z0-9._~%!$&'*+,;=-]+@)?",
+    r"[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*(?::[0-9]{1,5})?",
+    r#"(?:[/?#](?:[^\s<>"']*[^\s<>"'.,;:!?)\]])?)?"#,
+);
+
+const ISO_DATE: &str = concat!(
+    r"\b[0-9]{4}-(?:0[1-9]|1[0-2])-(?:0[1-9]|[12][0-9]|3[01])",
+    r"(?:T(?:[01][0-9]|2[0-3]):[0-5][0-9](?::[0-5][0-9](?:\.[0-9]+)?)?",
+    r"(?:Z|[+-](?:[01][0-9]|2[0-3]):?[0-5][0-9])?)?\b",
+);
+
+const MAC_ADDRESS: &str = concat!(
+    r"\b[0-9A-Fa-f]{2}(?::[0-9A-Fa-f]{2}){5}\b",
+    r"|\b[0-9A-Fa-f]{2}(?:-[0-9A-Fa-f]{2}){5}\b",
+);
+
+/// Returns the regular expression of `preset`.
+pub fn regex(preset: Preset) -> &'static str {
+    match preset {
+        Preset::Email => EMAIL,
+        Preset::Ipv4 => IPV4,
+        Preset::Ipv6 => IPV6,
+        Preset::Uuid => UUID,
+        Preset::Url => URL,
+        Preset::IsoDate => ISO_DATE,
+        Preset::MacAddress => MAC_ADDRESS,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Regex;
+
+    /// Returns the matches of `preset` in `text`.
+    fn matches(preset: Preset, text: &str) -> Vec<String> {
+        Regex::new(regex(preset))
+            .unwrap()
+            .find_iter(text)
+            .map(|m| m.as_str().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_email() {
+        assert_eq!(
+            matches(
+                Preset::Email,
+                "mail a.b+c@mail.example.org, not x@y or @z.com"
+            ),
+            vec!["a.b+c@mail.example.org"]
+        );
+    }
+
+    #[test]
+    fn test_ipv4() {
+        assert_eq!(
+            matches(Preset::Ipv4, "10.0.0.1 255.255.255.255 256.1.1.1x 1.2.3"),
+            vec!["10.0.0.1", "255.255.255.255"]
+        );
+    }
+
+    #[test]
+    fn test_ipv6() {
+        assert_eq!(
+            matches(
+                Preset::Ipv6,
+                "2001:db8:0:0:0:0:2:1 2001:db8::1 fe80::1:2 (::1) std::io 12:30:45"
+            ),
+            vec!["2001:db8:0:0:0:0:2:1", "2001:db8::1", "fe80::1:2", "::1"]
+        );
+    }
+
+    #[test]
+    fn test_uuid() {
+        assert_eq!(
+            matches(
+                Preset::Uuid,
+                "id=123e4567-e89b-12d3-a456-426614174000 123e4567-e89b-12d3-a456"
+            ),
+            vec!["123e4567-e89b-12d3-a456-426614174000"]
+        );
+    }
+
+    #[test]
+    fn test_url() {
+        assert_eq!(
+            matches(
+                Preset::Url,
+                "See https://example.com/a?b=c. (http://host:8080/x) ftp://f.org/"
+            ),
+            vec![
+                "https://example.com/a?b=c",
+                "http://host:8080/x",
+                "ftp://f.org/"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_iso_date() {
+        assert_eq!(
+            matches(
+                Preset::IsoDate,
+                "2024-02-29 2024-13-01 2024-01-02T03:04:05.6+01:00 20240102"
+            ),
+            vec!["2024-02-29", "2024-01-02T03:04:05.6+01:00"]
+        );
+    }
+
+    #[test]
+    fn test_mac_address() {
+        assert_eq!(
+            matches(
+                Preset::MacAddress,
+                "00:1A:2b:3c:4D:5e 00-1a-2b-3c-4d-5e 00:1a-2b:3c:4d:5e"
+            ),
+            vec!["00:1A:2b:3c:4D:5e", "00-1a-2b-3c-4d-5e"]
+        );
+    }
+}
@@ -3,9 +3,136 @@
 //! This module centralizes all display logic, including printing search
 //! information and formatted output lines, ensuring consistent presentation.
 
-use crate::config::Config;
+use crate::config::{Config, PathStyle};
+use crate::messages::{MessageId, count_message, message};
+use crate::paths::{absolute_path, relative_path, short_path, tilde_path};
+use crate::remote::is_url;
 use colored::*;
+use std::borrow::Cow;
 use std::fmt::Write;
+use std::ops::Range;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Whether line numbers are followed by a bare `:`, for `path:line:text`
+/// output that is easy to parse.
+static PLAIN_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// Whether file names in front of output lines are followed by a NUL byte
+/// instead of a `:`, for `--null`.
+static NUL_AFTER_FILE_NAME: AtomicBool = AtomicBool::new(false);
+
+/// How file paths are shown.
+static PATH_DISPLAY: Mutex<PathDisplay> = Mutex::new(PathDisplay {
+    base: PathBase::AsGiven,
+    style: PathStyle::Full,
+});
+
+/// How file paths are shown: where they start from, and how they are
+/// abbreviated.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathDisplay {
+    /// Where the paths start from.
+    pub base: PathBase,
+    /// How the paths are abbreviated.
+    pub style: PathStyle,
+}
+
+/// Where shown file paths start from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PathBase {
+    /// As given on the command line or found in directories.
+    AsGiven,
+    /// As absolute paths, for `--absolute-path`.
+    Absolute,
+    /// Relative to a directory, for `--relative-to`.
+    RelativeTo(String),
+}
+
+/// Whether a file heading has been printed yet, so that later headings can
+/// be separated from the previous file's lines.
+static HEADING_PRINTED: AtomicBool = AtomicBool::new(false);
+
+/// Whether errors about unreadable files are left out, for `--no-messages`.
+static NO_MESSAGES: AtomicBool = AtomicBool::new(false);
+
+/// Whether a file could not be searched, which makes the exit status 2.
+static FILE_ERROR_REPORTED: AtomicBool = AtomicBool::new(false);
+
+/// Switches between the padded line-number prefix meant for reading and the
+/// plain `line:` prefix meant for other programs.
+///
+/// # Arguments
+/// * `plain` - Whether to use the plain prefix.
+pub fn set_plain_output(plain: bool) {
+    PLAIN_OUTPUT.store(plain, Ordering::Relaxed);
+}
+
+/// Switches the separator after file names in front of output lines between
+/// `:` and a NUL byte.
+///
+/// # Arguments
+/// * `nul` - Whether to use a NUL byte.
+pub fn set_nul_after_file_name(nul: bool) {
+    NUL_AFTER_FILE_NAME.store(nul, Ordering::Relaxed);
+}
+
+/// Switches off the errors about unreadable files, for `--no-messages`.
+///
+/// # Arguments
+/// * `no_messages` - Whether to leave the errors out.
+pub fn set_no_messages(no_messages: bool) {
+    NO_MESSAGES.store(no_messages, Ordering::Relaxed);
+}
+
+/// Returns whether a file could not be searched, so that the search exits
+/// with status 2 once it is done.
+pub fn file_error_reported() -> bool {
+    FILE_ERROR_REPORTED.load(Ordering::Relaxed)
+}
+
+/// Forgets the file headings printed so far, so that the first heading of
+/// a new search is not separated from the output before it.
+pub fn reset_headings() {
+    HEADING_PRINTED.store(false, Ordering::Relaxed);
+}
+
+/// Sets how file paths are shown in the output and in reports.
+///
+/// # Arguments
+/// * `display` - The way of showing paths.
+pub fn set_path_display(display: PathDisplay) {
+    *PATH_DISPLAY.lock().unwrap() = display;
+}
+
+/// Returns `path` the way it is shown to the user. URLs are always shown as
+/// they are.
+pub fn display_path(path: &str) -> Cow<'_, str> {
+    if is_url(path) {
+        return Cow::Borrowed(path);
+    }
+    let display = PATH_DISPLAY.lock().unwrap();
+    let path = match &display.base {
+        PathBase::AsGiven => Cow::Borrowed(path),
+        PathBase::Absolute => Cow::Owned(absolute_path(path)),
+        PathBase::RelativeTo(base) => Cow::Owned(relative_path(path, base)),
+    };
+    match display.style {
+        PathStyle::Full => path,
+        PathStyle::Short => Cow::Owned(short_path(&path)),
+        PathStyle::Tilde => Cow::Owned(tilde_path(&path)),
+    }
+}
+
+/// Returns the text printed between a line number and the line itself.
+fn line_number_separator() -> &'static str {
+    if PLAIN_OUTPUT.load(Ordering::Relaxed) {
+        ":"
+    } else {
+        ":  "
+    }
+}
 
 /// Prints the initial information about the search operation to the console.
 ///
@@ -19,86 +146,156 @@ use std::fmt::Write;
 pub fn print_search_info(config: &Config, before_context_num: usize, after_context_num: usize) {
     let mut output = String::new();
 
-    output.push_str(
-        format!(
-            "Searching for '{}' in file '{}'...",
-            config.query, config.file_path
-        )
-        .as_str(),
-    );
+    let quoted_paths = config
+        .file_paths
+        .iter()
+        .map(|path| format!("'{}'", path))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let searching_in = if config.file_paths.len() > 1 {
+        MessageId::SearchingInFiles
+    } else {
+        MessageId::SearchingInFile
+    };
+
+    output.push_str(&message(searching_in, &[&config.query, &quoted_paths]));
 
     if config.ignore_case {
-        output.push_str("\n(Case-insensitive search)");
+        output.push('\n');
+        output.push_str(&message(MessageId::CaseInsensitive, &[]));
     }
     if config.line_number {
-        output.push_str("\n(Line numbers enabled)");
+        output.push('\n');
+        output.push_str(&message(MessageId::LineNumbersEnabled, &[]));
     }
     if before_context_num > 0 {
-        output.push_str(format!("\n(Context before: {} lines)", before_context_num).as_str());
+        output.push('\n');
+        output.push_str(&message(MessageId::ContextBefore, &[&before_context_num]));
     }
     if after_context_num > 0 {
-        output.push_str(format!("\n(Context after: {} lines)", after_context_num).as_str());
+        output.push('\n');
+        output.push_str(&message(MessageId::ContextAfter, &[&after_context_num]));
+    }
+    if let Some(max_total) = config.max_total {
+        output.push('\n');
+        output.push_str(&message(MessageId::StoppingAfterTotal, &[&max_total]));
     }
 
     println!("{}", output.bold().yellow());
 }
 
-/// Prints a single line of content, optionally prefixed with its line number.
+/// Writes the prefix of an output line: its file name, line number and byte
+/// offset, each only if requested.
 ///
-/// This function handles the formatting of individual output lines based on
-/// the `line_number` configuration.
+/// # Arguments
+/// * `output` - The string to write the prefix to.
+/// * `file_name` - The name of the file the line belongs to, or `None` when
+///   only a single file is being searched.
+/// * `line_num` - The number of the line.
+/// * `with_line_number` - Whether the line number should be included.
+/// * `byte_offset` - The byte offset to include, or `None` if offsets are
+///   not requested.
+fn write_prefix(
+    output: &mut String,
+    file_name: Option<&str>,
+    line_num: usize,
+    with_line_number: bool,
+    byte_offset: Option<u64>,
+) {
+    if let Some(name) = file_name {
+        let separator = if NUL_AFTER_FILE_NAME.load(Ordering::Relaxed) {
+            '\0'
+        } else {
+            ':'
+        };
+        write!(output, "{}{}", display_path(name).magenta(), separator).unwrap();
+    }
+    if with_line_number {
+        write!(
+            output,
+            "{}{}",
+            line_num.to_string().blue(),
+            line_number_separator()
+        )
+        .unwrap();
+    }
+    if let Some(offset) = byte_offset {
+        write!(output, "{}:", offset.to_string().blue()).unwrap();
+    }
+}
+
+/// Prints a context line, optionally prefixed with its file name, line
+/// number and byte offset.
+///
+/// Context lines are printed dimmed so that the matching lines between them
+/// stand out, even in large context blocks.
 ///
 /// # Arguments
+/// * `file_name` - The name of the file the line belongs to, or `None` when
+///   only a single file is being searched.
 /// * `line_num` - The number of the line to print.
 /// * `line_content` - The string content of the line to print.
 /// * `with_line_number` - A boolean flag indicating whether the line number
-///                        should be included in the output.
-pub fn print_line(line_num: usize, line_content: &str, with_line_number: bool) {
-    if with_line_number {
-        print!("{}:  ", line_num.to_string().blue());
-    }
-    println!("{line_content}")
+///   should be included in the output.
+/// * `byte_offset` - The byte offset of the line in its file, or `None` if
+///   offsets are not requested.
+pub fn print_context_line(
+    file_name: Option<&str>,
+    line_num: usize,
+    line_content: &str,
+    with_line_number: bool,
+    byte_offset: Option<u64>,
+) {
+    let mut output = String::new();
+    write_prefix(
+        &mut output,
+        file_name,
+        line_num,
+        with_line_number,
+        byte_offset,
+    );
+    println!("{}{}", output, line_content.dimmed())
 }
 
 /// Prints a line of content, highlighting all occurrences of the search pattern
-/// within that line. Optionally prefixes the line with its line number.
+/// within that line. Optionally prefixes the line with its file name, line
+/// number and byte offset.
 ///
-/// This function uses the provided `regex` to find all matches in `line_content`.
+/// This function uses the provided `matcher` to find all matches in `line_content`.
 /// Each matched segment is formatted in green and bold, while the rest of the
 /// line remains unformatted. Line numbers are printed in blue.
 ///
 /// # Arguments
+/// * `file_name` - The name of the file the line belongs to, or `None` when
+///   only a single file is being searched.
 /// * `line_num` - The number of the line to print.
 /// * `line_content` - The full string content of the line.
 /// * `with_line_number` - A boolean flag indicating whether the line number
-///                        should be included in the output.
-/// * `regex` - A reference to the `regex::Regex` object used for matching.
-///             This regex is used to find the exact positions of the pattern
-///             within `line_content` for highlighting.
+///   should be included in the output.
+/// * `byte_offset` - The byte offset of the line in its file, or `None` if
+///   offsets are not requested.
+/// * `matches` - The byte ranges of the matches in `line_content`, from
+///   left to right, which are highlighted.
 pub fn print_highlighted_line(
+    file_name: Option<&str>,
     line_num: usize,
     line_content: &str,
     with_line_num: bool,
-    regex: &regex::Regex,
+    byte_offset: Option<u64>,
+    matches: impl Iterator<Item = Range<usize>>,
 ) {
     let mut output = String::new();
     let mut last_end = 0;
 
-    if with_line_num {
-        write!(&mut output, "{}:  ", line_num.to_string().blue()).unwrap();
-    }
+    write_prefix(&mut output, file_name, line_num, with_line_num, byte_offset);
 
-    // Iterate through all matches found by the regex in the line content.
-    for m in regex.find_iter(line_content) {
+    for m in matches {
         // Append the text before the current match.
-        output.push_str(&line_content[last_end..m.start()]);
+        output.push_str(&line_content[last_end..m.start]);
 
         // Append the matched text, formatted in green and bold.
-        output.push_str(&format!(
-            "{}",
-            &line_content[m.start()..m.end()].green().bold()
-        ));
-        last_end = m.end();
+        output.push_str(&format!("{}", &line_content[m.start..m.end].green().bold()));
+        last_end = m.end;
     }
 
     // Append any remaining text after the last match.
@@ -106,3 +303,220 @@ pub fn print_highlighted_line(
 
     println!("{}", output);
 }
+
+/// Prints a single match on its own line, for `--only-matching`.
+///
+/// # Arguments
+/// * `file_name` - The name of the file the match belongs to, or `None` when
+///   only a single file is being searched.
+/// * `line_num` - The number of the line containing the match.
+/// * `matched` - The matched text.
+/// * `with_line_number` - A boolean flag indicating whether the line number
+///   should be included in the output.
+/// * `byte_offset` - The byte offset of the match in its file, or `None` if
+///   offsets are not requested.
+pub fn print_match(
+    file_name: Option<&str>,
+    line_num: usize,
+    matched: &str,
+    with_line_number: bool,
+    byte_offset: Option<u64>,
+) {
+    let mut output = String::new();
+    write_prefix(
+        &mut output,
+        file_name,
+        line_num,
+        with_line_number,
+        byte_offset,
+    );
+    println!("{}{}", output, matched.green().bold());
+}
+
+/// Prints a file name as a heading above the file's matching lines.
+///
+/// Every heading after the first is preceded by an empty line, so the
+/// output of consecutive files stays apart.
+///
+/// # Arguments
+/// * `file_name` - The name of the file whose lines follow.
+pub fn print_heading(file_name: &str) {
+    if HEADING_PRINTED.swap(true, Ordering::Relaxed) {
+        println!();
+    }
+    println!("{}", display_path(file_name).magenta().bold());
+}
+
+/// Prints the number of matching lines found in a file.
+///
+/// When `file_name` is provided the count is prefixed with it as
+/// `path:count`, which is the format used when several files are searched.
+///
+/// # Arguments
+/// * `file_name` - The name of the file the count belongs to, or `None` when
+///   only a single file is being searched.
+/// * `count` - The number of matching lines found in the file.
+pub fn print_file_count(file_name: Option<&str>, count: usize) {
+    match file_name {
+        Some(name) => println!("{}:{}", display_path(name).magenta(), count),
+        None => println!("{}", count),
+    }
+}
+
+/// Prints a bar chart of match counts, one labeled bar per row.
+///
+/// The longest bar is 40 characters wide and the others are scaled to it,
+/// with every non-zero count getting at least one character.
+///
+/// # Arguments
+/// * `rows` - The labels and their match counts, in the order to print them.
+pub fn print_histogram(rows: &[(String, usize)]) {
+    const MAX_BAR_WIDTH: usize = 40;
+
+    let label_width = rows
+        .iter()
+        .map(|(label, _)| label.chars().count())
+        .max()
+        .unwrap_or(0);
+    let max_count = rows.iter().map(|(_, count)| *count).max().unwrap_or(0);
+
+    for (label, count) in rows {
+        let bar_width = (count * MAX_BAR_WIDTH).div_ceil(max_count.max(1));
+        let padding = label_width - label.chars().count();
+        println!(
+            "{}{} {} {}",
+            label.magenta(),
+            " ".repeat(padding),
+            "█".repeat(bar_width).green(),
+            count
+        );
+    }
+}
+
+/// Prints the frequency table of `--count-unique`, one distinct match per
+/// line after its count, the way `uniq -c` does.
+///
+/// # Arguments
+/// * `rows` - The distinct matches and their counts, in the order to print
+///   them.
+pub fn print_unique_counts(rows: &[(String, usize)]) {
+    for (text, count) in rows {
+        println!("{:>7} {}", count, text.green().bold());
+    }
+}
+
+/// Prints the name of a file that contains at least one match.
+///
+/// # Arguments
+/// * `file_name` - The name of the matching file.
+/// * `null_terminated` - A boolean flag indicating whether the name should be
+///   terminated by a NUL byte instead of a newline, for use with `xargs -0`.
+pub fn print_file_name(file_name: &str, null_terminated: bool) {
+    let file_name = display_path(file_name);
+    if null_terminated {
+        print!("{}\0", file_name);
+    } else {
+        println!("{}", file_name.magenta());
+    }
+}
+
+/// Prints a warning about a non-fatal problem to stderr.
+///
+/// Warnings do not stop the search; they report things like files that
+/// were only partially searched.
+///
+/// # Arguments
+/// * `message` - The warning message to print.
+pub fn print_warning(message: &str) {
+    eprintln!("{}", message.yellow());
+}
+
+/// Prints an error about a file that cannot be searched to stderr, unless
+/// `--no-messages` is given. The search goes on with the other files, but
+/// exits with status 2.
+///
+/// # Arguments
+/// * `message` - The error message to print.
+pub fn print_file_error(message: &str) {
+    FILE_ERROR_REPORTED.store(true, Ordering::Relaxed);
+    if !NO_MESSAGES.load(Ordering::Relaxed) {
+        print_warning(message);
+    }
+}
+
+/// What the first number of the end-of-run summary counts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tally {
+    /// Matching lines.
+    Lines,
+    /// Single matches, e.g. of a byte pattern.
+    Matches,
+    /// Replacements made in the files.
+    Replacements,
+    /// Nothing but the matching files, as with `--files-with-matches`.
+    Files,
+}
+
+/// Prints the end-of-run summary line to stderr.
+///
+/// The summary goes to stderr so that it never mixes with the matching lines
+/// when the output is piped into another program.
+///
+/// # Arguments
+/// * `tally` - What `count` counts.
+/// * `count` - The total number of matching lines, matches or replacements.
+/// * `file_count` - The number of files with at least one match.
+/// * `elapsed` - The time the search took.
+pub fn print_summary(tally: Tally, count: usize, file_count: usize, elapsed: Duration) {
+    let elapsed = format!("{:.2}s", elapsed.as_secs_f64());
+    let counted = match tally {
+        Tally::Lines => MessageId::MatchingLines,
+        Tally::Matches => MessageId::Matches,
+        Tally::Replacements => MessageId::Replacements,
+        Tally::Files => {
+            let files = count_message(MessageId::MatchingFiles, file_count);
+            eprintln!(
+                "{}",
+                message(MessageId::FilesSummary, &[&files, &elapsed]).bold()
+            );
+            return;
+        }
+    };
+    let summary = message(
+        MessageId::Summary,
+        &[
+            &count_message(counted, count),
+            &count_message(MessageId::Files, file_count),
+            &elapsed,
+        ],
+    );
+    eprintln!("{}", summary.bold());
+}
+
+/// Prints the number of files skipped by `--dedupe-content` to stderr, below
+/// the summary.
+///
+/// # Arguments
+/// * `count` - The number of files skipped as duplicates.
+pub fn print_duplicates_skipped(count: usize) {
+    eprintln!("{}", count_message(MessageId::DuplicatesSkipped, count));
+}
+
+/// Prints a line of a unified diff, colored by its kind: removed lines red,
+/// added lines green and hunk headers cyan.
+///
+/// # Arguments
+/// * `line` - The diff line, starting with its `-`, `+`, ` ` or `@` marker.
+pub fn print_diff_line(line: &str) {
+    if line.starts_with("---") || line.starts_with("+++") {
+        println!("{}", line.bold());
+    } else if line.starts_with('-') {
+        println!("{}", line.red());
+    } else if line.starts_with('+') {
+        println!("{}", line.green());
+    } else if line.starts_with('@') {
+        println!("{}", line.cyan());
+    } else {
+        println!("{}", line);
+    }
+}
@@ -0,0 +1,147 @@
+//! Restricts the search to ranges of lines, for `--from` and `--until`.
+//!
+//! Like a sed address range, a range starts at a line containing the
+//! `--from` pattern and runs through the next line containing the `--until`
+//! pattern. Only the lines inside a range are searched, so e.g. a query of
+//! `''` prints whole stack traces or config blocks. Without `--from`, the
+//! range starts at the first line; without `--until`, it runs to the end of
+//! the file.
+
+use crate::config::Config;
+use crate::engine::{self, Regex, RegexBuilder};
+use crate::messages::{MessageId, message};
+
+use std::error::Error;
+
+/// The compiled `--from` and `--until` patterns.
+pub struct LineRange {
+    /// The pattern starting a range.
+    from: Option<Regex>,
+    /// The pattern ending a range.
+    until: Option<Regex>,
+}
+
+/// Tracks whether the lines of a single file are inside a range.
+pub struct RangeTracker<'r> {
+    /// The patterns delimiting the ranges.
+    range: &'r LineRange,
+    /// Whether the current line is inside a range.
+    inside: bool,
+    /// The number of the line the current range started at.
+    start_line: usize,
+    /// Whether the current line ends the range, so that the next one is
+    /// outside of it.
+    closing: bool,
+}
+
+impl LineRange {
+    /// Compiles the `--from` and `--until` patterns of `config`. Like the
+    /// query, they are searched as literal text, ignoring case with `-i`.
+    ///
+    /// # Returns
+    /// The range, `None` if neither option is given, or an error if a pattern
+    /// fails to compile.
+    pub fn new(config: &Config) -> Result<Option<LineRange>, Box<dyn Error>> {
+        if config.from.is_none() && config.until.is_none() {
+            return Ok(None);
+        }
+        let compile = |pattern: &String| {
+            RegexBuilder::new(&engine::escape(pattern))
+                .case_insensitive(config.ignore_case)
+                .build()
+                .map_err(|e| message(MessageId::InvalidPattern, &[&e]))
+        };
+        Ok(Some(LineRange {
+            from: config.from.as_ref().map(compile).transpose()?,
+            until: config.until.as_ref().map(compile).transpose()?,
+        }))
+    }
+
+    /// Starts tracking the ranges of a file.
+    pub fn tracker(&self) -> RangeTracker<'_> {
+        RangeTracker {
+            range: self,
+            inside: self.from.is_none(),
+            start_line: 0,
+            closing: false,
+        }
+    }
+}
+
+impl RangeTracker<'_> {
+    /// Moves on to a line, or to the next chunk of an over-long line.
+    ///
+    /// # Arguments
+    /// * `line_num` - The number of the line.
+    /// * `text` - The text of the line or chunk.
+    /// * `new_line` - Whether `text` starts a new line rather than continuing
+    ///   the previous one.
+    ///
+    /// # Returns
+    /// Whether the line is inside a range.
+    pub fn advance(&mut self, line_num: usize, text: &str, new_line: bool) -> bool {
+        if new_line && self.closing {
+            self.inside = false;
+            self.closing = false;
+        }
+        if !self.inside {
+            if self
+                .range
+                .from
+                .as_ref()
+                .is_some_and(|from| from.is_match(text))
+            {
+                self.inside = true;
+                self.start_line = line_num;
+            }
+        } else if line_num != self.start_line
+            && self
+                .range
+                .until
+                .as_ref()
+                .is_some_and(|until| until.is_match(text))
+        {
+            // The line ending the range is still part of it.
+            self.closing = true;
+        }
+        self.inside
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    /// Returns the numbers of the lines of `text` inside a range.
+    fn inside_lines(args: &[&str], text: &str) -> Vec<usize> {
+        let config = Config::parse_from(args);
+        let range = LineRange::new(&config).unwrap().unwrap();
+        let mut tracker = range.tracker();
+        text.lines()
+            .enumerate()
+            .filter(|(i, line)| tracker.advance(i + 1, line, true))
+            .map(|(i, _)| i + 1)
+            .collect()
+    }
+
+    #[test]
+    fn test_ranges() {
+        let text = "a\nBEGIN\nb\nEND\nc\nbegin end\nd\nend\n";
+        let args = ["grep-rust", "--from", "BEGIN", "--until", "END", "", "f"];
+        assert_eq!(inside_lines(&args, text), vec![2, 3, 4]);
+        let args = [
+            "grep-rust",
+            "-i",
+            "--from",
+            "begin",
+            "--until",
+            "end",
+            "",
+            "f",
+        ];
+        assert_eq!(inside_lines(&args, text), vec![2, 3, 4, 6, 7, 8]);
+        let args = ["grep-rust", "--until", "b", "", "f"];
+        assert_eq!(inside_lines(&args, text), vec![1, 2, 3]);
+    }
+}
@@ -0,0 +1,39 @@
+//! Gives the kernel readahead hints for opened files, for `--io-backend`.
+//!
+//! When many small files are searched, much of the time goes into waiting for
+//! each of them to be read in. With the `fadvise` backend, every file is
+//! announced with `posix_fadvise` as soon as it is opened: it will be read
+//! sequentially and in full, so the kernel starts reading all of it in the
+//! background and uses a larger readahead window. The hints are only given on
+//! Linux; elsewhere the backend reads files like the default one.
+
+use std::fs::File;
+
+/// Tells the kernel that `file` is about to be read sequentially and in full.
+///
+/// The hints are advisory, so failures are ignored.
+#[cfg(target_os = "linux")]
+pub fn advise_sequential(file: &File) {
+    use std::ffi::c_int;
+    use std::os::fd::AsRawFd;
+
+    const POSIX_FADV_SEQUENTIAL: c_int = 2;
+    const POSIX_FADV_WILLNEED: c_int = 3;
+
+    unsafe extern "C" {
+        fn posix_fadvise(fd: c_int, offset: i64, len: i64, advice: c_int) -> c_int;
+    }
+
+    // SAFETY: The descriptor belongs to `file`, which outlives the calls, and
+    // a length of 0 covers the whole file.
+    unsafe {
+        posix_fadvise(file.as_raw_fd(), 0, 0, POSIX_FADV_SEQUENTIAL);
+        posix_fadvise(file.as_raw_fd(), 0, 0, POSIX_FADV_WILLNEED);
+    }
+}
+
+/// Tells the kernel that `file` is about to be read sequentially and in full.
+///
+/// Readahead hints are not given on this platform, so this does nothing.
+#[cfg(not(target_os = "linux"))]
+pub fn advise_sequential(_file: &File) {}
@@ -0,0 +1,64 @@
+//! Streams remote inputs given as URLs.
+//!
+//! File arguments starting with `http://` or `https://` are downloaded with
+//! `curl`, and `s3://` URLs with the AWS CLI. The response body is streamed
+//! straight into the search, so nothing is written to disk.
+
+use crate::preprocessor::PreprocessorOutput;
+
+use std::{io, process::Command};
+
+/// Returns whether `path` is a URL rather than a local file.
+pub fn is_url(path: &str) -> bool {
+    ["http://", "https://", "s3://"]
+        .iter()
+        .any(|scheme| path.starts_with(scheme))
+}
+
+/// Starts downloading `url`.
+///
+/// A failed download, such as one with an HTTP error status, makes the
+/// download tool exit with an error, which `PreprocessorOutput::finish`
+/// returns with the tool's error message, so the URL is reported like a file
+/// that cannot be read.
+///
+/// # Returns
+/// A `PreprocessorOutput` to read the body from, or an error if the download
+/// tool could not be started.
+pub fn open_url(url: &str) -> io::Result<PreprocessorOutput> {
+    let mut command = if url.starts_with("s3://") {
+        let mut command = Command::new("aws");
+        command.args(["s3", "cp", url, "-"]);
+        command
+    } else {
+        let mut command = Command::new("curl");
+        command.args(["--fail", "--silent", "--show-error", "--location", url]);
+        command
+    };
+    PreprocessorOutput::spawn(&mut command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_is_url() {
+        assert!(is_url("https://example.com/app.log"));
+        assert!(is_url("s3://bucket/app.log"));
+        assert!(!is_url("logs/app.log"));
+        assert!(!is_url("https.log"));
+    }
+
+    #[test]
+    #[ignore = "needs curl on PATH; run with --ignored"]
+    fn test_failed_download_is_an_error() {
+        // Nothing listens on port 1, so curl fails to connect.
+        let mut output = open_url("http://127.0.0.1:1/app.log").expect("curl is not on PATH");
+        let mut body = Vec::new();
+        output.reader.read_to_end(&mut body).unwrap();
+        assert!(body.is_empty());
+        assert!(output.finish().is_err());
+    }
+}
@@ -0,0 +1,137 @@
+//! Runs the interactive `repl` subcommand.
+//!
+//! `grep-rust repl FILE...` reads the files into memory once and then
+//! searches them for each pattern entered at a prompt. Refining a pattern
+//! against a large log then costs one scan of memory instead of another read
+//! of the file.
+//!
+//! Only the options that decide which lines match and how they are shown
+//! apply to the patterns, such as `-i`, `-w`, `-v` or `-n`, as well as the
+//! options choosing the files; all others are rejected when `repl` is
+//! started. Files that cannot be read or are not valid UTF-8 are reported
+//! and skipped, as in a search.
+
+use crate::config::{Config, DeviceAction, DirectoryAction};
+use crate::interrupt::interrupted;
+use crate::matcher::Matcher;
+use crate::messages::{MessageId, count_message, message};
+use crate::my_lib::is_special_file;
+use crate::paths::fs_path;
+use crate::printer::{
+    Tally, print_file_error, print_highlighted_line, print_summary, print_warning, set_plain_output,
+};
+use crate::walker::expand_paths;
+
+use std::{
+    error::Error,
+    fs,
+    io::{self, BufRead, IsTerminal, Write},
+    path::Path,
+    time::Instant,
+};
+
+/// Loads the files and answers the patterns entered at the prompt until an
+/// empty line or the end of the input.
+///
+/// # Arguments
+/// * `config` - The search options applied to every pattern.
+/// * `file_paths` - The paths of the files to load.
+///
+/// # Returns
+/// `Ok(true)` once the session ends, or an error if the prompt cannot be
+/// read or written.
+pub fn run_repl(mut config: Config, file_paths: &[String]) -> Result<bool, Box<dyn Error>> {
+    if !config.no_smart_defaults {
+        config.line_number = true;
+        set_plain_output(!io::stdout().is_terminal());
+    }
+
+    let mut files = Vec::new();
+    for path in expand_paths(
+        file_paths,
+        config.directories,
+        !config.no_ext_filter,
+        config.one_file_system,
+    ) {
+        if config.devices == DeviceAction::Skip && is_special_file(&path) {
+            continue;
+        }
+        let text = fs::read(fs_path(Path::new(&path))).and_then(|bytes| {
+            String::from_utf8(bytes).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "stream did not contain valid UTF-8",
+                )
+            })
+        });
+        match text {
+            Ok(text) => files.push((path, text)),
+            Err(e) => print_file_error(&message(MessageId::FileError, &[&path, &e])),
+        }
+    }
+    let with_file_name = files.len() > 1 || config.directories == DirectoryAction::Recurse;
+
+    let prompt = io::stdin().is_terminal();
+    if prompt {
+        eprintln!("{}", count_message(MessageId::ReplReady, files.len()));
+    }
+
+    let mut input = io::stdin().lock();
+    let mut pattern = String::new();
+    loop {
+        if prompt {
+            print!("> ");
+            io::stdout().flush()?;
+        }
+        pattern.clear();
+        if input.read_line(&mut pattern)? == 0 || interrupted() {
+            break;
+        }
+        let pattern = pattern.trim_end_matches(['\n', '\r']);
+        if pattern.is_empty() {
+            break;
+        }
+
+        // A pattern that does not compile only costs the user a retry.
+        config.query = pattern.to_string();
+        config.patterns.clear();
+        let matcher = match Matcher::new(&config) {
+            Ok(matcher) => matcher,
+            Err(e) => {
+                print_warning(&e.to_string());
+                continue;
+            }
+        };
+
+        let start = Instant::now();
+        let mut match_count = 0;
+        let mut file_count = 0;
+        for (path, text) in &files {
+            if interrupted() {
+                break;
+            }
+            let file_name = with_file_name.then_some(path.as_str());
+            let before = match_count;
+            for (i, line) in text.lines().enumerate() {
+                if matcher.find_iter(line).next().is_some() != config.invert_match {
+                    match_count += 1;
+                    print_highlighted_line(
+                        file_name,
+                        i + 1,
+                        line,
+                        config.line_number,
+                        None,
+                        matcher.find_iter(line),
+                    );
+                }
+            }
+            if match_count > before {
+                file_count += 1;
+            }
+        }
+        if !config.no_summary {
+            print_summary(Tally::Lines, match_count, file_count, start.elapsed());
+        }
+    }
+    Ok(true)
+}
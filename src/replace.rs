@@ -0,0 +1,528 @@
+//! Replaces the matches in the files themselves, for `--replace`.
+//!
+//! Every match of the query is replaced by the replacement text and the
+//! file is written back. The new content is written to a temporary file
+//! next to it first, which then takes its place, so that a crash or a full
+//! disk cannot leave the file truncated. FIFOs, sockets and devices are
+//! skipped unless `-D read` is given, as in a search. Line endings are kept as they are, and
+//! files that are not valid UTF-8 are left alone. With `--preview`, nothing
+//! is written; the changes are printed as a unified diff instead, to be
+//! reviewed first. With `--confirm`, each change is shown the same way and
+//! only made if the user agrees to it.
+//!
+//! With `--backup SUFFIX`, the original of every changed file is kept next
+//! to it with `SUFFIX` appended to its name, and `--undo` puts the originals
+//! back.
+//!
+//! With `--preserve-case`, the replacement takes on the case of each match
+//! it replaces: replacing `color` with `colour` turns `Color` into `Colour`
+//! and `COLOR` into `COLOUR`. Matches of mixed case, such as `coLor`, get
+//! the replacement as it was given.
+
+use crate::config::{Config, DeviceAction};
+use crate::interrupt::interrupted;
+use crate::matcher::Matcher;
+use crate::messages::{MessageId, count_message, message};
+use crate::my_lib::is_special_file;
+use crate::paths::fs_path;
+use crate::printer::{Tally, print_diff_line, print_file_error, print_summary, print_warning};
+
+use std::{
+    borrow::Cow,
+    error::Error,
+    fs,
+    io::{self, BufRead, Write},
+    path::Path,
+    time::Instant,
+};
+
+/// The number of unchanged lines shown around each change in a preview.
+const DIFF_CONTEXT: usize = 3;
+
+/// A line changed by the replacement.
+#[derive(Debug, PartialEq, Eq)]
+pub struct LineEdit {
+    /// The index of the line in its file, starting at 0.
+    pub index: usize,
+    /// The line before the replacement, without its line ending.
+    pub old: String,
+    /// The line after the replacement, without its line ending.
+    pub new: String,
+    /// The number of matches replaced in the line.
+    pub replacements: usize,
+}
+
+/// An answer to the question `--confirm` asks for each change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Answer {
+    /// Make this change.
+    Yes,
+    /// Skip this change.
+    No,
+    /// Make this change and all the remaining ones.
+    All,
+    /// Skip this change and all the remaining ones.
+    Quit,
+}
+
+/// Replaces the matches in the files, or previews the replacements.
+///
+/// # Arguments
+/// * `file_paths` - The files to edit.
+/// * `config` - The search configuration, holding the replacement.
+/// * `matcher` - The matcher finding the text to replace.
+///
+/// # Returns
+/// Whether anything was replaced, or an error if a file cannot be written.
+/// Files that cannot be read are reported and skipped.
+pub fn replace_files(
+    file_paths: &[String],
+    config: &Config,
+    matcher: &Matcher,
+) -> Result<bool, Box<dyn Error>> {
+    let start = Instant::now();
+    let replacement = config.replace.as_deref().unwrap_or_default();
+    let mut replacements = 0;
+    let mut edited_files = 0;
+    // Once answered with `a` or `q`, the remaining changes are not asked about.
+    let mut answer = if config.confirm {
+        None
+    } else {
+        Some(Answer::All)
+    };
+    let mut input = io::stdin().lock();
+
+    for path in file_paths {
+        if interrupted() || answer == Some(Answer::Quit) {
+            break;
+        }
+        // Special files are skipped and unreadable files are reported and
+        // skipped like in a search, and the other files are still edited.
+        if config.devices == DeviceAction::Skip && is_special_file(path) {
+            continue;
+        }
+        let bytes = match fs::read(fs_path(Path::new(path))) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                print_file_error(&message(MessageId::FileError, &[path, &e]));
+                continue;
+            }
+        };
+        let Ok(text) = String::from_utf8(bytes) else {
+            print_warning(&message(MessageId::NotEdited, &[path]));
+            continue;
+        };
+
+        let lines: Vec<&str> = text.split_inclusive('\n').collect();
+        let mut edits = edit_lines(&lines, matcher, replacement, config.preserve_case);
+        if answer.is_none() {
+            edits = confirm_edits(path, &lines, edits, &mut input, &mut answer)?;
+        }
+        if edits.is_empty() {
+            continue;
+        }
+        replacements += edits.iter().map(|edit| edit.replacements).sum::<usize>();
+        edited_files += 1;
+
+        if config.preview {
+            for line in unified_diff(path, &lines, &edits) {
+                print_diff_line(&line);
+            }
+        } else {
+            if let Some(suffix) = &config.backup {
+                fs::copy(
+                    fs_path(Path::new(path)),
+                    fs_path(Path::new(&backup_path(path, suffix))),
+                )?;
+            }
+            write_replacing(path, apply(&lines, &edits).as_bytes())?;
+        }
+    }
+
+    if !config.no_summary {
+        print_summary(
+            Tally::Replacements,
+            replacements,
+            edited_files,
+            start.elapsed(),
+        );
+    }
+    Ok(replacements > 0)
+}
+
+/// Restores the files from the backups kept by `--backup`, for `--undo`.
+///
+/// Each backup replaces the file it was kept for, so it is gone afterwards.
+/// Files without a backup are left as they are.
+///
+/// # Arguments
+/// * `file_paths` - The files to restore.
+/// * `config` - The search configuration, holding the backup suffix.
+///
+/// # Returns
+/// Whether any file was restored, or an error if a backup cannot be moved.
+pub fn undo_files(file_paths: &[String], config: &Config) -> Result<bool, Box<dyn Error>> {
+    let suffix = config.backup.as_deref().unwrap_or_default();
+    let mut restored = 0;
+    for path in file_paths {
+        let backup = backup_path(path, suffix);
+        if !fs_path(Path::new(&backup)).is_file() {
+            continue;
+        }
+        fs::rename(fs_path(Path::new(&backup)), fs_path(Path::new(path)))?;
+        restored += 1;
+    }
+    if !config.quiet && !config.no_summary {
+        eprintln!("{}", count_message(MessageId::Restored, restored));
+    }
+    Ok(restored > 0)
+}
+
+/// Replaces the content of the file at `path` with `content`.
+///
+/// The content is written to a temporary file in the same directory, which
+/// gets the permissions of the original and is then renamed over it. If
+/// anything fails, the original is left as it was.
+fn write_replacing(path: &str, content: &[u8]) -> io::Result<()> {
+    let temp_path = format!("{}.grep-rust-{}", path, std::process::id());
+    let result = fs::write(fs_path(Path::new(&temp_path)), content)
+        .and_then(|()| fs::metadata(fs_path(Path::new(path))))
+        .and_then(|metadata| {
+            fs::set_permissions(fs_path(Path::new(&temp_path)), metadata.permissions())
+        })
+        .and_then(|()| fs::rename(fs_path(Path::new(&temp_path)), fs_path(Path::new(path))));
+    if result.is_err() {
+        let _ = fs::remove_file(fs_path(Path::new(&temp_path)));
+    }
+    result
+}
+
+/// Returns the path of the backup of the file at `path`.
+fn backup_path(path: &str, suffix: &str) -> String {
+    format!("{}{}", path, suffix)
+}
+
+/// Shows each of the edits of a file and asks whether to make it.
+///
+/// # Arguments
+/// * `path` - The path of the file.
+/// * `lines` - The lines of the file.
+/// * `edits` - The edits to the file.
+/// * `input` - Where the answers are read from.
+/// * `answer` - Set to the answer that ends the questions, once given.
+///
+/// # Returns
+/// The edits to make, or an error if the answers cannot be read.
+fn confirm_edits(
+    path: &str,
+    lines: &[&str],
+    edits: Vec<LineEdit>,
+    input: &mut impl BufRead,
+    answer: &mut Option<Answer>,
+) -> io::Result<Vec<LineEdit>> {
+    let mut confirmed = Vec::new();
+    for (i, edit) in edits.into_iter().enumerate() {
+        match *answer {
+            Some(Answer::All) => confirmed.push(edit),
+            Some(_) => break,
+            None => {
+                // The file header is only shown before the first change.
+                let diff = unified_diff(path, lines, std::slice::from_ref(&edit));
+                for line in diff.iter().skip(if i == 0 { 0 } else { 2 }) {
+                    print_diff_line(line);
+                }
+                match ask(input)? {
+                    Answer::Yes => confirmed.push(edit),
+                    Answer::No => {}
+                    Answer::All => {
+                        confirmed.push(edit);
+                        *answer = Some(Answer::All);
+                    }
+                    Answer::Quit => *answer = Some(Answer::Quit),
+                }
+            }
+        }
+    }
+    Ok(confirmed)
+}
+
+/// Asks whether to make a change until one of `y`, `n`, `a` or `q` is
+/// answered. The end of the input, or Ctrl-C, answers `q`.
+fn ask(input: &mut impl BufRead) -> io::Result<Answer> {
+    let mut line = String::new();
+    loop {
+        print!("{}", message(MessageId::ConfirmReplace, &[]));
+        io::stdout().flush()?;
+        line.clear();
+        if input.read_line(&mut line)? == 0 || interrupted() {
+            return Ok(Answer::Quit);
+        }
+        match line.trim().to_lowercase().as_str() {
+            "y" | "yes" => return Ok(Answer::Yes),
+            "n" | "no" => return Ok(Answer::No),
+            "a" | "all" => return Ok(Answer::All),
+            "q" | "quit" => return Ok(Answer::Quit),
+            _ => {}
+        }
+    }
+}
+
+/// Returns `line` without its line ending, and the line ending.
+fn split_line_ending(line: &str) -> (&str, &str) {
+    let content = line.strip_suffix('\n').unwrap_or(line);
+    let content = content.strip_suffix('\r').unwrap_or(content);
+    line.split_at(content.len())
+}
+
+/// Replaces the matches in each of `lines`, in the case of each match if
+/// `preserve_case` is set.
+///
+/// # Returns
+/// The lines that changed, in order.
+pub fn edit_lines(
+    lines: &[&str],
+    matcher: &Matcher,
+    replacement: &str,
+    preserve_case: bool,
+) -> Vec<LineEdit> {
+    let mut edits = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        let (content, _) = split_line_ending(line);
+        let mut new = String::with_capacity(content.len());
+        let mut last_end = 0;
+        let mut replacements = 0;
+        for m in matcher.find_iter(content) {
+            // An empty match next to a replaced one is not replaced again.
+            if m.start == m.end && m.start < last_end {
+                continue;
+            }
+            new.push_str(&content[last_end..m.start]);
+            if preserve_case {
+                new.push_str(&match_case(&content[m.clone()], replacement));
+            } else {
+                new.push_str(replacement);
+            }
+            last_end = m.end;
+            replacements += 1;
+        }
+        new.push_str(&content[last_end..]);
+        if replacements > 0 && new != content {
+            edits.push(LineEdit {
+                index,
+                old: content.to_string(),
+                new,
+                replacements,
+            });
+        }
+    }
+    edits
+}
+
+/// Returns `replacement` in the case of `matched`: upper case, lower case,
+/// or capitalized. Otherwise, e.g. if `matched` has no letters or mixes
+/// cases, `replacement` is returned as it is.
+fn match_case<'a>(matched: &str, replacement: &'a str) -> Cow<'a, str> {
+    let letters: Vec<char> = matched.chars().filter(|c| c.is_alphabetic()).collect();
+    let Some((first, rest)) = letters.split_first() else {
+        return Cow::Borrowed(replacement);
+    };
+    if letters.iter().all(|c| c.is_lowercase()) {
+        Cow::Owned(replacement.to_lowercase())
+    } else if letters.len() > 1 && letters.iter().all(|c| c.is_uppercase()) {
+        Cow::Owned(replacement.to_uppercase())
+    } else if first.is_uppercase() && rest.iter().all(|c| c.is_lowercase()) {
+        let mut chars = replacement.chars();
+        Cow::Owned(match chars.next() {
+            Some(first) => first
+                .to_uppercase()
+                .chain(chars.as_str().to_lowercase().chars())
+                .collect(),
+            None => String::new(),
+        })
+    } else {
+        Cow::Borrowed(replacement)
+    }
+}
+
+/// Returns the text of the file with the edits applied, keeping the line
+/// endings of the edited lines.
+pub fn apply(lines: &[&str], edits: &[LineEdit]) -> String {
+    let mut text = String::new();
+    let mut edits = edits.iter().peekable();
+    for (index, line) in lines.iter().enumerate() {
+        match edits.next_if(|edit| edit.index == index) {
+            Some(edit) => {
+                text.push_str(&edit.new);
+                text.push_str(split_line_ending(line).1);
+            }
+            None => text.push_str(line),
+        }
+    }
+    text
+}
+
+/// Returns the edits as a unified diff of the file at `path`, with
+/// `DIFF_CONTEXT` unchanged lines around each change.
+pub fn unified_diff(path: &str, lines: &[&str], edits: &[LineEdit]) -> Vec<String> {
+    let mut diff = vec![format!("--- a/{}", path), format!("+++ b/{}", path)];
+
+    // Edits close enough for their context to touch share a hunk.
+    let mut hunks: Vec<&[LineEdit]> = Vec::new();
+    let mut first = 0;
+    for i in 1..=edits.len() {
+        if i == edits.len() || edits[i].index - edits[i - 1].index > 2 * DIFF_CONTEXT {
+            hunks.push(&edits[first..i]);
+            first = i;
+        }
+    }
+
+    // A replacement containing newlines adds lines, which shifts the line
+    // numbers of the new file in later hunks.
+    let mut added_lines = 0isize;
+    for hunk in hunks {
+        let start = hunk[0].index.saturating_sub(DIFF_CONTEXT);
+        let end = (hunk[hunk.len() - 1].index + DIFF_CONTEXT + 1).min(lines.len());
+        let mut body = Vec::new();
+        let mut new_len = 0;
+        let mut edits = hunk.iter().peekable();
+        for (index, line) in lines.iter().enumerate().take(end).skip(start) {
+            match edits.next_if(|edit| edit.index == index) {
+                Some(edit) => {
+                    body.push(format!("-{}", edit.old));
+                    for new_line in edit.new.split('\n') {
+                        body.push(format!("+{}", new_line));
+                        new_len += 1;
+                    }
+                }
+                None => {
+                    body.push(format!(" {}", split_line_ending(line).0));
+                    new_len += 1;
+                }
+            }
+        }
+        let old_len = end - start;
+        diff.push(format!(
+            "@@ -{},{} +{},{} @@",
+            start + 1,
+            old_len,
+            start as isize + 1 + added_lines,
+            new_len
+        ));
+        diff.extend(body);
+        added_lines += new_len as isize - old_len as isize;
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_edit_and_apply() {
+        let config = Config::parse_from(["grep-rust", "--replace", "colour", "color", "f"]);
+        let matcher = Matcher::new(&config).unwrap();
+        let lines = ["a color\r\n", "plain\n", "color, color"];
+        let edits = edit_lines(&lines, &matcher, "colour", false);
+        assert_eq!(edits.len(), 2);
+        assert_eq!(edits[1].replacements, 2);
+        assert_eq!(apply(&lines, &edits), "a colour\r\nplain\ncolour, colour");
+    }
+
+    #[test]
+    fn test_preserve_case() {
+        let config = Config::parse_from(["grep-rust", "-i", "--replace", "colour", "color", "f"]);
+        let matcher = Matcher::new(&config).unwrap();
+        let lines = ["color Color COLOR coLOR\n"];
+        let edits = edit_lines(&lines, &matcher, "colour", true);
+        assert_eq!(apply(&lines, &edits), "colour Colour COLOUR colour\n");
+        assert_eq!(match_case("I", "we"), "We");
+        assert_eq!(match_case("42", "Answer"), "Answer");
+    }
+
+    #[test]
+    fn test_confirm_edits() {
+        let config = Config::parse_from(["grep-rust", "--replace", "y", "x", "f"]);
+        let matcher = Matcher::new(&config).unwrap();
+        let lines = ["x\n", "x\n", "x\n", "x\n"];
+        let edits = edit_lines(&lines, &matcher, "y", false);
+
+        // Unknown answers are asked again.
+        let mut input = "n\nmaybe\ny\na\n".as_bytes();
+        let mut answer = None;
+        let confirmed = confirm_edits("f", &lines, edits, &mut input, &mut answer).unwrap();
+        let indices: Vec<usize> = confirmed.iter().map(|edit| edit.index).collect();
+        assert_eq!(indices, vec![1, 2, 3]);
+        assert_eq!(answer, Some(Answer::All));
+
+        let edits = edit_lines(&lines, &matcher, "y", false);
+        let mut input = "y\nq\n".as_bytes();
+        let mut answer = None;
+        let confirmed = confirm_edits("f", &lines, edits, &mut input, &mut answer).unwrap();
+        assert_eq!(confirmed.len(), 1);
+        assert_eq!(answer, Some(Answer::Quit));
+    }
+
+    #[test]
+    fn test_backup_and_undo() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        let path = path.to_str().unwrap().to_string();
+        let paths = [path.clone()];
+        fs::write(&path, "a color\n").unwrap();
+
+        let args = [
+            "grep-rust",
+            "--replace",
+            "colour",
+            "--backup",
+            ".bak",
+            "color",
+        ];
+        let config = Config::parse_from(args.iter().copied().chain([path.as_str()]));
+        let matcher = Matcher::new(&config).unwrap();
+        assert!(replace_files(&paths, &config, &matcher).unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a colour\n");
+        assert_eq!(
+            fs::read_to_string(format!("{}.bak", path)).unwrap(),
+            "a color\n"
+        );
+
+        let config = Config::parse_from(["grep-rust", "--undo", "--backup", ".bak", &path])
+            .resolve_patterns()
+            .unwrap();
+        assert!(undo_files(&paths, &config).unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a color\n");
+        assert!(!Path::new(&format!("{}.bak", path)).exists());
+        assert!(!undo_files(&paths, &config).unwrap());
+    }
+
+    #[test]
+    fn test_unified_diff() {
+        let config = Config::parse_from(["grep-rust", "--replace", "y", "x", "f"]);
+        let matcher = Matcher::new(&config).unwrap();
+        let text = "x\n1\n2\n3\n4\n5\n6\n7\n8\nx\n";
+        let lines: Vec<&str> = text.split_inclusive('\n').collect();
+        let edits = edit_lines(&lines, &matcher, "y", false);
+        assert_eq!(
+            unified_diff("f", &lines, &edits),
+            vec![
+                "--- a/f",
+                "+++ b/f",
+                "@@ -1,4 +1,4 @@",
+                "-x",
+                "+y",
+                " 1",
+                " 2",
+                " 3",
+                "@@ -7,4 +7,4 @@",
+                " 6",
+                " 7",
+                " 8",
+                "-x",
+                "+y",
+            ]
+        );
+    }
+}
@@ -0,0 +1,443 @@
+//! Writes the matches as machine-readable reports, either for CI systems as
+//! a GitLab Code Quality report or as JUnit XML, or for other tools as JSON
+//! lines.
+//!
+//! With `--format`, the matching lines are not printed as text. They are
+//! collected during the search and written as a single report once every
+//! file has been searched.
+
+use crate::printer::display_path;
+
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    io::{self, Write},
+};
+
+/// A matching line to include in a report.
+pub struct ReportEntry<'a> {
+    /// The path of the file containing the line.
+    pub path: &'a str,
+    /// The number of the line in its file.
+    pub line_num: usize,
+    /// The matching line.
+    pub text: &'a str,
+    /// The byte ranges of the matches in `text`.
+    pub spans: Vec<(usize, usize)>,
+}
+
+/// Writes a GitLab Code Quality report, a JSON array with one issue per
+/// matching line.
+///
+/// The report is the same in every locale, and the fingerprint of an issue
+/// does not depend on its line number, so that GitLab does not report known
+/// issues as new ones once lines above them are added or removed.
+///
+/// # Arguments
+/// * `out` - The writer to write the report to.
+/// * `entries` - The matching lines.
+pub fn write_codequality(out: &mut impl Write, entries: &[ReportEntry]) -> io::Result<()> {
+    let mut json = String::from("[");
+    for (i, (entry, occurrence)) in entries.iter().zip(occurrences(entries)).enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        write!(
+            json,
+            "\n  {{\"description\": {}, \"check_name\": \"grep-rust\", \
+             \"fingerprint\": \"{:016x}\", \"severity\": \"minor\", \
+             \"location\": {{\"path\": {}, \"lines\": {{\"begin\": {}}}}}}}",
+            json_string(&description(entry)),
+            fingerprint(entry, occurrence),
+            json_string(&display_path(entry.path)),
+            entry.line_num
+        )
+        .unwrap();
+    }
+    json.push_str("\n]");
+    writeln!(out, "{}", json)
+}
+
+/// Writes a JUnit XML report, with one test suite per file and one failing
+/// test case per matching line.
+///
+/// A test case is named after the trimmed text of its line, numbered if the
+/// same text comes again in the file, rather than after its line number. CI
+/// systems track tests by name, so a violation keeps its history while
+/// lines above it change. The report is the same in every locale.
+///
+/// # Arguments
+/// * `out` - The writer to write the report to.
+/// * `entries` - The matching lines.
+pub fn write_junit(out: &mut impl Write, entries: &[ReportEntry]) -> io::Result<()> {
+    let suites = group_by_path(entries);
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    writeln!(
+        xml,
+        "<testsuites name=\"grep-rust\" tests=\"{0}\" failures=\"{0}\">",
+        entries.len()
+    )
+    .unwrap();
+    for (path, cases) in &suites {
+        writeln!(
+            xml,
+            "  <testsuite name={1} tests=\"{0}\" failures=\"{0}\">",
+            cases.len(),
+            xml_attribute(&display_path(path))
+        )
+        .unwrap();
+        for (entry, occurrence) in cases.iter().zip(occurrences(cases.iter().copied())) {
+            let name = match occurrence {
+                0 => entry.text.trim().to_string(),
+                n => format!("{} #{}", entry.text.trim(), n + 1),
+            };
+            writeln!(
+                xml,
+                "    <testcase name={} classname={}>\n      \
+                 <failure message={}>{}:{}: {}</failure>\n    </testcase>",
+                xml_attribute(&name),
+                xml_attribute(&display_path(path)),
+                xml_attribute(&description(entry)),
+                xml_text(&display_path(path)),
+                entry.line_num,
+                xml_text(entry.text)
+            )
+            .unwrap();
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+    xml.push_str("</testsuites>");
+    writeln!(out, "{}", xml)
+}
+
+/// What is known about a file in the JSON output, so that indexers need no
+/// second pass over the files to learn it.
+#[derive(Debug, Default)]
+pub struct FileInfo {
+    /// The size of the file in bytes.
+    pub size: Option<u64>,
+    /// The modification time, in seconds since the Unix epoch.
+    pub modified: Option<u64>,
+    /// The encoding detected from the start of the file.
+    pub encoding: Option<&'static str>,
+}
+
+/// Writes JSON lines for the matches: a `begin` event per file, one `match`
+/// event per matching line with the byte and column spans of every match in
+/// it, and an `end` event per file. Other tools can then highlight or patch
+/// the matches without searching again.
+///
+/// Columns count characters and start at 1; the end of a span is exclusive.
+/// The `begin` and `end` events carry the file's metadata, and files appear
+/// in the order they were first found in.
+///
+/// # Arguments
+/// * `out` - The writer to write the lines to.
+/// * `entries` - The matching lines.
+/// * `file_info` - Returns the metadata of the file at a path.
+pub fn write_json(
+    out: &mut impl Write,
+    entries: &[ReportEntry],
+    file_info: impl Fn(&str) -> FileInfo,
+) -> io::Result<()> {
+    for (path, lines) in group_by_path(entries) {
+        let info = file_info(path);
+        let optional = |value: Option<String>| value.unwrap_or_else(|| "null".to_string());
+        let metadata = format!(
+            "\"path\": {}, \"size\": {}, \"modified\": {}, \"encoding\": {}",
+            json_string(&display_path(path)),
+            optional(info.size.map(|size| size.to_string())),
+            optional(info.modified.map(|modified| modified.to_string())),
+            optional(info.encoding.map(json_string))
+        );
+        writeln!(out, "{{\"type\": \"begin\", {}}}", metadata)?;
+        for entry in &lines {
+            writeln!(out, "{}", json_match(entry))?;
+        }
+        writeln!(
+            out,
+            "{{\"type\": \"end\", {}, \"matched_lines\": {}}}",
+            metadata,
+            lines.len()
+        )?;
+    }
+    Ok(())
+}
+
+/// Returns the `match` event of a matching line.
+fn json_match(entry: &ReportEntry) -> String {
+    let mut json = format!(
+        "{{\"type\": \"match\", \"path\": {}, \"line_number\": {}, \"text\": {}, \"matches\": [",
+        json_string(&display_path(entry.path)),
+        entry.line_num,
+        json_string(entry.text)
+    );
+    for (i, &(start, end)) in entry.spans.iter().enumerate() {
+        if i > 0 {
+            json.push_str(", ");
+        }
+        let start_column = entry.text[..start].chars().count() + 1;
+        let end_column = start_column + entry.text[start..end].chars().count();
+        write!(
+            json,
+            "{{\"match\": {}, \"start\": {}, \"end\": {}, \
+             \"start_column\": {}, \"end_column\": {}}}",
+            json_string(&entry.text[start..end]),
+            start,
+            end,
+            start_column,
+            end_column
+        )
+        .unwrap();
+    }
+    json.push_str("]}");
+    json
+}
+
+/// Groups the matching lines by file, keeping the files in the order they
+/// were first found in. With `--sort-lines`, the lines of a file need not be
+/// next to each other.
+fn group_by_path<'e, 'a>(
+    entries: &'e [ReportEntry<'a>],
+) -> Vec<(&'a str, Vec<&'e ReportEntry<'a>>)> {
+    let mut groups: Vec<(&str, Vec<&ReportEntry>)> = Vec::new();
+    for entry in entries {
+        match groups.iter_mut().find(|(path, _)| *path == entry.path) {
+            Some((_, lines)) => lines.push(entry),
+            None => groups.push((entry.path, vec![entry])),
+        }
+    }
+    groups
+}
+
+/// Returns the description of a matching line in the reports, which names
+/// the first match in it. It is in English whatever the locale, since a
+/// report is read by other programs.
+fn description(entry: &ReportEntry) -> String {
+    match entry.spans.first() {
+        Some(&(start, end)) => {
+            format!("Found '{}': {}", &entry.text[start..end], entry.text.trim())
+        }
+        None => format!("Found: {}", entry.text.trim()),
+    }
+}
+
+/// Returns for each matching line how many lines with the same trimmed text
+/// come before it in its file, to tell such lines apart without their line
+/// numbers.
+fn occurrences<'e, 'a: 'e>(entries: impl IntoIterator<Item = &'e ReportEntry<'a>>) -> Vec<usize> {
+    let mut seen: HashMap<(&str, &str), usize> = HashMap::new();
+    entries
+        .into_iter()
+        .map(|entry| {
+            let count = seen.entry((entry.path, entry.text.trim())).or_default();
+            *count += 1;
+            *count - 1
+        })
+        .collect()
+}
+
+/// Returns a fingerprint identifying a matching line across runs: its path,
+/// its trimmed text and how many lines of the same text come before it in
+/// the file. Unlike its line number, none of these change when unrelated
+/// lines are edited.
+fn fingerprint(entry: &ReportEntry, occurrence: usize) -> u64 {
+    fnv1a(
+        format!(
+            "{}\0{}\0{}",
+            display_path(entry.path),
+            entry.text.trim(),
+            occurrence
+        )
+        .as_bytes(),
+    )
+}
+
+/// Returns the 64-bit FNV-1a hash of `bytes`.
+///
+/// Unlike the standard library's hasher, it is guaranteed to stay the same
+/// between builds, so it can be stored in reports and baseline files.
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    bytes.iter().fold(OFFSET_BASIS, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    })
+}
+
+/// Returns `text` as a quoted JSON string.
+pub fn json_string(text: &str) -> String {
+    let mut json = String::with_capacity(text.len() + 2);
+    json.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if u32::from(c) < 0x20 => write!(json, "\\u{:04x}", u32::from(c)).unwrap(),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// Returns `text` escaped for use as XML character data.
+///
+/// Control characters other than tabs are not allowed in XML 1.0 at all, so
+/// they are dropped.
+fn xml_text(text: &str) -> String {
+    let mut xml = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => xml.push_str("&amp;"),
+            '<' => xml.push_str("&lt;"),
+            '>' => xml.push_str("&gt;"),
+            '"' => xml.push_str("&quot;"),
+            '\t' => xml.push(c),
+            c if u32::from(c) < 0x20 => {}
+            c => xml.push(c),
+        }
+    }
+    xml
+}
+
+/// Returns `text` as a quoted XML attribute value.
+fn xml_attribute(text: &str) -> String {
+    format!("\"{}\"", xml_text(text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_string() {
+        assert_eq!(json_string("plain"), "\"plain\"");
+        assert_eq!(
+            json_string("a \"b\"\\\n\u{1}"),
+            "\"a \\\"b\\\"\\\\\\n\\u0001\""
+        );
+    }
+
+    #[test]
+    fn test_write_codequality() {
+        let entries = [ReportEntry {
+            path: "src/main.rs",
+            line_num: 3,
+            text: "    x.unwrap();",
+            spans: Vec::new(),
+        }];
+        let mut out = Vec::new();
+        write_codequality(&mut out, &entries).unwrap();
+        let report = String::from_utf8(out).unwrap();
+        assert!(report.starts_with("[\n  {\"description\": \"Found: x.unwrap();\", "));
+        assert!(
+            report.contains("\"location\": {\"path\": \"src/main.rs\", \"lines\": {\"begin\": 3}}")
+        );
+        assert!(report.ends_with("}\n]\n"));
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_line_numbers() {
+        let entry = |line_num, text| ReportEntry {
+            path: "src/main.rs",
+            line_num,
+            text,
+            spans: vec![(6, 12)],
+        };
+        let before = [entry(3, "    x.unwrap();"), entry(9, "    x.unwrap();")];
+        let after = [entry(5, "  x.unwrap();"), entry(12, "\tx.unwrap();")];
+        let fingerprints = |entries: &[ReportEntry]| -> Vec<u64> {
+            entries
+                .iter()
+                .zip(occurrences(entries))
+                .map(|(entry, occurrence)| fingerprint(entry, occurrence))
+                .collect()
+        };
+        assert_eq!(fingerprints(&before), fingerprints(&after));
+        assert_ne!(fingerprints(&before)[0], fingerprints(&before)[1]);
+        assert_eq!(description(&before[0]), "Found 'unwrap': x.unwrap();");
+    }
+
+    #[test]
+    fn test_write_junit() {
+        let entries = [
+            ReportEntry {
+                path: "a.rs",
+                line_num: 1,
+                text: "if a < b {",
+                spans: Vec::new(),
+            },
+            ReportEntry {
+                path: "b.rs",
+                line_num: 2,
+                text: "b",
+                spans: Vec::new(),
+            },
+            ReportEntry {
+                path: "a.rs",
+                line_num: 5,
+                text: "a & b",
+                spans: Vec::new(),
+            },
+            ReportEntry {
+                path: "b.rs",
+                line_num: 7,
+                text: "  b",
+                spans: vec![(2, 3)],
+            },
+        ];
+        let mut out = Vec::new();
+        write_junit(&mut out, &entries).unwrap();
+        let report = String::from_utf8(out).unwrap();
+        assert!(report.contains("<testsuites name=\"grep-rust\" tests=\"4\" failures=\"4\">"));
+        assert!(report.contains("<testsuite name=\"a.rs\" tests=\"2\" failures=\"2\">"));
+        assert!(report.contains(
+            "<testcase name=\"if a &lt; b {\" classname=\"a.rs\">\n      \
+             <failure message=\"Found: if a &lt; b {\">a.rs:1: if a &lt; b {</failure>"
+        ));
+        assert!(report.contains(">a.rs:5: a &amp; b</failure>"));
+        assert!(report.contains(
+            "<testcase name=\"b #2\" classname=\"b.rs\">\n      \
+             <failure message=\"Found 'b': b\">b.rs:7:   b</failure>"
+        ));
+        assert!(report.ends_with("</testsuites>\n"));
+    }
+
+    #[test]
+    fn test_write_json() {
+        let entries = [ReportEntry {
+            path: "notes.txt",
+            line_num: 4,
+            text: "caf\u{e9} au cafe",
+            spans: vec![(0, 5), (9, 13)],
+        }];
+        let mut out = Vec::new();
+        write_json(&mut out, &entries, |_| FileInfo {
+            size: Some(120),
+            modified: None,
+            encoding: Some("utf-8"),
+        })
+        .unwrap();
+        let metadata =
+            "\"path\": \"notes.txt\", \"size\": 120, \"modified\": null, \"encoding\": \"utf-8\"";
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!(
+                "{{\"type\": \"begin\", {0}}}\n\
+                 {{\"type\": \"match\", \"path\": \"notes.txt\", \"line_number\": 4, \
+                 \"text\": \"caf\u{e9} au cafe\", \"matches\": [{{\"match\": \"caf\u{e9}\", \
+                 \"start\": 0, \"end\": 5, \"start_column\": 1, \"end_column\": 5}}, \
+                 {{\"match\": \"cafe\", \"start\": 9, \"end\": 13, \"start_column\": 9, \
+                 \"end_column\": 13}}]}}\n\
+                 {{\"type\": \"end\", {0}, \"matched_lines\": 1}}\n",
+                metadata
+            )
+        );
+    }
+}
@@ -0,0 +1,109 @@
+//! Restricts the search to a section of a config file, for `--section`.
+//!
+//! INI and TOML files are split into sections by `[name]` headers, which
+//! may be nested with dots (`[server.tls]`) or name array tables
+//! (`[[server]]`). YAML files are split by their top-level keys. A line-based
+//! scanner follows the headers, so that only the lines of the named section,
+//! including its nested sections, are searched.
+
+/// How a file is split into sections.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Layout {
+    /// `[name]` headers, as in INI and TOML files.
+    Headers,
+    /// Top-level `key:` lines, as in YAML files.
+    TopLevelKeys,
+}
+
+/// Tracks whether the lines of a single file are inside the named section.
+pub struct SectionTracker<'n> {
+    /// The name of the section to search.
+    name: &'n str,
+    /// How the file is split into sections.
+    layout: Layout,
+    /// Whether the current line is inside the named section.
+    inside: bool,
+}
+
+impl<'n> SectionTracker<'n> {
+    /// Creates a tracker for the section `name` of the file at `path`,
+    /// whose extension decides how the file is split into sections.
+    pub fn new(name: &'n str, path: &str) -> Self {
+        let extension = path.rsplit_once('.').map(|(_, extension)| extension);
+        let layout = match extension {
+            Some("yaml" | "yml") => Layout::TopLevelKeys,
+            _ => Layout::Headers,
+        };
+        SectionTracker {
+            name,
+            layout,
+            inside: false,
+        }
+    }
+
+    /// Moves on to the next line.
+    ///
+    /// A `[name]` header line is not part of the section it starts, while a
+    /// top-level YAML key line is, since it can hold the value itself.
+    ///
+    /// # Returns
+    /// Whether the line is inside the named section.
+    pub fn advance(&mut self, line: &str) -> bool {
+        match self.layout {
+            Layout::Headers => {
+                let trimmed = line.trim();
+                if let Some(header) = trimmed.strip_prefix('[') {
+                    let header = header.trim_start_matches('[');
+                    let header = header.split(']').next().unwrap_or(header).trim();
+                    self.inside = self.is_named_section(header);
+                    return false;
+                }
+            }
+            Layout::TopLevelKeys => {
+                let starts_key = line
+                    .chars()
+                    .next()
+                    .is_some_and(|c| !c.is_whitespace() && !matches!(c, '#' | '-'));
+                if starts_key && let Some((key, _)) = line.split_once(':') {
+                    let key = key.trim().trim_matches(['"', '\'']);
+                    self.inside = self.is_named_section(key);
+                }
+            }
+        }
+        self.inside
+    }
+
+    /// Returns whether `header` names the section, or a section nested in it.
+    fn is_named_section(&self, header: &str) -> bool {
+        header
+            .strip_prefix(self.name)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the numbers of the lines of `text` inside the section.
+    fn inside_lines(name: &str, path: &str, text: &str) -> Vec<usize> {
+        let mut tracker = SectionTracker::new(name, path);
+        text.lines()
+            .enumerate()
+            .filter(|(_, line)| tracker.advance(line))
+            .map(|(i, _)| i + 1)
+            .collect()
+    }
+
+    #[test]
+    fn test_headers() {
+        let text = "timeout = 1\n[server]\ntimeout = 5\n[server.tls]\ntimeout = 9\n[servers]\ntimeout = 2\n";
+        assert_eq!(inside_lines("server", "app.toml", text), vec![3, 5]);
+    }
+
+    #[test]
+    fn test_top_level_keys() {
+        let text = "client:\n  timeout: 1\nserver:\n  # comment\n  timeout: 5\nport: 80\n";
+        assert_eq!(inside_lines("server", "app.yaml", text), vec![3, 4, 5]);
+    }
+}
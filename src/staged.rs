@@ -0,0 +1,82 @@
+//! Reads files as staged in the git index, for `--staged`.
+//!
+//! A pre-commit hook should judge a commit by exactly what will be
+//! committed. With `--staged`, the files with staged changes are searched in
+//! their staged version, which may differ from the working tree when only
+//! part of a file was added.
+
+use crate::preprocessor::PreprocessorOutput;
+
+use std::{
+    io,
+    process::{Command, Stdio},
+};
+
+/// Lists the files with staged changes below the current directory.
+///
+/// # Arguments
+/// * `paths` - The paths to limit the list to, or none to list all files.
+///
+/// # Returns
+/// The paths relative to the current directory, or an error if `git` could
+/// not be run or failed.
+pub fn staged_paths(paths: &[String]) -> io::Result<Vec<String>> {
+    // Deleted files have no staged content to search.
+    let output = Command::new("git")
+        .args([
+            "diff",
+            "--cached",
+            "--name-only",
+            "--diff-filter=d",
+            "--relative",
+            "-z",
+            "--",
+        ])
+        .args(paths)
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    let names = String::from_utf8(output.stdout)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(names
+        .split('\0')
+        .filter(|name| !name.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+/// Starts reading the staged content of the file at `path`.
+///
+/// # Returns
+/// A `PreprocessorOutput` to read the content from, or an error if `git`
+/// could not be started. If `git show` fails, `PreprocessorOutput::finish`
+/// returns its error message, so that a missing index entry is not mistaken
+/// for an empty file.
+pub fn open_staged(path: &str) -> io::Result<PreprocessorOutput> {
+    // `:./path` names the index entry of a path relative to the current
+    // directory rather than to the root of the repository.
+    let mut command = Command::new("git");
+    command
+        .args(["show", &format!(":./{}", path)])
+        .stdin(Stdio::null());
+    PreprocessorOutput::spawn(&mut command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_failed_git_show_is_an_error() {
+        let mut output = open_staged("no/such/file.rs").unwrap();
+        let mut content = String::new();
+        output.reader.read_to_string(&mut content).unwrap();
+        assert_eq!(content, "");
+        let error = output.finish().unwrap_err();
+        assert!(error.to_string().starts_with("fatal:"), "{}", error);
+    }
+}
@@ -0,0 +1,374 @@
+//! Provides a lightweight, line-by-line lexer that finds comments and string
+//! literals in source code.
+//!
+//! It backs `--only-comments` and `--only-strings`. The lexing rules are
+//! chosen by file extension and only know about comment markers and string
+//! delimiters, which is enough to tell a `TODO` in a comment apart from one
+//! in an identifier. The scanner keeps its state between lines, so block
+//! comments and multi-line strings are tracked across line breaks.
+
+use std::path::Path;
+
+/// The kind of a region found by the scanner.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegionKind {
+    /// A line or block comment, including its markers.
+    Comment,
+    /// A string literal, including its delimiters.
+    String,
+}
+
+/// A comment or string literal within a line, as a byte range.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+    /// The byte offset where the region starts.
+    pub start: usize,
+    /// The byte offset just past the end of the region.
+    pub end: usize,
+    /// What the region contains.
+    pub kind: RegionKind,
+}
+
+/// A string literal delimiter. The same delimiter opens and closes the string.
+struct StringDelimiter {
+    /// The delimiter text, e.g. `"` or `'''`.
+    delimiter: &'static str,
+    /// Whether the string may continue past the end of a line.
+    multiline: bool,
+}
+
+/// The comment and string syntax of a family of languages.
+struct Syntax {
+    /// Markers that start a comment running to the end of the line.
+    line_comments: &'static [&'static str],
+    /// Pairs of markers that open and close a block comment.
+    block_comments: &'static [(&'static str, &'static str)],
+    /// Delimiters of string literals, longest first.
+    strings: &'static [StringDelimiter],
+}
+
+const fn single_line(delimiter: &'static str) -> StringDelimiter {
+    StringDelimiter {
+        delimiter,
+        multiline: false,
+    }
+}
+
+const fn multi_line(delimiter: &'static str) -> StringDelimiter {
+    StringDelimiter {
+        delimiter,
+        multiline: true,
+    }
+}
+
+const C_LIKE: Syntax = Syntax {
+    line_comments: &["//"],
+    block_comments: &[("/*", "*/")],
+    strings: &[single_line("\""), single_line("'")],
+};
+
+// Rust's `'` starts lifetimes as well as character literals, so only double
+// quotes are treated as string delimiters.
+const RUST: Syntax = Syntax {
+    line_comments: &["//"],
+    block_comments: &[("/*", "*/")],
+    strings: &[multi_line("\"")],
+};
+
+const GO: Syntax = Syntax {
+    line_comments: &["//"],
+    block_comments: &[("/*", "*/")],
+    strings: &[single_line("\""), single_line("'"), multi_line("`")],
+};
+
+const JAVASCRIPT: Syntax = Syntax {
+    line_comments: &["//"],
+    block_comments: &[("/*", "*/")],
+    strings: &[single_line("\""), single_line("'"), multi_line("`")],
+};
+
+const PYTHON: Syntax = Syntax {
+    line_comments: &["#"],
+    block_comments: &[],
+    strings: &[
+        multi_line("\"\"\""),
+        multi_line("'''"),
+        single_line("\""),
+        single_line("'"),
+    ],
+};
+
+const HASH_COMMENTS: Syntax = Syntax {
+    line_comments: &["#"],
+    block_comments: &[],
+    strings: &[single_line("\""), single_line("'")],
+};
+
+const SQL: Syntax = Syntax {
+    line_comments: &["--"],
+    block_comments: &[("/*", "*/")],
+    strings: &[single_line("'"), single_line("\"")],
+};
+
+const LUA: Syntax = Syntax {
+    line_comments: &["--"],
+    block_comments: &[("--[[", "]]")],
+    strings: &[single_line("\""), single_line("'")],
+};
+
+const CSS: Syntax = Syntax {
+    line_comments: &[],
+    block_comments: &[("/*", "*/")],
+    strings: &[single_line("\""), single_line("'")],
+};
+
+const MARKUP: Syntax = Syntax {
+    line_comments: &[],
+    block_comments: &[("<!--", "-->")],
+    strings: &[],
+};
+
+/// Returns the syntax used for files with the given extension.
+fn syntax_for_extension(extension: &str) -> Option<&'static Syntax> {
+    let syntax = match extension.to_ascii_lowercase().as_str() {
+        "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "hh" | "java" | "cs" | "kt" | "kts"
+        | "swift" | "scala" | "dart" => &C_LIKE,
+        "rs" => &RUST,
+        "go" => &GO,
+        "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" => &JAVASCRIPT,
+        "py" | "pyi" => &PYTHON,
+        "sh" | "bash" | "zsh" | "rb" | "pl" | "pm" | "r" | "yaml" | "yml" | "toml" | "conf"
+        | "cmake" => &HASH_COMMENTS,
+        "sql" => &SQL,
+        "lua" => &LUA,
+        "css" | "scss" | "less" => &CSS,
+        "html" | "htm" | "xml" | "svg" | "xhtml" => &MARKUP,
+        _ => return None,
+    };
+    Some(syntax)
+}
+
+/// The lexer state carried from one line to the next.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    /// Plain code.
+    Code,
+    /// Inside a comment that runs to the end of the line.
+    LineComment,
+    /// Inside a block comment closed by the given marker.
+    BlockComment(&'static str),
+    /// Inside a string closed by the given delimiter.
+    String(&'static str),
+}
+
+/// Finds comments and string literals line by line in one file.
+pub struct SyntaxScanner {
+    /// The syntax rules of the file's language.
+    syntax: &'static Syntax,
+    /// The state left over from the previous line.
+    state: State,
+}
+
+impl SyntaxScanner {
+    /// Creates a scanner for the file at `path`, choosing the lexing rules
+    /// by its extension.
+    ///
+    /// # Returns
+    /// The scanner, or `None` if the file's language is not known.
+    pub fn for_path(path: &str) -> Option<Self> {
+        let extension = Path::new(path).extension()?.to_str()?;
+        Some(SyntaxScanner {
+            syntax: syntax_for_extension(extension)?,
+            state: State::Code,
+        })
+    }
+
+    /// Scans the next line of the file, or the next chunk of a line that is
+    /// read in several pieces.
+    ///
+    /// Lines must be passed in order, since comments and strings may span
+    /// several lines. A comment or string that is still open at the end of
+    /// `text` covers the rest of it.
+    ///
+    /// # Arguments
+    /// * `text` - The line, or the part of it not seen in earlier chunks.
+    /// * `continues_line` - Whether `text` continues the previous chunk
+    ///   rather than starting a new line.
+    ///
+    /// # Returns
+    /// The comments and string literals in `text`, in order.
+    pub fn scan_line(&mut self, text: &str, continues_line: bool) -> Vec<Region> {
+        if !continues_line {
+            self.end_line();
+        }
+
+        let mut regions = Vec::new();
+        let mut region_start = 0;
+        let mut i = 0;
+
+        while i < text.len() {
+            let rest = &text[i..];
+            match self.state {
+                State::Code => {
+                    if let Some((open, close)) = self
+                        .syntax
+                        .block_comments
+                        .iter()
+                        .find(|(open, _)| rest.starts_with(open))
+                    {
+                        self.state = State::BlockComment(close);
+                        region_start = i;
+                        i += open.len();
+                    } else if self
+                        .syntax
+                        .line_comments
+                        .iter()
+                        .any(|marker| rest.starts_with(marker))
+                    {
+                        self.state = State::LineComment;
+                        region_start = i;
+                        i = text.len();
+                    } else if let Some(string) = self
+                        .syntax
+                        .strings
+                        .iter()
+                        .find(|string| rest.starts_with(string.delimiter))
+                    {
+                        self.state = State::String(string.delimiter);
+                        region_start = i;
+                        i += string.delimiter.len();
+                    } else {
+                        i += rest.chars().next().map_or(1, char::len_utf8);
+                    }
+                }
+                State::LineComment => i = text.len(),
+                State::BlockComment(close) => match rest.find(close) {
+                    Some(offset) => {
+                        i += offset + close.len();
+                        regions.push(Region {
+                            start: region_start,
+                            end: i,
+                            kind: RegionKind::Comment,
+                        });
+                        self.state = State::Code;
+                    }
+                    None => i = text.len(),
+                },
+                State::String(delimiter) => {
+                    if rest.starts_with('\\') {
+                        // Skip the escaped character, whatever it is.
+                        i += 1;
+                        i += text[i..].chars().next().map_or(0, char::len_utf8);
+                    } else if rest.starts_with(delimiter) {
+                        i += delimiter.len();
+                        regions.push(Region {
+                            start: region_start,
+                            end: i,
+                            kind: RegionKind::String,
+                        });
+                        self.state = State::Code;
+                    } else {
+                        i += rest.chars().next().map_or(1, char::len_utf8);
+                    }
+                }
+            }
+        }
+
+        let open_kind = match self.state {
+            State::Code => None,
+            State::LineComment | State::BlockComment(_) => Some(RegionKind::Comment),
+            State::String(_) => Some(RegionKind::String),
+        };
+        if let Some(kind) = open_kind {
+            regions.push(Region {
+                start: region_start,
+                end: text.len(),
+                kind,
+            });
+        }
+
+        regions
+    }
+
+    /// Updates the state for a line break: line comments and single-line
+    /// strings end with their line.
+    fn end_line(&mut self) {
+        match self.state {
+            State::LineComment => self.state = State::Code,
+            State::String(delimiter) => {
+                let multiline = self
+                    .syntax
+                    .strings
+                    .iter()
+                    .any(|string| string.delimiter == delimiter && string.multiline);
+                if !multiline {
+                    self.state = State::Code;
+                }
+            }
+            State::Code | State::BlockComment(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds<'a>(scanner: &mut SyntaxScanner, line: &'a str) -> Vec<(&'a str, RegionKind)> {
+        scanner
+            .scan_line(line, false)
+            .into_iter()
+            .map(|region| (&line[region.start..region.end], region.kind))
+            .collect()
+    }
+
+    #[test]
+    fn test_line_comments_and_strings() {
+        let mut scanner = SyntaxScanner::for_path("main.rs").unwrap();
+        assert_eq!(
+            kinds(&mut scanner, r#"let todo = "TODO \" x"; // TODO: fix"#),
+            vec![
+                (r#""TODO \" x""#, RegionKind::String),
+                ("// TODO: fix", RegionKind::Comment),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_block_comments_span_lines() {
+        let mut scanner = SyntaxScanner::for_path("lib.c").unwrap();
+        assert_eq!(
+            kinds(&mut scanner, "int x; /* start"),
+            vec![("/* start", RegionKind::Comment)]
+        );
+        assert_eq!(
+            kinds(&mut scanner, "still comment */ int y;"),
+            vec![("still comment */", RegionKind::Comment)]
+        );
+        assert_eq!(kinds(&mut scanner, "int z;"), vec![]);
+    }
+
+    #[test]
+    fn test_line_comment_continues_into_next_chunk() {
+        let mut scanner = SyntaxScanner::for_path("script.py").unwrap();
+        assert_eq!(
+            kinds(&mut scanner, "x = 1  # a long"),
+            vec![("# a long", RegionKind::Comment)]
+        );
+        assert_eq!(
+            scanner.scan_line(" comment", true),
+            vec![Region {
+                start: 0,
+                end: 8,
+                kind: RegionKind::Comment
+            }]
+        );
+        assert_eq!(kinds(&mut scanner, "y = 2"), vec![]);
+    }
+
+    #[test]
+    fn test_unknown_extension() {
+        assert!(SyntaxScanner::for_path("notes.txt").is_none());
+        assert!(SyntaxScanner::for_path("Makefile").is_none());
+    }
+}
@@ -0,0 +1,75 @@
+//! Limits how fast files are read, for `--throttle`.
+//!
+//! A recursive search over an NFS or SMB share can read as fast as the
+//! network allows and starve every other client of the storage backend. The
+//! `Throttle` reader caps the rate at which bytes are read from a file by
+//! sleeping whenever reading runs ahead of the allowed rate. Files are
+//! searched one after another, so capping each of them caps the search.
+
+use std::{
+    io::{self, Read},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// A reader that reads at most a given number of bytes per second from
+/// another reader.
+pub struct Throttle<R> {
+    /// The underlying reader.
+    inner: R,
+    /// The allowed rate, in bytes per second.
+    bytes_per_sec: u64,
+    /// When reading started.
+    start: Instant,
+    /// The number of bytes read so far.
+    bytes_read: u64,
+}
+
+impl<R: Read> Throttle<R> {
+    /// Creates a reader reading at most `bytes_per_sec` bytes per second
+    /// from `inner`.
+    pub fn new(inner: R, bytes_per_sec: u64) -> Self {
+        Throttle {
+            inner,
+            bytes_per_sec: bytes_per_sec.max(1),
+            start: Instant::now(),
+            bytes_read: 0,
+        }
+    }
+}
+
+impl<R: Read> Read for Throttle<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Reads are kept to a tenth of a second's worth of bytes, so that the
+        // pauses between them stay short and Ctrl-C is noticed quickly.
+        let max_len = (self.bytes_per_sec / 10).max(1);
+        let len = buf
+            .len()
+            .min(usize::try_from(max_len).unwrap_or(usize::MAX));
+        let read = self.inner.read(&mut buf[..len])?;
+        self.bytes_read += read as u64;
+
+        let due = Duration::from_secs_f64(self.bytes_read as f64 / self.bytes_per_sec as f64);
+        if let Some(ahead) = due.checked_sub(self.start.elapsed()) {
+            thread::sleep(ahead);
+        }
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_throttle() {
+        let input = vec![b'x'; 300];
+        let start = Instant::now();
+        let mut output = Vec::new();
+        Throttle::new(input.as_slice(), 1000)
+            .read_to_end(&mut output)
+            .unwrap();
+        assert_eq!(output, input);
+        assert!(start.elapsed() >= Duration::from_millis(300));
+    }
+}
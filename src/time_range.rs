@@ -0,0 +1,470 @@
+//! Restricts the search to log lines from a span of time, for `--since` and
+//! `--until-time`.
+//!
+//! Most log formats start every entry with its timestamp. The timestamp at
+//! the start of each line is parsed, and only the lines logged within the
+//! span are searched. Lines without a timestamp of their own, such as the
+//! rest of a stack trace, belong to the entry above them; lines before the
+//! first timestamp of a file are outside of the span.
+//!
+//! Timestamps are read with `--time-format`, or else with the first of
+//! `DEFAULT_FORMATS` that fits. Timestamps without a UTC offset are taken to
+//! be in local time, at the offset local time had on their date, and those
+//! without a year to be from the current year.
+
+use crate::config::{Config, parse_duration};
+use crate::messages::{MessageId, message};
+
+use std::{
+    error::Error,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The timestamp formats tried when `--time-format` is not given: ISO 8601
+/// and its common variations, syslog, and the Apache and nginx access logs
+/// (whose `[` in front of the timestamp is skipped).
+const DEFAULT_FORMATS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S%z",
+    "%Y-%m-%d %H:%M:%S%z",
+    "%Y/%m/%d %H:%M:%S%z",
+    "%d/%b/%Y:%H:%M:%S %z",
+    "%b %e %H:%M:%S",
+    "%Y-%m-%d",
+];
+
+/// The abbreviated English month names, as `%b` reads them.
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// The span of time the searched lines must have been logged in.
+pub struct TimeRange {
+    /// The earliest time searched, in seconds since the Unix epoch.
+    since: Option<i64>,
+    /// The time the searched lines must be before, in seconds since the Unix
+    /// epoch.
+    until: Option<i64>,
+    /// The `--time-format`, if given.
+    format: Option<String>,
+    /// The current year, for timestamps without one.
+    current_year: i64,
+}
+
+/// Tracks whether the lines of a single file are inside the time range.
+pub struct TimeTracker<'r> {
+    /// The span of time searched.
+    range: &'r TimeRange,
+    /// Whether the entry the current line belongs to is inside the span.
+    inside: bool,
+}
+
+/// The fields read from a timestamp.
+struct Fields {
+    year: Option<i64>,
+    month: i64,
+    day: i64,
+    hour: i64,
+    minute: i64,
+    second: i64,
+    /// The UTC offset, in seconds, if the timestamp has one.
+    offset: Option<i64>,
+}
+
+impl TimeRange {
+    /// Reads the `--since` and `--until-time` bounds of `config`.
+    ///
+    /// A bound is either a duration such as `1h`, counted back from now, or
+    /// a timestamp in one of the default formats.
+    ///
+    /// # Returns
+    /// The time range, `None` if neither bound is given, or an error if a
+    /// bound cannot be read.
+    pub fn new(config: &Config) -> Result<Option<TimeRange>, Box<dyn Error>> {
+        if config.since.is_none() && config.until_time.is_none() {
+            return Ok(None);
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs() as i64);
+        let mut range = TimeRange {
+            since: None,
+            until: None,
+            format: config.time_format.clone(),
+            current_year: civil_from_days((now + local_offset(now)).div_euclid(86_400)).0,
+        };
+
+        let bound = |value: &str| -> Result<i64, Box<dyn Error>> {
+            if let Ok(ago) = parse_duration(value) {
+                return Ok(now - ago.as_secs() as i64);
+            }
+            DEFAULT_FORMATS
+                .iter()
+                .find_map(|format| {
+                    parse(value, format)
+                        .filter(|&(_, len)| len == value.len())
+                        .map(|(fields, _)| range.to_epoch(&fields))
+                })
+                .ok_or_else(|| message(MessageId::InvalidTime, &[&value]).into())
+        };
+        let since = config.since.as_deref().map(&bound).transpose()?;
+        let until = config.until_time.as_deref().map(&bound).transpose()?;
+        range.since = since;
+        range.until = until;
+        Ok(Some(range))
+    }
+
+    /// Returns a tracker for the lines of a new file.
+    pub fn tracker(&self) -> TimeTracker<'_> {
+        TimeTracker {
+            range: self,
+            inside: false,
+        }
+    }
+
+    /// Returns the time `line` was logged at, in seconds since the Unix
+    /// epoch, if it starts with a timestamp.
+    fn line_time(&self, line: &str) -> Option<i64> {
+        let line = line.strip_prefix('[').unwrap_or(line);
+        let fields = match &self.format {
+            Some(format) => parse(line, format),
+            None => DEFAULT_FORMATS
+                .iter()
+                .find_map(|format| parse(line, format)),
+        };
+        fields.map(|(fields, _)| self.to_epoch(&fields))
+    }
+
+    /// Converts the fields of a timestamp to seconds since the Unix epoch.
+    ///
+    /// A timestamp without a UTC offset is in local time, whose offset is
+    /// looked up for the timestamp itself, so that the switches to and from
+    /// daylight saving time are followed.
+    fn to_epoch(&self, fields: &Fields) -> i64 {
+        let days = days_from_civil(
+            fields.year.unwrap_or(self.current_year),
+            fields.month,
+            fields.day,
+        );
+        let seconds = days * 86_400 + fields.hour * 3_600 + fields.minute * 60 + fields.second;
+        match fields.offset {
+            Some(offset) => seconds - offset,
+            None => {
+                // The offset at the time is unknown until the time is, so it
+                // is first guessed with the offset of the local time read as
+                // UTC. Only times near a switch need the second guess.
+                let guess = seconds - local_offset(seconds);
+                seconds - local_offset(guess)
+            }
+        }
+    }
+}
+
+impl TimeTracker<'_> {
+    /// Advances the tracker to the next line.
+    ///
+    /// # Returns
+    /// Whether the line is inside the time range.
+    pub fn advance(&mut self, line: &str) -> bool {
+        if let Some(time) = self.range.line_time(line) {
+            self.inside = self.range.since.is_none_or(|since| time >= since)
+                && self.range.until.is_none_or(|until| time < until);
+        }
+        self.inside
+    }
+}
+
+/// Reads a timestamp in `format` from the start of `text`.
+///
+/// The format knows `%Y`, `%m`, `%d`, `%e` (a day that may be padded with a
+/// space), `%H`, `%M`, `%S` (optionally followed by a fraction, which is
+/// ignored), `%b` (an English month abbreviation), `%z` (an optional `Z` or
+/// `+HH:MM`, with or without the colon) and `%%`. Any other character has to
+/// appear as it is.
+///
+/// # Returns
+/// The fields read and the length of the timestamp, or `None` if `text`
+/// does not start with a timestamp in `format`.
+fn parse(text: &str, format: &str) -> Option<(Fields, usize)> {
+    let mut fields = Fields {
+        year: None,
+        month: 1,
+        day: 1,
+        hour: 0,
+        minute: 0,
+        second: 0,
+        offset: None,
+    };
+    let bytes = text.as_bytes();
+    let mut pos = 0;
+    let mut spec = format.chars();
+    while let Some(c) = spec.next() {
+        if c != '%' {
+            let mut buf = [0; 4];
+            let literal = c.encode_utf8(&mut buf).as_bytes();
+            if !bytes[pos..].starts_with(literal) {
+                return None;
+            }
+            pos += literal.len();
+            continue;
+        }
+        match spec.next()? {
+            'Y' => fields.year = Some(number(bytes, &mut pos, 4)?),
+            'm' => fields.month = number(bytes, &mut pos, 2).filter(|m| (1..=12).contains(m))?,
+            'd' => fields.day = number(bytes, &mut pos, 2).filter(|d| (1..=31).contains(d))?,
+            'e' => {
+                if bytes.get(pos) == Some(&b' ') {
+                    pos += 1;
+                    fields.day = number(bytes, &mut pos, 1)?;
+                } else {
+                    fields.day = number(bytes, &mut pos, 2)?;
+                }
+                if !(1..=31).contains(&fields.day) {
+                    return None;
+                }
+            }
+            'H' => fields.hour = number(bytes, &mut pos, 2).filter(|h| *h < 24)?,
+            'M' => fields.minute = number(bytes, &mut pos, 2).filter(|m| *m < 60)?,
+            'S' => {
+                // A leap second is read as the last second of its minute.
+                fields.second = number(bytes, &mut pos, 2).filter(|s| *s <= 60)?.min(59);
+                if matches!(bytes.get(pos), Some(b'.' | b','))
+                    && bytes.get(pos + 1).is_some_and(u8::is_ascii_digit)
+                {
+                    pos += 1;
+                    while bytes.get(pos).is_some_and(u8::is_ascii_digit) {
+                        pos += 1;
+                    }
+                }
+            }
+            'b' => {
+                let month = MONTHS
+                    .iter()
+                    .position(|name| bytes[pos..].starts_with(name.as_bytes()))?;
+                fields.month = month as i64 + 1;
+                pos += 3;
+            }
+            'z' => fields.offset = utc_offset(bytes, &mut pos),
+            '%' => {
+                if bytes.get(pos) != Some(&b'%') {
+                    return None;
+                }
+                pos += 1;
+            }
+            _ => return None,
+        }
+    }
+    Some((fields, pos))
+}
+
+/// Reads a number of exactly `digits` digits at `pos`, moving past it.
+fn number(bytes: &[u8], pos: &mut usize, digits: usize) -> Option<i64> {
+    let digits = bytes.get(*pos..*pos + digits)?;
+    if !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    *pos += digits.len();
+    Some(
+        digits
+            .iter()
+            .fold(0, |value, digit| value * 10 + i64::from(digit - b'0')),
+    )
+}
+
+/// Reads a UTC offset, `Z` or `+HH:MM` with or without the colon, at `pos`,
+/// moving past it. Nothing is read if there is no offset.
+fn utc_offset(bytes: &[u8], pos: &mut usize) -> Option<i64> {
+    match bytes.get(*pos) {
+        Some(b'Z') => {
+            *pos += 1;
+            Some(0)
+        }
+        Some(&sign @ (b'+' | b'-')) => {
+            let mut end = *pos + 1;
+            let hours = number(bytes, &mut end, 2)?;
+            if bytes.get(end) == Some(&b':') {
+                end += 1;
+            }
+            let minutes = number(bytes, &mut end, 2)?;
+            *pos = end;
+            let offset = hours * 3_600 + minutes * 60;
+            Some(if sign == b'-' { -offset } else { offset })
+        }
+        _ => None,
+    }
+}
+
+/// Returns the number of days from 1970-01-01 to the given date of the
+/// proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Returns the year, month and day of the date `days` days after
+/// 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Returns the offset of local time from UTC at `now`, in seconds.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "dragonfly"
+))]
+fn local_offset(now: i64) -> i64 {
+    use std::ffi::{c_char, c_int, c_long};
+
+    // `time_t` is a `long` on these platforms.
+    let now = now as c_long;
+
+    /// The `struct tm` of the C library, up to the fields read here. The
+    /// `tm_gmtoff` and `tm_zone` fields are not standard, and only laid out
+    /// this way on the platforms this function is built for.
+    #[repr(C)]
+    struct Tm {
+        tm_sec: c_int,
+        tm_min: c_int,
+        tm_hour: c_int,
+        tm_mday: c_int,
+        tm_mon: c_int,
+        tm_year: c_int,
+        tm_wday: c_int,
+        tm_yday: c_int,
+        tm_isdst: c_int,
+        tm_gmtoff: c_long,
+        tm_zone: *const c_char,
+    }
+
+    unsafe extern "C" {
+        fn localtime_r(time: *const c_long, result: *mut Tm) -> *mut Tm;
+    }
+
+    let mut tm = std::mem::MaybeUninit::<Tm>::uninit();
+    // SAFETY: Both pointers are valid for the call, and `tm` is only read
+    // once `localtime_r` reports having filled it in.
+    unsafe {
+        if localtime_r(&now, tm.as_mut_ptr()).is_null() {
+            return 0;
+        }
+        // `long` is 32 bits wide on some platforms.
+        #[allow(clippy::useless_conversion)]
+        i64::from(tm.assume_init().tm_gmtoff)
+    }
+}
+
+/// Returns the offset of local time from UTC at `now`, in seconds.
+///
+/// The local time zone is not looked up on this platform, so local time is
+/// taken to be UTC.
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "dragonfly"
+)))]
+fn local_offset(_now: i64) -> i64 {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_days_from_civil() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11_017);
+        assert_eq!(civil_from_days(11_017), (2000, 3, 1));
+        assert_eq!(civil_from_days(days_from_civil(2024, 2, 29)), (2024, 2, 29));
+    }
+
+    #[test]
+    fn test_default_formats() {
+        let config = Config::parse_from(["grep-rust", "--since", "2024-01-01", "x", "f"]);
+        let range = TimeRange::new(&config).unwrap().unwrap();
+        let utc = days_from_civil(2024, 1, 2) * 86_400 + 3_600;
+        assert_eq!(
+            range.line_time("2024-01-02T01:00:00Z INFO started"),
+            Some(utc)
+        );
+        assert_eq!(
+            range.line_time("2024-01-02 02:00:00.123+01:00 INFO"),
+            Some(utc)
+        );
+        assert_eq!(
+            range.line_time("[02/Jan/2024:01:00:00 +0000] GET /"),
+            Some(utc)
+        );
+        assert_eq!(range.line_time("    at main.rs:10"), None);
+    }
+
+    #[test]
+    fn test_tracker() {
+        let args = [
+            "grep-rust",
+            "--since",
+            "2024-01-02T00:00:00Z",
+            "--until-time",
+            "2024-01-03T00:00:00Z",
+            "x",
+            "f",
+        ];
+        let config = Config::parse_from(args);
+        let range = TimeRange::new(&config).unwrap().unwrap();
+        let mut tracker = range.tracker();
+        assert!(!tracker.advance("preamble"));
+        assert!(!tracker.advance("2024-01-01T23:59:59Z old"));
+        assert!(tracker.advance("2024-01-02T00:00:00Z new"));
+        assert!(tracker.advance("  continued"));
+        assert!(!tracker.advance("2024-01-03T00:00:00Z too new"));
+    }
+
+    #[test]
+    fn test_time_format() {
+        let args = [
+            "grep-rust",
+            "--since",
+            "1h",
+            "--time-format",
+            "%d.%m.%Y %H:%M",
+            "x",
+            "f",
+        ];
+        let config = Config::parse_from(args);
+        let range = TimeRange::new(&config).unwrap().unwrap();
+        assert!(range.line_time("31.12.2023 23:59 done").is_some());
+        assert_eq!(range.line_time("2023-12-31 23:59 done"), None);
+
+        let config = Config::parse_from(["grep-rust", "--since", "yesterday", "x", "f"]);
+        assert!(TimeRange::new(&config).is_err());
+    }
+}
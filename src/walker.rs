@@ -0,0 +1,396 @@
+//! Turns the paths given on the command line into the list of files to search.
+//!
+//! Regular files are passed through as they are. Directories are handled
+//! according to the `--directories` action: they are either reported and
+//! skipped, skipped silently, or walked recursively. Files found while walking are
+//! skipped without being opened if their extension marks them as binary,
+//! such as images, archives or compiled objects. Wildcard patterns such as
+//! `'logs/**/*.log'` are expanded here, both for Windows, whose shells leave
+//! them to the program, and for quoted patterns on Unix, which the shell
+//! passes on unexpanded.
+
+use crate::config::DirectoryAction;
+use crate::glob::{Glob, has_glob_chars};
+use crate::messages::{MessageId, message};
+use crate::paths::fs_path;
+use crate::printer::print_file_error;
+
+use std::{collections::HashSet, fs, path::Path};
+
+/// The extensions of files that are known to be binary, and are skipped
+/// while walking directories. Lowercase, and sorted for binary search.
+const BINARY_EXTENSIONS: &[&str] = &[
+    "7z", "a", "avi", "bin", "bmp", "bz2", "class", "db", "dll", "dmg", "doc", "docx", "dylib",
+    "eot", "exe", "flac", "gif", "gz", "ico", "iso", "jar", "jpeg", "jpg", "lib", "mkv", "mov",
+    "mp3", "mp4", "o", "obj", "ogg", "otf", "pdb", "pdf", "png", "pyc", "pyo", "rlib", "so",
+    "sqlite", "tar", "tgz", "tif", "tiff", "ttf", "war", "wasm", "wav", "webm", "webp", "whl",
+    "woff", "woff2", "xls", "xlsx", "xz", "zip", "zst",
+];
+
+/// Expands the command-line paths into the files that should be searched.
+///
+/// When directories are recursed into, their entries are visited in
+/// lexicographic order so the resulting file list is stable between runs.
+/// Symbolic links found inside directories are not followed, while links
+/// named on the command line are. Hard links to a file already found while
+/// walking any of the directories are left out, while paths named on the
+/// command line are always kept, even if they name the same file. With `one_file_system`, directories on
+/// another file system than the directory being walked, i.e. mount points,
+/// are left out. Directories given while `directory_action`
+/// is `Read`, and directories that cannot be read, are reported on stderr
+/// and skipped, which makes the exit status 2 like that of an unreadable
+/// file.
+///
+/// # Arguments
+/// * `paths` - The paths given on the command line.
+/// * `directory_action` - What to do with paths that are directories.
+/// * `skip_binary` - Whether to skip files with a known binary extension
+///   found inside directories. Files named on the command line are kept.
+/// * `one_file_system` - Whether to stay on the file system of each walked
+///   directory.
+///
+/// # Returns
+/// The files to search.
+pub fn expand_paths(
+    paths: &[String],
+    directory_action: DirectoryAction,
+    skip_binary: bool,
+    one_file_system: bool,
+) -> Vec<String> {
+    let mut files = Vec::new();
+    // The files found while walking, shared by all walks so that a file
+    // hard-linked into two of the given directories is searched once.
+    let mut seen = HashSet::new();
+
+    let paths = &expand_wildcards(paths, directory_action == DirectoryAction::Recurse);
+
+    for path in paths {
+        // Paths that cannot be inspected are passed on as files, so that
+        // opening them reports the actual error.
+        let is_dir = fs::metadata(fs_path(Path::new(path))).is_ok_and(|metadata| metadata.is_dir());
+        if !is_dir {
+            files.push(path.clone());
+            continue;
+        }
+
+        match directory_action {
+            DirectoryAction::Read => print_file_error(&message(MessageId::IsADirectory, &[path])),
+            DirectoryAction::Skip => {}
+            DirectoryAction::Recurse => {
+                let path = Path::new(path);
+                let device = if one_file_system {
+                    device_id(path)
+                } else {
+                    None
+                };
+                let mut walked = Vec::new();
+                walk_directory(path, device, device_id, skip_binary, &mut walked);
+                // Hard links can make the same file show up under several
+                // names while walking. Each underlying file is searched only
+                // once, so its matches are not reported twice.
+                walked.retain(|file| {
+                    file_identity(file).is_none_or(|identity| seen.insert(identity))
+                });
+                files.extend(walked);
+            }
+        }
+    }
+
+    files
+}
+
+/// Expands the wildcard patterns among `paths`, the way Unix shells do.
+///
+/// Paths that name an existing file are kept as they are, even if they
+/// contain wildcard characters. Patterns that match nothing are kept too, so
+/// that opening them reports the missing file. Directories matched by a
+/// pattern are left out unless `keep_directories` is set, since shells and
+/// grep pass over them without a warning.
+fn expand_wildcards(paths: &[String], keep_directories: bool) -> Vec<String> {
+    let mut expanded = Vec::with_capacity(paths.len());
+    for path in paths {
+        let matches = if has_glob_chars(path) && fs::metadata(fs_path(Path::new(path))).is_err() {
+            expand_wildcard(path)
+        } else {
+            Vec::new()
+        };
+        if matches.is_empty() {
+            expanded.push(path.clone());
+        } else {
+            expanded.extend(matches.into_iter().filter(|found| {
+                keep_directories
+                    || !fs::metadata(fs_path(Path::new(found)))
+                        .is_ok_and(|metadata| metadata.is_dir())
+            }));
+        }
+    }
+    expanded
+}
+
+/// Returns the paths matching the wildcard `pattern`, in lexicographic order.
+///
+/// Only the directory the first wildcard appears in is listed, unless the
+/// pattern continues into subdirectories (as in `src/*/*.rs` or
+/// `src/**/*.rs`), in which case that directory is walked recursively.
+/// Backslashes are treated as path separators.
+fn expand_wildcard(pattern: &str) -> Vec<String> {
+    let pattern = pattern.replace('\\', "/");
+    let pattern = pattern.strip_prefix("./").unwrap_or(&pattern);
+    let glob_start = pattern.find(['*', '?', '[']).unwrap_or(pattern.len());
+    let base = pattern[..glob_start].rfind('/').map(|end| &pattern[..end]);
+    let recursive = pattern[glob_start..].contains('/');
+
+    let mut candidates = Vec::new();
+    list_entries(base, recursive, &mut candidates);
+
+    let glob = Glob::new(pattern);
+    candidates.retain(|candidate| glob.is_match(candidate));
+    candidates.sort();
+    candidates
+}
+
+/// Collects the entries of a directory into `entries`, as paths starting
+/// with `prefix`.
+///
+/// # Arguments
+/// * `prefix` - The directory to list, or `None` for the current directory,
+///   whose entries are then collected without a prefix.
+/// * `recursive` - Whether to also collect the entries of subdirectories.
+/// * `entries` - The list the entry paths are appended to.
+fn list_entries(prefix: Option<&str>, recursive: bool, entries: &mut Vec<String>) {
+    let dir = match prefix {
+        None => ".",
+        Some("") => "/",
+        Some(prefix) => prefix,
+    };
+    let Ok(dir_entries) = fs::read_dir(fs_path(Path::new(dir))) else {
+        return;
+    };
+
+    for entry in dir_entries.filter_map(Result::ok) {
+        let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        let path = match prefix {
+            Some(prefix) => format!("{}/{}", prefix, name),
+            None => name,
+        };
+        if recursive && entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+            list_entries(Some(&path), recursive, entries);
+        }
+        entries.push(path);
+    }
+}
+
+/// Recursively collects the files below `dir` into `files`.
+///
+/// The collected paths are built from `dir` as given, so they keep the form
+/// the user typed, while the file system is accessed through `fs_path` so
+/// that deep trees and network shares also work on Windows.
+///
+/// # Arguments
+/// * `dir` - The directory to walk.
+/// * `device` - The file system to stay on, or `None` to walk into every
+///   subdirectory.
+/// * `device_of` - The function returning the file system of a directory,
+///   which is `device_id` but for the tests.
+/// * `skip_binary` - Whether to skip files with a known binary extension.
+/// * `files` - The list the found files are appended to.
+fn walk_directory(
+    dir: &Path,
+    device: Option<u64>,
+    device_of: fn(&Path) -> Option<u64>,
+    skip_binary: bool,
+    files: &mut Vec<String>,
+) {
+    let entries = match fs::read_dir(fs_path(dir)) {
+        Ok(entries) => entries,
+        Err(e) => {
+            print_file_error(&message(MessageId::FileError, &[&dir.display(), &e]));
+            return;
+        }
+    };
+
+    let mut entries: Vec<_> = entries.filter_map(Result::ok).collect();
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let entry_path = dir.join(entry.file_name());
+
+        if file_type.is_dir() {
+            if device.is_none() || device_of(&entry_path) == device {
+                walk_directory(&entry_path, device, device_of, skip_binary, files);
+            }
+        } else if file_type.is_symlink() || (skip_binary && has_binary_extension(&entry_path)) {
+            continue;
+        } else {
+            match entry_path.into_os_string().into_string() {
+                Ok(path) => files.push(path),
+                Err(path) => print_file_error(&message(
+                    MessageId::InvalidFileName,
+                    &[&path.to_string_lossy()],
+                )),
+            }
+        }
+    }
+}
+
+/// Returns the id of the file system device holding `path`, or `None` if its
+/// metadata cannot be read.
+#[cfg(unix)]
+fn device_id(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    fs::metadata(fs_path(path))
+        .ok()
+        .map(|metadata| metadata.dev())
+}
+
+/// Returns the id of the file system device holding `path`.
+///
+/// Device ids are not available on this platform, so this always returns
+/// `None` and walks are never limited to one file system.
+#[cfg(not(unix))]
+fn device_id(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Returns the (device, inode) pair identifying the file behind `path`.
+///
+/// Symlinks are followed, so every name leading to the same file yields the
+/// same identity. On platforms without inode numbers, or if the file's
+/// metadata cannot be read, `None` is returned and the file is never treated
+/// as a duplicate.
+#[cfg(unix)]
+fn file_identity(path: &str) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    fs::metadata(fs_path(Path::new(path)))
+        .ok()
+        .map(|metadata| (metadata.dev(), metadata.ino()))
+}
+
+/// Returns the (device, inode) pair identifying the file behind `path`.
+///
+/// Inode numbers are not available on this platform, so files are never
+/// treated as duplicates.
+#[cfg(not(unix))]
+fn file_identity(_path: &str) -> Option<(u64, u64)> {
+    None
+}
+
+/// Returns whether the extension of `path` marks it as a binary file.
+fn has_binary_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            BINARY_EXTENSIONS
+                .binary_search(&extension.to_ascii_lowercase().as_str())
+                .is_ok()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_wildcard() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("logs/old")).unwrap();
+        for file in [
+            "a.log",
+            "b.log",
+            "notes.txt",
+            "logs/c.log",
+            "logs/old/d.log",
+        ] {
+            fs::write(root.join(file), "").unwrap();
+        }
+        let root_text = root.to_str().unwrap().replace('\\', "/");
+
+        let expanded = expand_wildcard(&format!("{}/*.log", root_text));
+        assert_eq!(
+            expanded,
+            vec![
+                format!("{}/a.log", root_text),
+                format!("{}/b.log", root_text)
+            ]
+        );
+        let expanded = expand_wildcard(&format!("{}/**/*.log", root_text));
+        assert_eq!(expanded.len(), 4);
+        assert!(expand_wildcard(&format!("{}/*.csv", root_text)).is_empty());
+
+        // Directories are kept only when they are to be walked.
+        let pattern = [format!("{}/*", root_text)];
+        assert_eq!(expand_wildcards(&pattern, false).len(), 3);
+        assert_eq!(expand_wildcards(&pattern, true).len(), 4);
+    }
+
+    #[test]
+    fn test_skips_binary_extensions() {
+        assert!(BINARY_EXTENSIONS.windows(2).all(|pair| pair[0] < pair[1]));
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for file in ["logo.PNG", "lib.so", "main.rs"] {
+            fs::write(root.join(file), "").unwrap();
+        }
+        let paths = [root.to_str().unwrap().to_string()];
+
+        let files = expand_paths(&paths, DirectoryAction::Recurse, true, false);
+        assert_eq!(files.len(), 1);
+        assert!(files[0].ends_with("main.rs"));
+        let files = expand_paths(&paths, DirectoryAction::Recurse, false, false);
+        assert_eq!(files.len(), 3);
+    }
+
+    #[test]
+    fn test_one_file_system() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for subdir in ["sub", "mnt/deeper"] {
+            fs::create_dir_all(root.join(subdir)).unwrap();
+        }
+        for file in ["a.txt", "sub/b.txt", "mnt/c.txt", "mnt/deeper/d.txt"] {
+            fs::write(root.join(file), "").unwrap();
+        }
+
+        // `mnt` and everything below it are taken to be on another device.
+        fn fake_device_id(path: &Path) -> Option<u64> {
+            Some(if path.iter().any(|part| part == "mnt") {
+                2
+            } else {
+                1
+            })
+        }
+        let names = |files: Vec<String>| -> Vec<String> {
+            files
+                .iter()
+                .map(|file| {
+                    Path::new(file)
+                        .file_name()
+                        .unwrap()
+                        .to_string_lossy()
+                        .into_owned()
+                })
+                .collect()
+        };
+
+        let mut files = Vec::new();
+        walk_directory(root, Some(1), fake_device_id, false, &mut files);
+        assert_eq!(names(files), ["a.txt", "b.txt"]);
+        let mut files = Vec::new();
+        walk_directory(root, None, fake_device_id, false, &mut files);
+        assert_eq!(names(files), ["a.txt", "c.txt", "d.txt", "b.txt"]);
+
+        // A tree on a single device is walked whole.
+        let paths = [root.to_str().unwrap().to_string()];
+        let files = expand_paths(&paths, DirectoryAction::Recurse, false, true);
+        assert_eq!(files.len(), 4);
+    }
+}
@@ -0,0 +1,149 @@
+//! Searches again whenever the searched files change, for `--watch`.
+//!
+//! After each search, the searched paths are polled for changes: files are
+//! compared by size and modification time, and directories searched with
+//! `--directories recurse` are walked again, so that new and deleted files
+//! count as changes too. Polling needs no platform-specific file system
+//! notifications and sees the same files the search does, at the cost of
+//! reacting within `POLL_INTERVAL` rather than at once. Ctrl-C ends the
+//! watch.
+
+use crate::config::{Config, DirectoryAction};
+use crate::interrupt::interrupted;
+use crate::my_lib::run;
+use crate::paths::fs_path;
+use crate::printer::{print_warning, reset_headings};
+use crate::walker::expand_paths;
+
+use std::{
+    error::Error,
+    fs,
+    io::{self, Write},
+    path::Path,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+/// How often the searched files are checked for changes.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The escape sequence clearing the terminal and moving the cursor to its
+/// top left corner.
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+/// The size and modification time of every searched file, in search order.
+type Snapshot = Vec<(String, Option<(u64, SystemTime)>)>;
+
+/// The paths to watch, taken from the configuration of the first search.
+struct Watched {
+    file_paths: Vec<String>,
+    directories: DirectoryAction,
+    skip_binary: bool,
+    one_file_system: bool,
+}
+
+impl Watched {
+    /// Returns the current state of the watched files.
+    fn snapshot(&self) -> Snapshot {
+        // Directories that are not searched were reported by the search, and
+        // are not reported again on every poll.
+        let directories = match self.directories {
+            DirectoryAction::Read => DirectoryAction::Skip,
+            directories => directories,
+        };
+        let files = expand_paths(
+            &self.file_paths,
+            directories,
+            self.skip_binary,
+            self.one_file_system,
+        );
+        files
+            .into_iter()
+            .filter(|path| path != "-")
+            .map(|path| {
+                let state = fs::metadata(fs_path(Path::new(&path)))
+                    .and_then(|metadata| Ok((metadata.len(), metadata.modified()?)))
+                    .ok();
+                (path, state)
+            })
+            .collect()
+    }
+}
+
+/// Runs the search, then runs it again every time the searched files
+/// change, until Ctrl-C is pressed.
+///
+/// # Arguments
+/// * `config` - The configuration of the first search.
+/// * `next_config` - Makes the configuration of every later search, since
+///   a search consumes its configuration.
+///
+/// # Returns
+/// Whether the last search found a match, or an error if the first search
+/// fails. Errors of later searches are reported and the watch goes on.
+pub fn run_watch(config: Config, next_config: impl Fn() -> Config) -> Result<bool, Box<dyn Error>> {
+    let watched = Watched {
+        file_paths: config.file_paths.clone(),
+        directories: config.directories,
+        skip_binary: !config.no_ext_filter,
+        one_file_system: config.one_file_system,
+    };
+    let clear = config.clear;
+
+    let mut snapshot = watched.snapshot();
+    let mut found = run(config)?;
+    loop {
+        io::stdout().flush()?;
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            if interrupted() {
+                return Ok(found);
+            }
+            let current = watched.snapshot();
+            if current != snapshot {
+                snapshot = current;
+                break;
+            }
+        }
+
+        if clear {
+            print!("{}", CLEAR_SCREEN);
+        } else {
+            println!();
+        }
+        reset_headings();
+        match run(next_config()) {
+            Ok(result) => found = result,
+            Err(e) => print_warning(&e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let file = root.join("todo.md");
+        fs::write(&file, "TODO: a\n").unwrap();
+        let watched = Watched {
+            file_paths: vec![root.to_str().unwrap().to_string()],
+            directories: DirectoryAction::Recurse,
+            skip_binary: true,
+            one_file_system: false,
+        };
+
+        let before = watched.snapshot();
+        assert_eq!(before.len(), 1);
+        assert_eq!(watched.snapshot(), before);
+
+        fs::write(&file, "TODO: a\nTODO: b\n").unwrap();
+        assert_ne!(watched.snapshot(), before);
+
+        fs::write(root.join("more.md"), "").unwrap();
+        assert_eq!(watched.snapshot().len(), 2);
+    }
+}
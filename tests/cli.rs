@@ -0,0 +1,1086 @@
+//! Runs the `grep-rust` binary on real files and checks what it prints and
+//! its exit status.
+
+use std::fs;
+use std::path::Path;
+use std::process::{Child, Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+/// How long a search may take before it is considered hung.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A directory of files to search, removed again when dropped.
+struct TestDir(TempDir);
+
+impl TestDir {
+    /// Creates an empty directory for the test `name`.
+    fn new(name: &str) -> Self {
+        let dir = tempfile::Builder::new()
+            .prefix(&format!("grep-rust-cli-{}-", name))
+            .tempdir()
+            .unwrap();
+        TestDir(dir)
+    }
+
+    /// Returns the path of the directory.
+    fn path(&self) -> &Path {
+        self.0.path()
+    }
+
+    /// Writes `content` to the file `name` in the directory.
+    fn write(&self, name: &str, content: impl AsRef<[u8]>) -> &Self {
+        let path = self.path().join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(path, content).unwrap();
+        self
+    }
+
+    /// Runs `grep-rust` with `args` in the directory, in the English locale
+    /// and without colors.
+    fn grep(&self, args: &[&str]) -> Output {
+        self.grep_with_env(args, &[])
+    }
+
+    /// Runs `grep-rust` like `grep`, with the environment variables `vars`
+    /// set in addition.
+    fn grep_with_env(&self, args: &[&str], vars: &[(&str, &str)]) -> Output {
+        let child = self.spawn(args, vars);
+        wait(child, args)
+    }
+
+    /// Starts `grep-rust` like `grep_with_env`, without waiting for it.
+    fn spawn(&self, args: &[&str], vars: &[(&str, &str)]) -> Child {
+        Command::new(env!("CARGO_BIN_EXE_grep-rust"))
+            .args(args)
+            .current_dir(self.path())
+            .env("LANG", "C")
+            .env("LC_ALL", "C")
+            .env("NO_COLOR", "1")
+            .envs(vars.iter().copied())
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap()
+    }
+}
+
+/// Waits for the `grep-rust` started with `args` to finish, killing it if it
+/// takes too long.
+fn wait(mut child: Child, args: &[&str]) -> Output {
+    let start = Instant::now();
+    while child.try_wait().unwrap().is_none() {
+        if start.elapsed() > TIMEOUT {
+            child.kill().unwrap();
+            panic!("grep-rust {args:?} did not finish");
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    child.wait_with_output().unwrap()
+}
+
+/// Returns what `output` printed to stdout.
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+/// Returns what `output` printed to stderr.
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn test_dedupe_content_skips_copies_only() {
+    let dir = TestDir::new("dedupe");
+    let head = "x".repeat(100 * 1024);
+    dir.write("a.txt", format!("{head}\nneedle one\n"))
+        .write("b.txt", format!("{head}\nneedle one\n"))
+        .write("c.txt", format!("{head}\nneedle two\n"));
+
+    let output = dir.grep(&["--dedupe-content", "needle", "a.txt", "b.txt", "c.txt"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "a.txt:2:needle one\nc.txt:2:needle two\n");
+}
+
+#[test]
+#[cfg(unix)]
+fn test_dedupe_content_does_not_open_fifos() {
+    let dir = TestDir::new("dedupe-fifo");
+    dir.write("a.txt", "needle\n");
+    let status = Command::new("mkfifo")
+        .arg(dir.path().join("fifo"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let output = dir.grep(&["--dedupe-content", "needle", "fifo", "a.txt"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "a.txt:1:needle\n");
+}
+
+#[test]
+fn test_unreadable_file_does_not_stop_the_search() {
+    let dir = TestDir::new("missing");
+    dir.write("a.txt", "needle a\n")
+        .write("c.txt", "needle c\n");
+
+    let output = dir.grep(&["needle", "a.txt", "b.txt", "c.txt"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(stdout(&output), "a.txt:1:needle a\nc.txt:1:needle c\n");
+    assert!(stderr(&output).starts_with("b.txt: No such file or directory"));
+
+    let output = dir.grep(&["--no-messages", "--no-summary", "needle", "b.txt", "c.txt"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(stderr(&output), "");
+
+    let output = dir.grep(&["--quiet", "needle", "b.txt", "c.txt"]);
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn test_directories_are_reported_and_skipped() {
+    let dir = TestDir::new("directories");
+    dir.write("a.txt", "needle a\n")
+        .write("d/b.txt", "needle b\n")
+        .write("z.txt", "needle z\n");
+
+    let output = dir.grep(&["--no-summary", "needle", "a.txt", "d", "z.txt"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(stdout(&output), "a.txt:1:needle a\nz.txt:1:needle z\n");
+    assert_eq!(stderr(&output), "d: Is a directory\n");
+
+    let output = dir.grep(&["-d", "skip", "--no-summary", "needle", "a.txt", "d"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stderr(&output), "");
+
+    let output = dir.grep(&["-d", "recurse", "needle", "d"]);
+    assert_eq!(stdout(&output), "d/b.txt:1:needle b\n");
+}
+
+#[test]
+#[cfg(unix)]
+fn test_failed_preprocessor_is_an_error() {
+    let dir = TestDir::new("pre-glob");
+    dir.write("a.txt", "").write("b.log", "needle b\n");
+
+    let failing = "*.txt=echo needle a; echo cannot convert >&2; exit 3";
+    let output = dir.grep(&["--pre-glob", failing, "needle", "a.txt", "b.log"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stdout(&output).ends_with("b.log:1:needle b\n"));
+    assert!(stderr(&output).starts_with("a.txt: cannot convert\n"));
+
+    let output = dir.grep(&["--pre-glob", "*.txt=echo needle a", "needle", "a.txt"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "1:needle a\n");
+}
+
+#[test]
+fn test_summary_counts_lines_and_files() {
+    let dir = TestDir::new("summary");
+    dir.write("a.txt", "needle needle\nneedle\n")
+        .write("b.txt", "needle\n")
+        .write("c.txt", "hay\n");
+
+    let summary = |args: &[&str]| {
+        let output = dir.grep(args);
+        let stderr = stderr(&output);
+        // Leave out the elapsed time, which differs from run to run.
+        stderr[..stderr.rfind(" (").unwrap()].to_string()
+    };
+    assert_eq!(
+        summary(&["needle", "a.txt", "b.txt", "c.txt"]),
+        "3 matching lines in 2 files"
+    );
+    assert_eq!(summary(&["needle", "b.txt"]), "1 matching line in 1 file");
+    assert_eq!(
+        summary(&["-l", "needle", "a.txt", "b.txt", "c.txt"]),
+        "2 matching files"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn test_failed_exec_command_is_reported() {
+    let dir = TestDir::new("exec");
+    dir.write("a.txt", "needle a\n")
+        .write("b.txt", "needle b\n");
+
+    let failing = "grep -q 'needle a' {}";
+    let output = dir.grep(&[
+        "-l",
+        "--no-summary",
+        "--exec",
+        failing,
+        "needle",
+        "a.txt",
+        "b.txt",
+    ]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(stdout(&output), "a.txt\nb.txt\n");
+    assert_eq!(
+        stderr(&output),
+        "b.txt: --exec command failed with exit status: 1\n"
+    );
+
+    let output = dir.grep(&["-l", "--exec", "true", "needle", "a.txt", "b.txt"]);
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn test_report_spans_follow_the_scope() {
+    let dir = TestDir::new("report-spans");
+    dir.write("a.rs", "let todo = 1; // todo\n")
+        .write("a.csv", "name,note\ntodo,todo later\n");
+
+    let output = dir.grep(&["--format", "json", "--only-comments", "todo", "a.rs"]);
+    let report = stdout(&output);
+    assert!(report.contains(
+        "\"matches\": [{\"match\": \"todo\", \"start\": 17, \"end\": 21, \
+         \"start_column\": 18, \"end_column\": 22}]"
+    ));
+
+    let args = [
+        "--format",
+        "json",
+        "--csv",
+        "--column-name",
+        "note",
+        "todo",
+        "a.csv",
+    ];
+    let report = stdout(&dir.grep(&args));
+    assert!(report.contains(
+        "\"matches\": [{\"match\": \"todo\", \"start\": 5, \"end\": 9, \
+         \"start_column\": 6, \"end_column\": 10}]"
+    ));
+}
+
+#[test]
+fn test_max_total_caps_matches_across_files() {
+    let dir = TestDir::new("max-total");
+    dir.write("a.txt", "needle 1\nneedle 2\n")
+        .write("b.txt", "needle 3\nneedle 4\n")
+        .write("c.txt", "needle 5\n");
+
+    let output = dir.grep(&["--max-total", "3", "needle", "a.txt", "b.txt", "c.txt"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout(&output),
+        "a.txt:1:needle 1\na.txt:2:needle 2\nb.txt:1:needle 3\n"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn test_quiet_and_files_with_matches_stop_at_the_first_match() {
+    let dir = TestDir::new("first-match");
+    dir.write("endless.txt", "");
+    // The preprocessor never ends, so only a search that stops at the first
+    // match finishes.
+    let endless = "*.txt=yes needle";
+
+    let output = dir.grep(&["-q", "--pre-glob", endless, "needle", "endless.txt"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "");
+
+    let args = [
+        "-l",
+        "--no-summary",
+        "--pre-glob",
+        endless,
+        "needle",
+        "endless.txt",
+    ];
+    let output = dir.grep(&args);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "endless.txt\n");
+}
+
+#[test]
+fn test_max_memory_still_finds_matches_in_long_lines() {
+    let dir = TestDir::new("max-memory");
+    let long_line = format!("{}needle", "a".repeat(300_000));
+    dir.write("long.txt", format!("{long_line}\nshort needle\n"));
+
+    let args = ["--max-memory", "64K", "-o", "needle", "long.txt"];
+    let output = dir.grep(&args);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "1:needle\n2:needle\n");
+
+    // Only the chunk of the long line around the match is printed.
+    let output = dir.grep(&["--max-memory", "64K", "needle", "long.txt"]);
+    let lines: Vec<_> = stdout(&output).lines().map(str::len).collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0] < 64 * 1024);
+}
+
+#[test]
+fn test_repl_skips_files_it_cannot_load() {
+    let dir = TestDir::new("repl-files");
+    dir.write("a.txt", "needle\n")
+        .write("bad.txt", b"needle \xff\n");
+
+    // The input is empty, so the session ends right after loading.
+    let output = dir.grep(&["repl", "missing.txt", "bad.txt", "a.txt"]);
+    assert_eq!(output.status.code(), Some(2));
+    let errors = stderr(&output);
+    assert!(errors.contains("missing.txt: No such file or directory"));
+    assert!(errors.contains("bad.txt: stream did not contain valid UTF-8"));
+
+    let output = dir.grep(&["-c", "repl", "a.txt"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("'--count' cannot be used with 'repl'"));
+}
+
+#[test]
+fn test_empty_pattern_matches_blank_lines() {
+    let dir = TestDir::new("empty-pattern");
+    dir.write("a.txt", "zero\nBEGIN\none\n\ntwo\nEND\n\n");
+
+    let output = dir.grep(&["", "a.txt"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout(&output),
+        "1:zero\n2:BEGIN\n3:one\n4:\n5:two\n6:END\n7:\n"
+    );
+
+    let output = dir.grep(&["-x", "", "a.txt"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "4:\n7:\n");
+
+    let output = dir.grep(&["--from", "BEGIN", "--until", "END", "", "a.txt"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "2:BEGIN\n3:one\n4:\n5:two\n6:END\n");
+}
+
+#[test]
+fn test_sort_orders_the_searched_files() {
+    let dir = TestDir::new("sort");
+    dir.write("b.txt", "needle\n")
+        .write("c.txt", "needle needle needle\n")
+        .write("a.txt", "needle needle\n");
+    let files = ["b.txt", "c.txt", "a.txt"];
+
+    let sorted = |key_args: &[&str]| {
+        let args: Vec<_> = ["-l", "--no-summary"]
+            .iter()
+            .chain(key_args)
+            .chain(&["needle"])
+            .chain(&files)
+            .copied()
+            .collect();
+        stdout(&dir.grep(&args))
+    };
+    assert_eq!(sorted(&[]), "b.txt\nc.txt\na.txt\n");
+    assert_eq!(sorted(&["--sort", "path"]), "a.txt\nb.txt\nc.txt\n");
+    assert_eq!(sorted(&["--sortr", "path"]), "c.txt\nb.txt\na.txt\n");
+    assert_eq!(sorted(&["--sort", "size"]), "b.txt\na.txt\nc.txt\n");
+    assert_eq!(sorted(&["--sortr", "size"]), "c.txt\na.txt\nb.txt\n");
+}
+
+#[test]
+#[cfg(unix)]
+fn test_timeouts_abandon_endless_files() {
+    let dir = TestDir::new("timeout");
+    dir.write("endless.txt", "").write("z.txt", "needle\n");
+    let endless = "endless.txt=yes hay";
+
+    let args = [
+        "--no-summary",
+        "--file-timeout",
+        "200ms",
+        "--pre-glob",
+        endless,
+    ];
+    let output = dir.grep(&[&args[..], &["needle", "endless.txt", "z.txt"]].concat());
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "z.txt:1:needle\n");
+    assert_eq!(
+        stderr(&output),
+        "endless.txt: search timed out after 200ms\n"
+    );
+
+    let args = ["--no-summary", "--timeout", "200ms", "--pre-glob", endless];
+    let output = dir.grep(&[&args[..], &["needle", "endless.txt", "z.txt"]].concat());
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "");
+    assert_eq!(
+        stderr(&output),
+        "endless.txt: search timed out after 200ms, remaining files were not searched\n"
+    );
+}
+
+#[test]
+fn test_only_matching_with_byte_offsets() {
+    let dir = TestDir::new("byte-offset");
+    dir.write("a.txt", "ab needle cd needle\nxx\nneedle\n");
+
+    let output = dir.grep(&["--no-summary", "-o", "-b", "needle", "a.txt"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "1:3:needle\n1:13:needle\n3:23:needle\n");
+
+    let output = dir.grep(&["--no-summary", "-b", "needle", "a.txt"]);
+    assert_eq!(stdout(&output), "1:0:ab needle cd needle\n3:23:needle\n");
+}
+
+#[test]
+fn test_top_reports_the_files_with_most_matches() {
+    let dir = TestDir::new("top");
+    dir.write("a.txt", "needle\n")
+        .write("b.txt", "needle\nneedle\nneedle\n")
+        .write("c.txt", "needle\nhay\nneedle\n");
+
+    let output = dir.grep(&[
+        "--no-summary",
+        "--top",
+        "2",
+        "needle",
+        "a.txt",
+        "b.txt",
+        "c.txt",
+    ]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "b.txt:3\nc.txt:2\n");
+}
+
+#[test]
+fn test_sort_lines_across_files() {
+    let dir = TestDir::new("sort-lines");
+    dir.write("a.txt", "b\na\nb\nc\n").write("b.txt", "a\nd\n");
+
+    let output = dir.grep(&["--no-summary", "--sort-lines", "", "a.txt", "b.txt"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout(&output),
+        "a.txt:2:a\nb.txt:1:a\na.txt:1:b\na.txt:3:b\na.txt:4:c\nb.txt:2:d\n"
+    );
+
+    let args = [
+        "--no-summary",
+        "--sort-lines",
+        "--unique",
+        "",
+        "a.txt",
+        "b.txt",
+    ];
+    let output = dir.grep(&args);
+    assert_eq!(
+        stdout(&output),
+        "a.txt:2:a\na.txt:1:b\na.txt:4:c\nb.txt:2:d\n"
+    );
+}
+
+#[test]
+fn test_quickfix_file_lists_every_match() {
+    let dir = TestDir::new("quickfix");
+    dir.write("a.txt", "hay\n  x needle\n")
+        .write("b.txt", "needle\n");
+
+    let args = [
+        "--no-summary",
+        "--quickfix",
+        "matches.qf",
+        "needle",
+        "a.txt",
+        "b.txt",
+    ];
+    let output = dir.grep(&args);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "a.txt:2:  x needle\nb.txt:1:needle\n");
+    assert_eq!(
+        fs::read_to_string(dir.path().join("matches.qf")).unwrap(),
+        "a.txt:2:5:   x needle\nb.txt:1:1: needle\n"
+    );
+}
+
+#[test]
+fn test_emacs_output_puts_a_nul_after_file_names() {
+    let dir = TestDir::new("emacs");
+    dir.write("a.txt", "hay\n  x needle\n");
+
+    // Emacs needs the file name even when a single file is searched, and no
+    // summary may follow the lines.
+    let output = dir.grep(&["--emacs", "needle", "a.txt"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "a.txt\x002:  x needle\n");
+    assert_eq!(stderr(&output), "");
+}
+
+#[test]
+fn test_fail_on_match_inverts_the_exit_status() {
+    let dir = TestDir::new("fail-on-match");
+    dir.write("a.txt", "needle\n");
+
+    let status = |args: &[&str]| dir.grep(args).status.code();
+    assert_eq!(
+        status(&["-q", "--fail-on-match", "needle", "a.txt"]),
+        Some(1)
+    );
+    assert_eq!(status(&["-q", "--fail-on-match", "hay", "a.txt"]), Some(0));
+    assert_eq!(
+        status(&["-q", "--fail-if-no-match", "needle", "a.txt"]),
+        Some(0)
+    );
+    assert_eq!(
+        status(&["-q", "--fail-if-no-match", "hay", "a.txt"]),
+        Some(1)
+    );
+}
+
+#[test]
+fn test_max_allowed_fails_only_above_the_limit() {
+    let dir = TestDir::new("max-allowed");
+    dir.write("a.txt", "needle\nneedle\nhay\nneedle\n");
+
+    let output = dir.grep(&[
+        "--no-summary",
+        "--max-allowed",
+        "3",
+        "-c",
+        "needle",
+        "a.txt",
+    ]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "3\n");
+    assert_eq!(stderr(&output), "");
+
+    let output = dir.grep(&[
+        "--no-summary",
+        "--max-allowed",
+        "2",
+        "-c",
+        "needle",
+        "a.txt",
+    ]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "3\n");
+    assert_eq!(
+        stderr(&output),
+        "3 matches found, more than the 2 allowed\n"
+    );
+}
+
+#[test]
+fn test_context_skips_blank_and_comment_lines() {
+    let dir = TestDir::new("context-skip");
+    dir.write("a.rs", "a\n// c\n\nneedle\n\n// x\nd\ne\n");
+
+    let context = |skip: &[&str]| {
+        let args = [
+            &["--no-summary", "-B", "1", "-A", "1"],
+            skip,
+            &["needle", "a.rs"],
+        ]
+        .concat();
+        stdout(&dir.grep(&args))
+    };
+    assert_eq!(context(&[]), "3:\n4:needle\n5:\n");
+    assert_eq!(
+        context(&["--context-skip-blank"]),
+        "2:// c\n3:\n4:needle\n5:\n6:// x\n"
+    );
+    assert_eq!(
+        context(&["--context-skip-blank", "--context-skip-comments"]),
+        "1:a\n2:// c\n3:\n4:needle\n5:\n6:// x\n7:d\n"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn test_resume_skips_files_searched_before() {
+    let dir = TestDir::new("checkpoint");
+    dir.write("a.txt", "needle a\n")
+        .write("endless.txt", "")
+        .write("c.txt", "needle c\n");
+
+    // The search is cut short in the endless file, which is therefore not
+    // recorded as searched.
+    let args = [
+        "--no-summary",
+        "--checkpoint",
+        "search.checkpoint",
+        "--timeout",
+        "200ms",
+        "--pre-glob",
+        "endless.txt=yes hay",
+        "needle",
+        "a.txt",
+        "endless.txt",
+        "c.txt",
+    ];
+    let output = dir.grep(&args);
+    assert_eq!(stdout(&output), "a.txt:1:needle a\n");
+    assert_eq!(
+        fs::read_to_string(dir.path().join("search.checkpoint")).unwrap(),
+        "# grep-rust checkpoint\na.txt\n"
+    );
+
+    let args = [
+        "--no-summary",
+        "--checkpoint",
+        "search.checkpoint",
+        "--resume",
+        "needle",
+        "a.txt",
+        "c.txt",
+    ];
+    let output = dir.grep(&args);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "c.txt:1:needle c\n");
+
+    // Counts are only printed at the end of the run, so a run killed before
+    // then records no file, while one that ends records them after printing.
+    let args = [
+        "--no-summary",
+        "--checkpoint",
+        "counts.checkpoint",
+        "-c",
+        "--pre-glob",
+        "endless.txt=yes hay",
+        "needle",
+        "a.txt",
+        "endless.txt",
+    ];
+    let mut child = dir.spawn(&args, &[]);
+    thread::sleep(Duration::from_millis(300));
+    child.kill().unwrap();
+    child.wait().unwrap();
+    assert_eq!(
+        fs::read_to_string(dir.path().join("counts.checkpoint")).unwrap(),
+        "# grep-rust checkpoint\n"
+    );
+    let output = dir.grep(&[&["--timeout", "200ms"][..], &args].concat());
+    assert_eq!(stdout(&output), "a.txt:1\n");
+    assert_eq!(
+        fs::read_to_string(dir.path().join("counts.checkpoint")).unwrap(),
+        "# grep-rust checkpoint\na.txt\n"
+    );
+}
+
+#[test]
+fn test_throttle_slows_down_reading() {
+    let dir = TestDir::new("throttle");
+    dir.write("a.txt", format!("{}\nneedle\n", "a".repeat(300 * 1024)));
+
+    // 300 KiB at 1 MiB/s take about 0.3 seconds.
+    let start = Instant::now();
+    let output = dir.grep(&["--no-summary", "--throttle", "1M", "-c", "needle", "a.txt"]);
+    assert!(start.elapsed() >= Duration::from_millis(200));
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "1\n");
+}
+
+#[test]
+fn test_fadvise_backend_finds_the_same_lines() {
+    let dir = TestDir::new("io-backend");
+    dir.write("d/a.txt", "hay\nneedle a\n").write(
+        "d/e/b.txt",
+        format!("{}\nneedle b\n", "x".repeat(100 * 1024)),
+    );
+
+    let search = |backend| {
+        let args = [
+            "--no-summary",
+            "--io-backend",
+            backend,
+            "-d",
+            "recurse",
+            "--sort",
+            "path",
+        ];
+        let output = dir.grep(&[&args[..], &["needle", "d"]].concat());
+        assert_eq!(output.status.code(), Some(0));
+        stdout(&output)
+    };
+    assert_eq!(
+        search("fadvise"),
+        "d/a.txt:2:needle a\nd/e/b.txt:2:needle b\n"
+    );
+    assert_eq!(search("fadvise"), search("std"));
+}
+
+#[test]
+fn test_chunked_reports_byte_offsets() {
+    let dir = TestDir::new("chunked");
+    // The first match straddles the end of the first 1 KiB window.
+    let bundle = format!("{}needle{}needle", "x".repeat(1020), "y".repeat(3000));
+    dir.write("bundle.min.js", bundle);
+
+    let output = dir.grep(&["--no-summary", "--chunked", "1K", "needle", "bundle.min.js"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "1020:needle\n4026:needle\n");
+}
+
+#[test]
+#[cfg(unix)]
+fn test_aliases_of_a_file_are_searched_once_while_walking() {
+    let dir = TestDir::new("aliases");
+    dir.write("d/a.txt", "needle\n")
+        .write("d/other.txt", "needle\n");
+    fs::hard_link(dir.path().join("d/a.txt"), dir.path().join("d/hard.txt")).unwrap();
+    std::os::unix::fs::symlink("a.txt", dir.path().join("d/soft.txt")).unwrap();
+
+    let args = [
+        "--no-summary",
+        "needle",
+        "d/a.txt",
+        "d/hard.txt",
+        "d/soft.txt",
+        "d/other.txt",
+    ];
+    // Like grep, every name given on the command line is searched.
+    let output = dir.grep(&args);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout(&output),
+        "d/a.txt:1:needle\nd/hard.txt:1:needle\nd/soft.txt:1:needle\nd/other.txt:1:needle\n"
+    );
+    let output = dir.grep(&["--no-summary", "needle", "d/a.txt", "d/a.txt"]);
+    assert_eq!(stdout(&output), "d/a.txt:1:needle\nd/a.txt:1:needle\n");
+
+    // While walking, the hard link is searched only once and the symbolic
+    // link is not followed.
+    let output = dir.grep(&[
+        "--no-summary",
+        "-d",
+        "recurse",
+        "--sort",
+        "path",
+        "needle",
+        "d",
+    ]);
+    assert_eq!(stdout(&output), "d/a.txt:1:needle\nd/other.txt:1:needle\n");
+
+    // So is a hard link into another of the walked directories.
+    fs::create_dir(dir.path().join("e")).unwrap();
+    fs::hard_link(dir.path().join("d/a.txt"), dir.path().join("e/link.txt")).unwrap();
+    let output = dir.grep(&["--no-summary", "-d", "recurse", "needle", "d", "e"]);
+    assert_eq!(stdout(&output), "d/a.txt:1:needle\nd/other.txt:1:needle\n");
+}
+
+#[test]
+#[cfg(unix)]
+fn test_fifos_are_read_only_with_devices_read() {
+    let dir = TestDir::new("devices");
+    dir.write("a.txt", "needle a\n");
+    let fifo = dir.path().join("fifo");
+    let status = Command::new("mkfifo").arg(&fifo).status().unwrap();
+    assert!(status.success());
+
+    // Without a writer, opening the FIFO would block forever.
+    let output = dir.grep(&["--no-summary", "needle", "fifo", "a.txt"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "a.txt:1:needle a\n");
+
+    let writer = thread::spawn(move || fs::write(fifo, "needle in fifo\n").unwrap());
+    let output = dir.grep(&["--no-summary", "-D", "read", "needle", "fifo", "a.txt"]);
+    writer.join().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "fifo:1:needle in fifo\na.txt:1:needle a\n");
+}
+
+#[test]
+fn test_context_lines_are_dimmed() {
+    let dir = TestDir::new("dimmed-context");
+    dir.write("a.txt", "before\nneedle\nafter\n");
+
+    let args = [
+        "--no-summary",
+        "--no-smart-defaults",
+        "-B",
+        "1",
+        "-A",
+        "1",
+        "needle",
+        "a.txt",
+    ];
+    let output = dir.grep_with_env(&args, &[("CLICOLOR_FORCE", "1")]);
+    assert_eq!(output.status.code(), Some(0));
+    let lines: Vec<_> = stdout(&output).lines().map(String::from).collect();
+    assert_eq!(
+        lines[lines.len() - 3..],
+        [
+            "\x1b[2mbefore\x1b[0m",
+            "\x1b[1;32mneedle\x1b[0m",
+            "\x1b[2mafter\x1b[0m",
+        ]
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn test_ctrl_c_reports_the_results_found_so_far() {
+    let dir = TestDir::new("interrupt");
+    dir.write("a.txt", "needle\n").write("endless.txt", "");
+
+    let args = [
+        "--pre-glob",
+        "endless.txt=yes hay",
+        "needle",
+        "a.txt",
+        "endless.txt",
+    ];
+    let child = dir.spawn(&args, &[]);
+    thread::sleep(Duration::from_millis(300));
+    let status = Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let output = wait(child, &args);
+    assert_eq!(output.status.code(), Some(130));
+    assert_eq!(stdout(&output), "a.txt:1:needle\n");
+    assert!(
+        stderr(&output).starts_with(
+            "Interrupted, showing the results found so far\n1 matching line in 1 file ("
+        )
+    );
+}
+
+#[test]
+fn test_count_unique_counts_only_the_range() {
+    let dir = TestDir::new("count-unique-range");
+    dir.write("a.txt", "foo 1\nSTART\nfoo 2\nEND\nfoo 3\n");
+
+    let args = [
+        "--no-summary",
+        "--count-unique",
+        "--from",
+        "START",
+        "--until",
+        "END",
+    ];
+    let output = dir.grep(&[&args[..], &["foo", "a.txt"]].concat());
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout(&output).split_whitespace().collect::<Vec<_>>(),
+        ["1", "foo"]
+    );
+}
+
+#[test]
+fn test_invert_match_with_only_matching_holds_nothing() {
+    let dir = TestDir::new("invert-only-matching");
+    dir.write("a.txt", "foo a\nbar\nfoo b\n");
+
+    for mode in [&["--sort-lines"][..], &["--format", "json"]] {
+        let args = [&["--no-summary", "-v", "-o"], mode, &["foo", "a.txt"]].concat();
+        let output = dir.grep(&args);
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(stdout(&output), "", "{args:?}");
+    }
+
+    let output = dir.grep(&["--no-summary", "-v", "--sort-lines", "foo", "a.txt"]);
+    assert_eq!(stdout(&output), "2:bar\n");
+}
+
+#[test]
+fn test_held_matches_respect_baseline_and_section() {
+    let dir = TestDir::new("held-scope");
+    dir.write("a.txt", "foo old\n")
+        .write("s.ini", "[a]\nkey = foo\n[b]\nkey = foo\nother = foo\n");
+    let args = [
+        "--no-summary",
+        "--baseline",
+        "known.txt",
+        "--write-baseline",
+        "foo",
+        "a.txt",
+    ];
+    assert_eq!(dir.grep(&args).status.code(), Some(0));
+    dir.write("a.txt", "foo old\nfoo new\n");
+
+    let args = [
+        "--no-summary",
+        "--baseline",
+        "known.txt",
+        "--format",
+        "json",
+        "-o",
+        "foo",
+        "a.txt",
+    ];
+    let report = stdout(&dir.grep(&args));
+    assert_eq!(report.matches("\"type\": \"match\"").count(), 1);
+    assert!(report.contains("\"line_number\": 2"));
+
+    let args = [
+        "--no-summary",
+        "--section",
+        "a",
+        "--count-unique",
+        "-o",
+        "foo",
+        "s.ini",
+    ];
+    let output = dir.grep(&args);
+    assert_eq!(
+        stdout(&output).split_whitespace().collect::<Vec<_>>(),
+        ["1", "foo"]
+    );
+}
+
+#[test]
+fn test_replace_goes_on_after_an_unreadable_file() {
+    let dir = TestDir::new("replace-missing");
+    dir.write("a.txt", "color a\n").write("c.txt", "color c\n");
+
+    let args = [
+        "--no-summary",
+        "--replace",
+        "colour",
+        "color",
+        "a.txt",
+        "b.txt",
+        "c.txt",
+    ];
+    let output = dir.grep(&args);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).starts_with("b.txt: No such file or directory"));
+    assert_eq!(
+        fs::read_to_string(dir.path().join("a.txt")).unwrap(),
+        "colour a\n"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.path().join("c.txt")).unwrap(),
+        "colour c\n"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn test_replace_skips_fifos() {
+    let dir = TestDir::new("replace-fifo");
+    dir.write("a.txt", "color a\n");
+    let status = Command::new("mkfifo")
+        .arg(dir.path().join("fifo"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    // Without a writer, reading the FIFO would block forever.
+    let output = dir.grep(&[
+        "--no-summary",
+        "--replace",
+        "colour",
+        "color",
+        "fifo",
+        "a.txt",
+    ]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        fs::read_to_string(dir.path().join("a.txt")).unwrap(),
+        "colour a\n"
+    );
+    // Only the file and the FIFO are left, without a temporary file.
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+}
+
+#[test]
+fn test_threads_print_what_a_single_thread_prints() {
+    let dir = TestDir::new("threads");
+    // With pieces of 100 bytes, the file is split into about 20, and most
+    // pieces start or end with a matching line. The last line has no
+    // newline, and every seventh line is blank.
+    let mut text: String = (1..=150)
+        .map(|i| match i % 5 {
+            _ if i % 7 == 0 => "\n".to_string(),
+            0 => format!("{i} error and error\n"),
+            1 | 2 => format!("{i} {} error\n", "x".repeat(i % 13)),
+            _ => format!("{i} ok\n"),
+        })
+        .collect();
+    text.push_str("151 error");
+    dir.write("big.log", &text).write("small.log", "1 error\n");
+    let vars = [("GREP_RUST_PIECE_LEN", "100")];
+
+    for flags in [
+        &["-n"][..],
+        &["-n", "-b"],
+        &["-o", "-n", "-b"],
+        &["-v", "-n"],
+        &["-c"],
+        &["--heading", "-n"],
+    ] {
+        // The empty pattern has only empty matches, which must make blank
+        // lines match as well. `14` first matches after the first piece,
+        // which must not use up the heading.
+        for pattern in ["error", "", "14"] {
+            for files in [
+                &["big.log"][..],
+                &["small.log", "big.log"],
+                &["big.log", "small.log"],
+            ] {
+                let args = [&["--no-summary"][..], flags, &[pattern], files].concat();
+                let single = dir.grep_with_env(&args, &vars);
+                let args = [&["--threads", "4"][..], &args].concat();
+                let threaded = dir.grep_with_env(&args, &vars);
+                assert_eq!(threaded.status.code(), single.status.code(), "{args:?}");
+                assert_eq!(stdout(&threaded), stdout(&single), "{args:?}");
+            }
+        }
+    }
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_since_follows_daylight_saving_time() {
+    let dir = TestDir::new("since-dst");
+    dir.write(
+        "app.log",
+        "2024-01-15 10:00:00 a\n\
+         2024-01-15 11:00:00 b\n\
+         2024-07-01 11:00:00 c\n\
+         2024-07-01 12:00:00 d\n",
+    );
+
+    // Winter times are an hour ahead of UTC in this zone, summer times two.
+    let output = dir.grep_with_env(
+        &[
+            "--since",
+            "2024-01-15T09:30:00Z",
+            "--until-time",
+            "2024-07-01T09:30:00Z",
+            "--no-summary",
+            ":00 ",
+            "app.log",
+        ],
+        &[("TZ", "CET-1CEST,M3.5.0,M10.5.0/3")],
+    );
+    assert_eq!(
+        stdout(&output),
+        "2:2024-01-15 11:00:00 b\n3:2024-07-01 11:00:00 c\n"
+    );
+}
+
+#[test]
+fn test_unordered_threads_print_the_same_lines() {
+    let dir = TestDir::new("unordered");
+    let text: String = (1..=200)
+        .map(|i| format!("{i} {}\n", if i % 3 == 0 { "error" } else { "ok" }))
+        .collect();
+    dir.write("big.log", &text);
+    let vars = [("GREP_RUST_PIECE_LEN", "100")];
+
+    let args = [
+        "--no-summary",
+        "-b",
+        "--no-smart-defaults",
+        "error",
+        "big.log",
+    ];
+    let single = dir.grep_with_env(&args, &vars);
+    let args = [&["--threads", "4", "--unordered"][..], &args].concat();
+    let unordered = dir.grep_with_env(&args, &vars);
+    assert_eq!(unordered.status.code(), Some(0));
+    // The lines after the banner come in any order, each with its byte
+    // offset.
+    let mut lines: Vec<_> = stdout(&unordered).lines().map(String::from).collect();
+    lines[1..].sort_by_key(|line| line.split(':').next().unwrap().parse::<u64>().unwrap());
+    assert_eq!(lines.join("\n") + "\n", stdout(&single));
+}